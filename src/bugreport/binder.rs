@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display, Formatter};
+
+use chrono::Duration;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::locks::{HolderState, LockGraph};
+use super::traces::{AnrTraces, LockRef, ProcessDump, ThreadDump};
+
+lazy_static! {
+    /// `outgoing transaction 2500001: 0000000000000000 from 5140:5140 to 1500:1532 code 3
+    /// flags 10 pri 0:110 r1 elapsed 9012ms node 1024 size 152:8 data 0000000000000000`.
+    /// Kernels without the Android patches print neither the `elapsed` nor a `min:` priority.
+    static ref TRANSACTION: Regex = Regex::new(
+        r"^\s*(?P<kind>outgoing|incoming|pending|bad) transaction (?P<id>\d+): \S+ from (?P<from_pid>\d+):(?P<from_tid>\d+) to (?P<to_pid>\d+):(?P<to_tid>\d+) code (?P<code>[0-9a-f]+) flags (?P<flags>[0-9a-f]+) pri \S+ r\d+(?: elapsed (?P<elapsed>\d+)ms)?"
+    )
+    .unwrap();
+}
+
+/// `TF_ONE_WAY`: the caller does not wait for a reply.
+const FLAG_ONE_WAY: u32 = 0x01;
+
+/// A binder thread, or a process when `tid` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BinderEndpoint {
+    pub pid: u32,
+    pub tid: u32,
+}
+
+impl Display for BinderEndpoint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.pid, self.tid)
+    }
+}
+
+/// Where a transaction line was listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionKind {
+    /// Under the thread that sent it and waits for the reply.
+    Outgoing,
+    /// Under the thread that is handling it.
+    Incoming,
+    /// In the todo list of a process or thread that has not picked it up yet.
+    Pending,
+    Bad,
+}
+
+/// One line of the binder debugfs `transactions` or `state` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinderTransaction {
+    pub id: u64,
+    pub kind: TransactionKind,
+    pub from: BinderEndpoint,
+    /// `tid` is 0 while no thread of the target process has picked the transaction up.
+    pub to: BinderEndpoint,
+    pub code: u32,
+    pub flags: u32,
+    pub elapsed: Option<Duration>,
+}
+
+impl BinderTransaction {
+    pub fn parse(line: &str) -> Option<Self> {
+        let caps = TRANSACTION.captures(line)?;
+        let number = |name: &str| caps[name].parse::<u32>().ok();
+        let kind = match &caps["kind"] {
+            "outgoing" => TransactionKind::Outgoing,
+            "incoming" => TransactionKind::Incoming,
+            "pending" => TransactionKind::Pending,
+            _ => TransactionKind::Bad,
+        };
+        Some(Self {
+            id: caps["id"].parse().ok()?,
+            kind,
+            from: BinderEndpoint {
+                pid: number("from_pid")?,
+                tid: number("from_tid")?,
+            },
+            to: BinderEndpoint {
+                pid: number("to_pid")?,
+                tid: number("to_tid")?,
+            },
+            code: u32::from_str_radix(&caps["code"], 16).ok()?,
+            flags: u32::from_str_radix(&caps["flags"], 16).ok()?,
+            elapsed: caps
+                .name("elapsed")
+                .and_then(|ms| ms.as_str().parse().ok())
+                .map(Duration::milliseconds),
+        })
+    }
+
+    pub fn is_one_way(&self) -> bool {
+        self.flags & FLAG_ONE_WAY != 0
+    }
+}
+
+/// Both sides of one transaction, matched by id. Either may be missing when the file was read
+/// while the transaction was being delivered or answered.
+#[derive(Debug, Clone, Copy)]
+pub struct BinderCall<'b> {
+    pub outgoing: Option<&'b BinderTransaction>,
+    /// The `incoming` line, or the `pending` one while no thread has picked the call up.
+    pub incoming: Option<&'b BinderTransaction>,
+}
+
+impl BinderCall<'_> {
+    fn either(&self) -> &BinderTransaction {
+        self.outgoing.or(self.incoming).unwrap()
+    }
+
+    pub fn id(&self) -> u64 {
+        self.either().id
+    }
+
+    pub fn from(&self) -> BinderEndpoint {
+        self.either().from
+    }
+
+    pub fn to(&self) -> BinderEndpoint {
+        // The incoming side names the thread that picked the call up
+        self.incoming.map_or(self.either().to, |incoming| incoming.to)
+    }
+
+    pub fn code(&self) -> u32 {
+        self.either().code
+    }
+
+    pub fn elapsed(&self) -> Option<Duration> {
+        self.outgoing.and_then(|t| t.elapsed).or_else(|| self.incoming.and_then(|t| t.elapsed))
+    }
+
+    /// Whether the target process has no free binder thread to pick the call up.
+    pub fn is_queued(&self) -> bool {
+        self.to().tid == 0
+    }
+}
+
+/// The transactions of the BINDER TRANSACTIONS or BINDER STATE section.
+#[derive(Debug, Default)]
+pub struct BinderTransactions {
+    transactions: Vec<BinderTransaction>,
+}
+
+impl BinderTransactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keeps the transaction lines; `proc`, `thread`, `node` and `buffer` lines are skipped.
+    pub fn parse(&mut self, lines: &[&str]) {
+        self.transactions
+            .extend(lines.iter().filter_map(|line| BinderTransaction::parse(line)));
+    }
+
+    pub fn transactions(&self) -> &[BinderTransaction] {
+        &self.transactions
+    }
+
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// Every transaction with its outgoing and incoming side, in the order the file lists them.
+    pub fn calls(&self) -> Vec<BinderCall<'_>> {
+        let mut calls: Vec<BinderCall<'_>> = Vec::new();
+        // Index into `calls` per transaction id
+        let mut by_id: HashMap<u64, usize> = HashMap::new();
+        for transaction in self.transactions.iter().filter(|t| t.kind != TransactionKind::Bad) {
+            let index = *by_id.entry(transaction.id).or_insert_with(|| {
+                calls.push(BinderCall {
+                    outgoing: None,
+                    incoming: None,
+                });
+                calls.len() - 1
+            });
+            let call = &mut calls[index];
+            match transaction.kind {
+                TransactionKind::Outgoing => call.outgoing = Some(transaction),
+                TransactionKind::Incoming => call.incoming = Some(transaction),
+                _ => {
+                    call.incoming.get_or_insert(transaction);
+                }
+            }
+        }
+        calls
+    }
+
+    /// The call the thread is blocked in. A thread lists its transaction stack top first, so
+    /// with nested calls this is the latest.
+    pub fn outgoing_call(&self, thread: BinderEndpoint) -> Option<BinderCall<'_>> {
+        let outgoing = self.transactions.iter().find(|t| {
+            t.kind == TransactionKind::Outgoing && t.from == thread && !t.is_one_way()
+        })?;
+        let incoming = self.transactions.iter().find(|t| {
+            t.id == outgoing.id && matches!(t.kind, TransactionKind::Incoming | TransactionKind::Pending)
+        });
+        Some(BinderCall {
+            outgoing: Some(outgoing),
+            incoming,
+        })
+    }
+}
+
+/// A thread dump together with the process it belongs to.
+pub type DumpedThread<'a> = (&'a ProcessDump, &'a ThreadDump);
+
+/// One step of a [`CallChain`].
+#[derive(Debug)]
+pub enum ChainHop<'a> {
+    /// A synchronous binder call; the threads are `None` when the dumps do not have them, as
+    /// for native processes that were not dumped.
+    Binder {
+        call: BinderCall<'a>,
+        caller: Option<DumpedThread<'a>>,
+        callee: Option<DumpedThread<'a>>,
+    },
+    /// A wait for a lock held by another thread of the same process.
+    Lock {
+        process: &'a ProcessDump,
+        waiter: &'a ThreadDump,
+        holder: &'a ThreadDump,
+        lock: &'a LockRef,
+    },
+}
+
+/// What a thread is blocked on, followed across binder calls and lock waits until a thread that
+/// waits for nothing, e.g. app main thread -> system_server binder thread -> vendor HAL.
+#[derive(Debug)]
+pub struct CallChain<'a> {
+    pub hops: Vec<ChainHop<'a>>,
+    /// The thread at the end of the chain, or the process when a call is still queued.
+    pub root: BinderEndpoint,
+    pub root_thread: Option<DumpedThread<'a>>,
+}
+
+/// The dump of the thread `endpoint` names; a process's main thread has the pid as its tid.
+fn find_thread(traces: &AnrTraces, endpoint: BinderEndpoint) -> Option<DumpedThread<'_>> {
+    traces.get_process(endpoint.pid).find_map(|process| {
+        process
+            .get_thread_by_sys_tid(endpoint.tid)
+            .map(|thread| (process, thread))
+    })
+}
+
+impl<'a> CallChain<'a> {
+    /// Follows `start` through the binder calls it is blocked in and, in every process it
+    /// reaches, through the lock chain of the thread serving the call. `None` if `start`
+    /// waits for nothing.
+    pub fn trace(binder: &'a BinderTransactions, traces: &'a AnrTraces, start: BinderEndpoint) -> Option<Self> {
+        let mut hops = Vec::new();
+        let mut visited = HashSet::new();
+        let mut current = start;
+        while visited.insert(current) {
+            let thread = find_thread(traces, current);
+            if let Some(call) = binder.outgoing_call(current) {
+                let to = call.to();
+                hops.push(ChainHop::Binder {
+                    call,
+                    caller: thread,
+                    callee: find_thread(traces, to),
+                });
+                current = to;
+                if call.is_queued() {
+                    break;
+                }
+                continue;
+            }
+
+            let Some((process, thread)) = thread else { break };
+            let graph = LockGraph::build(process);
+            let Some(chain) = graph.position(thread).and_then(|position| graph.chain_from(position)) else {
+                break;
+            };
+            for (pair, lock) in chain.threads.windows(2).zip(&chain.locks) {
+                hops.push(ChainHop::Lock {
+                    process,
+                    waiter: pair[0],
+                    holder: pair[1],
+                    lock,
+                });
+            }
+            match chain.root().sys_tid {
+                Some(tid) => current = BinderEndpoint { pid: process.pid, tid },
+                None => break,
+            }
+        }
+
+        (!hops.is_empty()).then(|| CallChain {
+            hops,
+            root: current,
+            root_thread: find_thread(traces, current),
+        })
+    }
+
+    /// What the root is doing; `None` when it was not dumped.
+    pub fn root_state(&self) -> Option<HolderState> {
+        self.root_thread.map(|(_, thread)| HolderState::of(thread))
+    }
+
+    /// The threads the chain reaches that the dumps have, in chain order, without the start.
+    pub fn threads(&self) -> Vec<DumpedThread<'a>> {
+        self.hops
+            .iter()
+            .filter_map(|hop| match hop {
+                ChainHop::Binder { callee, .. } => *callee,
+                ChainHop::Lock { process, holder, .. } => Some((*process, *holder)),
+            })
+            .collect()
+    }
+}
+
+fn describe(endpoint: BinderEndpoint, thread: Option<DumpedThread<'_>>) -> String {
+    match thread {
+        Some((_, thread)) => format!("{} \"{}\"", endpoint, thread.name),
+        None => endpoint.to_string(),
+    }
+}
+
+fn endpoint_of(process: &ProcessDump, thread: &ThreadDump) -> BinderEndpoint {
+    BinderEndpoint {
+        pid: process.pid,
+        tid: thread.sys_tid.unwrap_or(0),
+    }
+}
+
+impl Display for ChainHop<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ChainHop::Binder { call, caller, callee } => {
+                write!(
+                    f,
+                    "{} -> {} binder call {} code {:#x}",
+                    describe(call.from(), *caller),
+                    describe(call.to(), *callee),
+                    call.id(),
+                    call.code()
+                )?;
+                if let Some(elapsed) = call.elapsed() {
+                    write!(f, " for {}ms", elapsed.num_milliseconds())?;
+                }
+                if call.is_queued() {
+                    write!(f, ", no thread has picked it up")?;
+                }
+                Ok(())
+            }
+            ChainHop::Lock { process, waiter, holder, lock } => write!(
+                f,
+                "{} -> {} waits for lock <{}>",
+                describe(endpoint_of(process, waiter), Some((process, waiter))),
+                describe(endpoint_of(process, holder), Some((process, holder))),
+                lock.address
+            ),
+        }
+    }
+}
+
+impl Display for CallChain<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for hop in &self.hops {
+            writeln!(f, "  {}", hop)?;
+        }
+        match self.root_state() {
+            Some(state) => writeln!(f, "  root {} is {}", describe(self.root, self.root_thread), state),
+            None => writeln!(f, "  root {} was not dumped", self.root),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::traces::TEST_TRACES;
+
+    /// system_server main waits for a lock held by a binder thread that calls into the power
+    /// HAL; the app's call into system_server waits in its queue.
+    const TRANSACTIONS: &str = r#"binder transactions:
+proc 5140
+context binder
+  thread 5140: l 10 need_return 0 tr 0
+    outgoing transaction 2500001: 0000000000000000 from 5140:5140 to 1500:0 code 3 flags 10 pri 0:110 r1 elapsed 9012ms node 1024 size 152:8 data 0000000000000000
+  buffer 2499000: 0000000000000000 size 4:0:0 delivered
+proc 1500
+context binder
+  thread 1532: l 11 need_return 0 tr 0
+    outgoing transaction 2500007: 0000000000000000 from 1500:1532 to 800:800 code 1 flags 10 pri 0:110 r1 elapsed 8990ms node 3 size 92:0 data 0000000000000000
+  pending transaction 2500001: 0000000000000000 from 5140:5140 to 1500:0 code 3 flags 10 pri 0:110 r1 elapsed 9012ms node 1024 size 152:8 data 0000000000000000
+  pending transaction 2500020: 0000000000000000 from 0:0 to 1500:0 code 1f flags 11 pri 0:120 r0 node 77 size 60:0 data 0000000000000000
+proc 800
+context binder
+  thread 800: l 12 need_return 0 tr 0
+    incoming transaction 2500007: 0000000000000000 from 1500:1532 to 800:800 code 1 flags 10 pri 0:110 r1 node 3 size 92:0 data 0000000000000000
+"#;
+
+    fn load() -> (BinderTransactions, AnrTraces) {
+        let mut binder = BinderTransactions::new();
+        binder.parse(&TRANSACTIONS.lines().collect::<Vec<_>>());
+        (binder, AnrTraces::from_bytes(TEST_TRACES.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_parse() {
+        let (binder, _) = load();
+        assert_eq!(binder.len(), 5);
+        let first = &binder.transactions()[0];
+        assert_eq!(first.kind, TransactionKind::Outgoing);
+        assert_eq!((first.from.to_string(), first.to.to_string()), ("5140:5140".to_string(), "1500:0".to_string()));
+        assert_eq!((first.code, first.elapsed), (3, Some(Duration::milliseconds(9012))));
+        assert!(binder.transactions()[3].is_one_way());
+        assert_eq!(binder.transactions()[4].elapsed, None);
+
+        let calls = binder.calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].incoming.map(|t| t.kind), Some(TransactionKind::Pending));
+        assert!(calls[0].is_queued());
+        assert_eq!(calls[1].to(), BinderEndpoint { pid: 800, tid: 800 });
+        assert!(calls[2].outgoing.is_none());
+    }
+
+    #[test]
+    fn test_chain() {
+        let (binder, traces) = load();
+        let main = BinderEndpoint { pid: 1500, tid: 1500 };
+        let chain = CallChain::trace(&binder, &traces, main).unwrap();
+        assert_eq!(chain.hops.len(), 2);
+        assert_eq!(chain.root, BinderEndpoint { pid: 800, tid: 800 });
+        let names: Vec<&str> = chain.threads().iter().map(|(_, thread)| thread.name.as_str()).collect();
+        assert_eq!(names, vec!["Binder:1500_3", "android.hardwar"]);
+        assert_eq!(
+            chain.to_string(),
+            "  1500:1500 \"main\" -> 1500:1532 \"Binder:1500_3\" waits for lock <0x0c2a1b3f>\n  1500:1532 \"Binder:1500_3\" -> 800:800 \"android.hardwar\" binder call 2500007 code 0x1 for 8990ms\n  root 800:800 \"android.hardwar\" is in native code\n"
+        );
+
+        // The app's call waits in system_server's queue, so the chain stops there
+        let app = CallChain::trace(&binder, &traces, BinderEndpoint { pid: 5140, tid: 5140 }).unwrap();
+        assert_eq!(app.hops.len(), 1);
+        assert_eq!(app.root, BinderEndpoint { pid: 1500, tid: 0 });
+        assert!(app.to_string().ends_with("for 9012ms, no thread has picked it up\n  root 1500:0 was not dumped\n"));
+
+        assert!(CallChain::trace(&binder, &traces, BinderEndpoint { pid: 800, tid: 800 }).is_none());
+    }
+}
@@ -1,30 +1,26 @@
-use glob::glob;
-use std::fs::{self, File};
-use std::io;
-use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Read;
+use std::ops::Deref;
+use std::path::Path;
+use zip::result::ZipError;
 use zip::ZipArchive;
 
 use super::bugreport_txt::BugreportTxt;
-
-struct BugreportDirs {
-    bugreport_txt: PathBuf,
-    anr_files_dir: PathBuf,
-    miuilog_reboot_dir: PathBuf,
-    miuilog_scout_dir: PathBuf,
-}
-
-impl BugreportDirs {
-    fn new() -> Self {
-        BugreportDirs {
-            bugreport_txt: PathBuf::new(),
-            anr_files_dir: PathBuf::new(),
-            miuilog_reboot_dir: PathBuf::new(),
-            miuilog_scout_dir: PathBuf::new(),
-        }
-    }
-}
-
+use super::clock::TimeDisplay;
+use super::logcat::event::EventLogTags;
+use crate::error::{Error, Result};
+
+const MAIN_ENTRY: &str = "main_entry.txt";
+const ANR_FILES_DIR: &str = "FS/data/anr/";
+const MIUILOG_REBOOT_DIR: &str = "FS/data/miuilog/stability/reboot/";
+const MIUILOG_SCOUT_DIR: &str = "FS/data/miuilog/stability/scout/";
+const EVENT_LOG_TAGS: &str = "FS/system/etc/event-log-tags";
+
+/// A dumpstate zip read in place: the main `bugreport-*.txt` is loaded into memory and every
+/// other entry stays reachable by its name inside the archive, so nothing is unpacked to disk.
+/// A plain bugreport text opens as a report without entries.
 pub struct Bugreport {
+    archive: Option<ZipArchive<File>>,
     bugreport_txt: BugreportTxt,
     anr_files: Vec<String>,
     miuilog_reboots: Vec<String>,
@@ -32,128 +28,224 @@ pub struct Bugreport {
 }
 
 impl Bugreport {
-    pub fn new(bugreport_zip_path: &Path) -> Self {
-        let bugreport_txt = BugreportTxt::new(bugreport_zip_path).unwrap();
-        Bugreport {
+    pub fn new(bugreport_zip_path: &Path) -> Result<Self> {
+        let mut archive = ZipArchive::new(File::open(bugreport_zip_path)?)?;
+        let main_entry = Self::find_main_entry(&mut archive)?;
+        let bugreport_txt = BugreportTxt::from_bytes(Self::read_from(&mut archive, &main_entry)?);
+        Ok(Bugreport {
+            archive: Some(archive),
             bugreport_txt,
             anr_files: Vec::new(),
             miuilog_reboots: Vec::new(),
             miuilog_scouts: Vec::new(),
-        }
+        })
     }
 
-    fn unzip(path: &Path) -> io::Result<()> {
-        // Unzip the bug report file
-        let zip_file = std::fs::File::open(path)?;
-        let mut archive = zip::ZipArchive::new(zip_file)?;
-        let base_dir = path
-            .file_stem()
-            .unwrap_or_else(|| std::ffi::OsStr::new("."));
-        for i in 0..archive.len() {
-            let mut file = archive.by_index(i)?;
-            let out_path = Path::new(base_dir).join(file.name());
-            if file.is_dir() {
-                std::fs::create_dir_all(&out_path)?;
-            } else {
-                let mut outfile = std::fs::File::create(&out_path)?;
-                io::copy(&mut file, &mut outfile)?;
+    /// Opens a bugreport text that did not come in a zip.
+    pub fn from_text(bugreport_txt_path: &Path) -> Result<Self> {
+        Ok(Bugreport {
+            archive: None,
+            bugreport_txt: BugreportTxt::new(bugreport_txt_path)?,
+            anr_files: Vec::new(),
+            miuilog_reboots: Vec::new(),
+            miuilog_scouts: Vec::new(),
+        })
+    }
+
+    /// `main_entry.txt` holds the name of the main bugreport text. Older zips lack it, in which
+    /// case the first top-level `bugreport*.txt` entry is used instead.
+    fn find_main_entry(archive: &mut ZipArchive<File>) -> Result<String> {
+        if archive.index_for_name(MAIN_ENTRY).is_some() {
+            let name = String::from_utf8_lossy(&Self::read_from(archive, MAIN_ENTRY)?)
+                .trim()
+                .to_string();
+            if archive.index_for_name(&name).is_some() {
+                return Ok(name);
             }
         }
-        Ok(())
+
+        archive
+            .file_names()
+            .find(|name| !name.contains('/') && name.starts_with("bugreport") && name.ends_with(".txt"))
+            .map(str::to_string)
+            .ok_or(Error::Archive(ZipError::FileNotFound))
     }
 
-    pub fn load(&mut self) -> io::Result<()> {
+    fn read_from(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>> {
+        let mut entry = archive.by_name(name)?;
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    /// Chooses the offset times are expressed in, see [`BugreportTxt::set_time_display`].
+    pub fn set_time_display(&mut self, display: TimeDisplay) {
+        self.bugreport_txt.set_time_display(display);
+    }
+
+    pub fn load(&mut self) -> Result<()> {
         // Load the bug report and extract relevant information
         self.bugreport_txt.load()?;
-        // self.anr_files = self.bugreport_txt.get_anr_files();
-        // self.miuilog_reboots = self.bugreport_txt.get_miuilog_reboots();
-        // self.miuilog_scouts = self.bugreport_txt.get_miuilog_scouts();
-        Ok(())
+        self.index_entries();
+        self.load_event_tags()
+    }
+
+    /// Like `load()`, but goes through the parse cache at `cache_path`.
+    pub fn load_cached(&mut self, cache_path: &Path) -> Result<bool> {
+        let hit = self.bugreport_txt.load_cached(cache_path)?;
+        self.index_entries();
+        self.load_event_tags()?;
+        Ok(hit)
     }
-}
 
-fn extract(
-    feedback_id: u64,
-    bugreport_zip: &Path,
-    user_feedback_path: &Path,
-) -> Result<BugreportDirs, Box<dyn std::error::Error>> {
-    // Create feedback directory
-    let feedback_dir = user_feedback_path.join(feedback_id.to_string());
-    fs::create_dir_all(&feedback_dir)?;
-
-    // Extract and remove the initial bugreport zip
-    if let Err(e) = unzip_and_delete(bugreport_zip, &feedback_dir) {
-        eprintln!("Failed to extract initial bugreport: {}", e);
-    }
-
-    // Find subsequent bugreport zip
-    let pattern = feedback_dir.join("bugreport*.zip");
-    let matches = glob(pattern.to_str().unwrap())?;
-    let bugreport_zip_path = matches.filter_map(Result::ok).next();
-
-    let bugreport_zip_path = match bugreport_zip_path {
-        Some(path) => path,
-        None => {
-            eprintln!("No bugreport*.zip found");
-            // TODO: modify this to actual paths
-            return Ok(BugreportDirs {
-                bugreport_txt: feedback_dir.join("bugreport.txt"),
-                anr_files_dir: feedback_dir.join("anr_files"),
-                miuilog_reboot_dir: feedback_dir.join("miuilog_reboot"),
-                miuilog_scout_dir: feedback_dir.join("miuilog_scout"),
-            });
+    /// Layers the device's own event tag definitions over the built-in ones, if the zip has
+    /// them.
+    fn load_event_tags(&mut self) -> Result<()> {
+        let Some(archive) = self.archive.as_mut() else {
+            return Ok(());
+        };
+        if archive.index_for_name(EVENT_LOG_TAGS).is_none() {
+            return Ok(());
         }
-    };
+        let device_tags = Self::read_from(archive, EVENT_LOG_TAGS)?;
+        let mut tags = EventLogTags::builtin();
+        tags.extend(EventLogTags::parse(&String::from_utf8_lossy(&device_tags)));
+        self.bugreport_txt.set_event_tags(tags);
+        Ok(())
+    }
 
-    // Create extraction directory
-    let bugreport_dir = feedback_dir.join(
-        bugreport_zip_path
-            .file_stem()
-            .ok_or("Invalid zip filename")?
-            .to_str()
-            .ok_or("Non-UTF8 filename")?,
-    );
-    fs::create_dir_all(&bugreport_dir)?;
-
-    // Extract and remove secondary zip
-    if let Err(e) = unzip_and_delete(&bugreport_zip_path, &bugreport_dir) {
-        eprintln!("Failed to extract secondary bugreport: {}", e);
-    }
-
-    // Check for reboot directory
-    let reboot_mqs_dir = bugreport_dir
-        .join("FS")
-        .join("data")
-        .join("miuilog")
-        .join("stability")
-        .join("reboot");
-
-    if reboot_mqs_dir.is_dir() {
-        let zip_pattern = reboot_mqs_dir.join("*.zip");
-        for zip_path in glob(zip_pattern.to_str().unwrap())?.filter_map(Result::ok) {
-            let extract_dir = zip_path.with_extension("");
-            fs::create_dir_all(&extract_dir)?;
-            if let Err(e) = unzip_and_delete(&zip_path, &extract_dir) {
-                eprintln!("Failed to extract nested zip: {}", e);
-            }
+    fn index_entries(&mut self) {
+        self.anr_files = self.entries_under(ANR_FILES_DIR);
+        self.miuilog_reboots = self.entries_under(MIUILOG_REBOOT_DIR);
+        self.miuilog_scouts = self.entries_under(MIUILOG_SCOUT_DIR);
+    }
+
+    fn entries_under(&self, dir: &str) -> Vec<String> {
+        self.entry_names()
+            .filter(|name| name.starts_with(dir) && !name.ends_with('/'))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Names of every entry in the archive, e.g. `FS/data/anr/anr_2024-08-16-10-01-30-123`.
+    /// Empty for a plain bugreport text.
+    pub fn entry_names(&self) -> impl Iterator<Item = &str> {
+        self.archive.iter().flat_map(ZipArchive::file_names)
+    }
+
+    /// Reads one entry of the archive into memory.
+    pub fn read_entry(&mut self, name: &str) -> Result<Vec<u8>> {
+        match self.archive.as_mut() {
+            Some(archive) => Self::read_from(archive, name),
+            None => Err(Error::Archive(ZipError::FileNotFound)),
         }
-    } else {
-        eprintln!("No reboot directory found");
     }
 
-    // TODO: modify this to actual paths
-    Ok(BugreportDirs {
-        bugreport_txt: feedback_dir.join("bugreport.txt"),
-        anr_files_dir: feedback_dir.join("anr_files"),
-        miuilog_reboot_dir: feedback_dir.join("miuilog_reboot"),
-        miuilog_scout_dir: feedback_dir.join("miuilog_scout"),
-    })
+    pub fn get_bugreport_txt(&self) -> &BugreportTxt {
+        &self.bugreport_txt
+    }
+
+    pub fn into_bugreport_txt(self) -> BugreportTxt {
+        self.bugreport_txt
+    }
+
+    pub fn get_anr_files(&self) -> &Vec<String> {
+        &self.anr_files
+    }
+
+    pub fn get_miuilog_reboots(&self) -> &Vec<String> {
+        &self.miuilog_reboots
+    }
+
+    pub fn get_miuilog_scouts(&self) -> &Vec<String> {
+        &self.miuilog_scouts
+    }
+}
+
+impl Deref for Bugreport {
+    type Target = BugreportTxt;
+
+    fn deref(&self) -> &BugreportTxt {
+        &self.bugreport_txt
+    }
+}
+
+/// Opens either a dumpstate zip or a plain bugreport text and loads it, through the parse cache
+/// at `cache_path` when one is given. Entries of a zip stay readable through the result.
+pub fn open(path: &Path, cache_path: Option<&Path>, display: TimeDisplay) -> Result<Bugreport> {
+    let is_zip = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("zip"));
+    let mut bugreport = if is_zip {
+        Bugreport::new(path)?
+    } else {
+        Bugreport::from_text(path)?
+    };
+    bugreport.set_time_display(display);
+    match cache_path {
+        Some(cache_path) => bugreport.load_cached(cache_path).map(|_| ())?,
+        None => bugreport.load()?,
+    }
+    Ok(bugreport)
 }
 
-fn unzip_and_delete(zip_path: &Path, dest_dir: &Path) -> io::Result<()> {
-    let file = File::open(zip_path)?;
-    let mut archive = ZipArchive::new(file)?;
-    archive.extract(dest_dir)?;
-    fs::remove_file(zip_path)?;
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn write_test_zip(path: &Path, with_main_entry: bool) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = SimpleFileOptions::default();
+        if with_main_entry {
+            writer.start_file(MAIN_ENTRY, options).unwrap();
+            writer.write_all(b"bugreport-test-2024-08-16-10-02-11.txt").unwrap();
+        }
+        writer.start_file("bugreport-test-2024-08-16-10-02-11.txt", options).unwrap();
+        writer
+            .write_all(
+                b"== dumpstate: 2024-08-16 10:02:11\n\
+                  Build fingerprint: 'Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys'\n\
+                  Uptime: up 0 weeks, 0 days, 0 hours, 32 minutes\n",
+            )
+            .unwrap();
+        writer.start_file("FS/data/anr/anr_2024-08-16-10-01-30-123", options).unwrap();
+        writer.write_all(b"----- pid 5140 at 2024-08-16 10:01:30 -----\n").unwrap();
+        writer.start_file(EVENT_LOG_TAGS, options).unwrap();
+        writer.write_all(b"70000 screen_toggled (state|1|5)\n99999 vendor_event (value|3)\n").unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_load_from_zip() {
+        let dir = std::env::temp_dir().join(format!("bugreport_zip_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for with_main_entry in [true, false] {
+            let zip_path = dir.join(format!("bugreport-{}.zip", with_main_entry));
+            write_test_zip(&zip_path, with_main_entry);
+
+            let mut bugreport = Bugreport::new(&zip_path).unwrap();
+            bugreport.load().unwrap();
+            assert_eq!(bugreport.get_bugreport_txt().get_metadata().version, "OS2.0.107.0.VOBCNXM");
+            assert_eq!(
+                bugreport.get_anr_files(),
+                &vec!["FS/data/anr/anr_2024-08-16-10-01-30-123".to_string()]
+            );
+            let anr = bugreport.read_entry("FS/data/anr/anr_2024-08-16-10-01-30-123").unwrap();
+            assert!(anr.starts_with(b"----- pid 5140"));
+            let tags = bugreport.get_bugreport_txt().get_event_tags();
+            assert!(tags.get("vendor_event").is_some());
+            assert_eq!(tags.get("screen_toggled").unwrap().fields[0].name, "state");
+            assert!(tags.get("am_anr").is_some());
+
+            // Opening through `open` keeps the archive reachable
+            let mut opened = open(&zip_path, None, TimeDisplay::Device).unwrap();
+            assert_eq!(opened.get_metadata().version, "OS2.0.107.0.VOBCNXM");
+            assert!(opened.read_entry(EVENT_LOG_TAGS).is_ok());
+        }
+        // Nothing besides the zips themselves may be written next to them
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,117 +1,377 @@
+use std::borrow::Cow;
 use std::fs::File;
 use std::io;
+use std::ops::Deref;
 use std::path::Path;
+use std::sync::OnceLock;
 
-use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone};
+use chrono::Duration;
+use chrono_tz::Tz;
 use memmap2::Mmap;
 
-use super::dumpsys::Dumpsys;
-use super::logcat::{LogcatLine, LogcatSection};
+use super::binder::BinderTransactions;
+use super::cache::{CacheKey, CachedReport, CachedSection};
+use super::clock::{ReportClock, TimeDisplay, TimezoneSource, TIMEZONE_PROPERTY};
+use super::decode::{self, DecodeMode, RawLines};
+use super::logcat::event::{EventLogTags, EventRecord};
+use super::logcat::window::TimeWindow;
+use super::logcat::{LogBuffer, LogcatLine};
 use super::metadata::Metadata;
-use super::section::{Section, SectionContent, SECTION_BEGIN, SECTION_BEGIN_NO_CMD, SECTION_END};
+use super::process::ProcessTable;
+use super::properties::Properties;
+use super::query::Query;
+use super::section::{
+    parse_seconds, Section, SectionContent, SECTION_BEGIN, SECTION_BEGIN_NO_CMD, SECTION_END,
+};
+use super::timeline::Timeline;
+use super::traces::AnrTraces;
+use crate::error::Result;
+
+fn is_log_section(name: &str) -> bool {
+    name == "SYSTEM LOG" || name == "EVENT LOG" || name == "KERNEL LOG"
+}
+
+fn is_process_section(name: &str) -> bool {
+    name == "PROCESSES AND THREADS" || name == "PROCESSES"
+}
+
+/// A line that opens or closes a section, as found while indexing the report.
+#[derive(Debug)]
+struct SectionMarker {
+    /// 1-based line number of the marker.
+    line: usize,
+    /// Byte offset of the start of the marker line.
+    start: usize,
+    /// Byte offset right after the marker line.
+    end: usize,
+    name: String,
+    command: Option<String>,
+    /// Only closing markers carry a duration.
+    duration: Option<Duration>,
+}
+
+/// The bytes of a bugreport text, either mapped from a file on disk or read out of a zip entry.
+#[derive(Debug)]
+pub enum RawFile {
+    Mapped(Mmap),
+    Owned(Vec<u8>),
+}
+
+impl Deref for RawFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            RawFile::Mapped(mmap) => mmap,
+            RawFile::Owned(bytes) => bytes,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct BugreportTxt {
-    pub raw_file: Mmap,
+    pub raw_file: RawFile,
     pub metadata: Metadata,
     pub sections: Vec<Section>,
+    decode_mode: DecodeMode,
+    time_display: TimeDisplay,
+    repaired_lines: Vec<usize>,
+    process_table: OnceLock<ProcessTable>,
+    event_tags: EventLogTags,
 }
 
 impl BugreportTxt {
-    pub fn new(path: &Path) -> io::Result<Self> {
+    pub fn new(path: &Path) -> Result<Self> {
         // Open the file
         let raw_file = File::open(path)?;
         let mmap_file = unsafe { Mmap::map(&raw_file)? };
-        Ok(BugreportTxt {
-            raw_file: mmap_file,
+        Ok(Self::from_raw(RawFile::Mapped(mmap_file)))
+    }
+
+    /// Wraps a bugreport text that is already in memory, e.g. the main entry of a dumpstate zip.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self::from_raw(RawFile::Owned(bytes))
+    }
+
+    fn from_raw(raw_file: RawFile) -> Self {
+        BugreportTxt {
+            raw_file,
             metadata: Metadata::new(),
             sections: Vec::new(),
-        })
+            decode_mode: DecodeMode::default(),
+            time_display: TimeDisplay::default(),
+            repaired_lines: Vec::new(),
+            process_table: OnceLock::new(),
+            event_tags: EventLogTags::builtin(),
+        }
     }
 
-    pub fn load(&mut self) -> io::Result<()> {
-        let result = self.read_and_slice()?;
-        self.pair_sections(&result);
+    /// Chooses how invalid UTF-8 is handled by the next `load()`.
+    pub fn set_decode_mode(&mut self, mode: DecodeMode) {
+        self.decode_mode = mode;
+    }
+
+    /// Chooses the offset times are expressed in by the next `load()`.
+    pub fn set_time_display(&mut self, display: TimeDisplay) {
+        self.time_display = display;
+    }
+
+    /// Replaces the definitions EVENT LOG payloads are decoded with, e.g. with the device's own
+    /// `event-log-tags` layered over the built-in ones.
+    pub fn set_event_tags(&mut self, tags: EventLogTags) {
+        self.event_tags = tags;
+    }
+
+    pub fn get_event_tags(&self) -> &EventLogTags {
+        &self.event_tags
+    }
+
+    /// 1-based numbers of the lines that were not valid UTF-8 and had to be repaired, covering
+    /// the header and every section parsed so far.
+    pub fn get_repaired_lines(&self) -> Vec<usize> {
+        let mut lines = self.repaired_lines.clone();
+        for section in &self.sections {
+            lines.extend_from_slice(section.get_repaired_lines());
+        }
+        lines
+    }
+
+    /// Reads the header and indexes the sections. Section contents are only parsed when they
+    /// are first accessed through [`BugreportTxt::get_content`].
+    pub fn load(&mut self) -> Result<()> {
+        let markers = self.read_and_slice()?;
+        self.pair_sections(&markers)?;
+        let (timezone, source) = self.find_timezone();
+        let clock = ReportClock::new(timezone, self.time_display).with_source(source);
+        self.metadata.set_clock(clock);
         Ok(())
     }
 
-    fn read_and_slice(&mut self) -> io::Result<Vec<(usize, String)>> {
-        let bugreport = std::str::from_utf8(&self.raw_file).unwrap();
-        let mut lines = bugreport.lines();
+    /// The device timezone from `persist.sys.timezone` in SYSTEM PROPERTIES, else from the
+    /// dumpstate header. `None` with [`TimezoneSource::Guessed`] if the report gives neither.
+    fn find_timezone(&self) -> (Option<Tz>, TimezoneSource) {
+        // A properties section that cannot be decoded leaves the header to go by
+        let property = self.property(TIMEZONE_PROPERTY).ok().flatten();
+        if let Some(timezone) = property.and_then(ReportClock::parse_timezone) {
+            return (Some(timezone), TimezoneSource::Property);
+        }
+        match self.metadata.timezone.as_deref().and_then(ReportClock::parse_timezone) {
+            Some(timezone) => (Some(timezone), TimezoneSource::Header),
+            None => (None, TimezoneSource::Guessed),
+        }
+    }
 
-        self.metadata.parse(&mut lines)?;
+    /// Like `load()`, but reuses the parse stored in the cache file at `cache_path` when it was
+    /// written for exactly these bytes. On a miss every log section is parsed and the cache is
+    /// rewritten. Returns whether the cache was used.
+    pub fn load_cached(&mut self, cache_path: &Path) -> Result<bool> {
+        let key = CacheKey::new(&self.raw_file, self.decode_mode, self.time_display);
+        if let Some(cached) = CachedReport::read(cache_path, &key) {
+            self.restore(cached);
+            return Ok(true);
+        }
 
-        // Create a vector to store the line number and content of each match
-        let mut matches: Vec<(usize, String)> = Vec::new();
-        let filter_and_add =
-            |matches: &mut Vec<(usize, String)>, line_number: usize, group: &str| match group {
-                "BLOCK STAT" => {}
-                l if l.ends_with("PROTO") => {}
-                _ => {
-                    matches.push((line_number, group.to_string()));
+        self.load()?;
+        for section in self.sections.iter().filter(|s| is_log_section(&s.name)) {
+            self.get_content(section)?;
+        }
+        // A cache that cannot be written only costs the next open a full parse
+        let _ = self.to_cache(key).write(cache_path);
+        Ok(false)
+    }
+
+    fn to_cache(&self, key: CacheKey) -> CachedReport<'_> {
+        let sections = self
+            .sections
+            .iter()
+            .map(|section| {
+                let byte_range = section.get_byte_range();
+                CachedSection {
+                    name: section.name.clone(),
+                    command: section.command.clone(),
+                    start_line: *section.get_line_range().start(),
+                    end_line: *section.get_line_range().end(),
+                    byte_start: byte_range.start,
+                    byte_end: byte_range.end,
+                    duration_us: section.get_duration().and_then(|d| d.num_microseconds()),
+                    logcat: section
+                        .content()
+                        .and_then(SectionContent::as_logcat)
+                        .map(Cow::Borrowed),
+                    repaired_lines: section.get_repaired_lines().to_vec(),
                 }
-            };
+            })
+            .collect();
+        CachedReport {
+            key,
+            metadata: Cow::Borrowed(&self.metadata),
+            repaired_lines: self.repaired_lines.clone(),
+            sections,
+        }
+    }
+
+    fn restore(&mut self, cached: CachedReport<'static>) {
+        self.metadata = cached.metadata.into_owned();
+        self.repaired_lines = cached.repaired_lines;
+        self.sections = cached
+            .sections
+            .into_iter()
+            .map(|cached| {
+                let section = Section::new(
+                    cached.name,
+                    cached.command,
+                    cached.start_line,
+                    cached.end_line,
+                    cached.byte_start..cached.byte_end,
+                    cached.duration_us.map(Duration::microseconds),
+                );
+                if let Some(logcat) = cached.logcat {
+                    let content = match SectionContent::for_name(&section.name) {
+                        SectionContent::EventLog(_) => SectionContent::EventLog(logcat.into_owned()),
+                        SectionContent::KernelLog(_) => SectionContent::KernelLog(logcat.into_owned()),
+                        _ => SectionContent::SystemLog(logcat.into_owned()),
+                    };
+                    section.set_content(content, cached.repaired_lines);
+                }
+                section
+            })
+            .collect();
+    }
+
+    fn read_and_slice(&mut self) -> Result<Vec<SectionMarker>> {
+        // The header is everything before the first section marker
+        let mut scan = RawLines::new(&self.raw_file);
+        let header_end = loop {
+            let start = scan.offset();
+            match scan.next() {
+                Some(bytes) if !bytes.starts_with(b"------ ") => continue,
+                _ => break start,
+            }
+        };
+        let mut metadata = Metadata::new();
+        let mut repaired_lines = Vec::new();
+        let mut decode_error = None;
+        let header = {
+            let header_lines = RawLines::new(&self.raw_file[..header_end]);
+            let mut lines = header_lines.enumerate().map_while(|(index, bytes)| {
+                match decode::decode_line(bytes, self.decode_mode) {
+                    Ok((line, was_repaired)) => {
+                        if was_repaired {
+                            repaired_lines.push(index + 1);
+                        }
+                        Some(line)
+                    }
+                    Err(e) => {
+                        decode_error = Some(e.at_line(index + 1));
+                        None
+                    }
+                }
+            });
+            metadata.parse(&mut lines)
+        };
+        if let Some(e) = decode_error {
+            return Err(e);
+        }
+        header?;
+
+        // Create a vector to store the position and name of each section marker
+        let mut matches: Vec<SectionMarker> = Vec::new();
+        let filter_and_add = |matches: &mut Vec<SectionMarker>, marker: SectionMarker| match marker
+            .name
+            .as_str()
+        {
+            "BLOCK STAT" => {}
+            l if l.ends_with("PROTO") => {}
+            _ => {
+                matches.push(marker);
+            }
+        };
 
-        for (line_number, line) in lines.enumerate() {
-            let line_number = line_number + self.metadata.lines_passed;
+        let mut raw_lines = RawLines::new(&self.raw_file[header_end..]);
+        let mut line_number = metadata.lines_passed;
+        loop {
+            let start = header_end + raw_lines.offset();
+            let Some(bytes) = raw_lines.next() else {
+                break;
+            };
+            line_number += 1;
+            // Only marker lines are decoded here, everything else waits for its section
+            if !bytes.starts_with(b"------ ") {
+                continue;
+            }
+            let (line, _) = decode::decode_line(bytes, DecodeMode::Replace)?;
+            let marker = |name: &str, command: Option<&str>| SectionMarker {
+                line: line_number,
+                start,
+                end: header_end + raw_lines.offset(),
+                name: name.to_string(),
+                command: command.map(str::to_string),
+                duration: None,
+            };
             // Check if the first regex matches and capture groups
             if let Some(caps) = SECTION_END.captures(&line) {
                 if let Some(group) = caps.get(2) {
                     // Get the second capture group
-                    filter_and_add(&mut matches, line_number + 1, group.as_str());
+                    let mut end = marker(group.as_str(), None);
+                    end.duration = caps.get(1).and_then(|d| parse_seconds(d.as_str()));
+                    filter_and_add(&mut matches, end);
                 }
             }
             // Check for the second regex
             else if let Some(caps) = SECTION_BEGIN.captures(&line) {
                 if let Some(group) = caps.get(1) {
-                    filter_and_add(&mut matches, line_number + 1, group.as_str());
+                    let command = caps.get(2).map(|c| c.as_str());
+                    filter_and_add(&mut matches, marker(group.as_str(), command));
                 }
             }
             // Check for the third regex
             else if let Some(caps) = SECTION_BEGIN_NO_CMD.captures(&line) {
                 if let Some(group) = caps.get(1) {
-                    filter_and_add(&mut matches, line_number + 1, group.as_str());
+                    filter_and_add(&mut matches, marker(group.as_str(), None));
                 }
             }
         }
+        self.metadata = metadata;
+        self.repaired_lines = repaired_lines;
         Ok(matches)
     }
 
-    fn pair_sections(&mut self, matches: &Vec<(usize, String)>) {
+    fn pair_sections(&mut self, matches: &[SectionMarker]) -> Result<()> {
         // iterate over matches with indices
         let mut second_occurance = false;
-        let bugreport = std::str::from_utf8(&self.raw_file).unwrap();
-        let lines: Vec<&str> = bugreport.lines().collect();
-        for (index, (line_number, content)) in matches.iter().enumerate() {
-            if index > 0 && content.contains(&matches.get(index - 1).unwrap().1) {
+        let mut sections = Vec::new();
+        for (index, end) in matches.iter().enumerate() {
+            if index > 0 && end.name.contains(&matches[index - 1].name) {
                 second_occurance = true;
             }
             if !second_occurance {
                 continue;
             }
 
-            let start_line = matches.get(index - 1).unwrap().0;
-            let end_line = *line_number;
-            let mut current_section = Section::new(
-                content.to_string(),
-                start_line + 1,
-                end_line - 1,
-                match content.as_str() {
-                    "SYSTEM LOG" => SectionContent::SystemLog(LogcatSection::new(Vec::new())),
-                    "EVENT LOG" => SectionContent::EventLog(LogcatSection::new(Vec::new())),
-                    "DUMPSYS" => SectionContent::Dumpsys(Dumpsys::new()),
-                    _ => SectionContent::Other,
-                },
-            );
-
-            current_section.parse(
-                &lines[start_line + 1..end_line],
-                self.metadata.timestamp.year(),
-            );
-
-            self.sections.push(current_section);
+            let begin = &matches[index - 1];
+            sections.push(Section::new(
+                end.name.clone(),
+                begin.command.clone(),
+                begin.line + 1,
+                end.line - 1,
+                begin.end..end.start,
+                end.duration,
+            ));
 
             second_occurance = false;
         }
+        self.sections = sections;
+        Ok(())
+    }
+
+    /// Parses the section on first access and returns the cached content afterwards.
+    pub fn get_content<'s>(&self, section: &'s Section) -> Result<&'s SectionContent> {
+        section.parse(
+            &self.raw_file,
+            self.decode_mode,
+            &self.metadata.clock,
+            self.metadata.wall_time,
+        )
     }
 
     pub fn get_sections(&self) -> &Vec<Section> {
@@ -120,19 +380,164 @@ impl BugreportTxt {
 }
 
 impl BugreportTxt {
-    pub fn search_by_tag(&self, tag: &str) -> Option<Vec<LogcatLine>> {
+    pub fn search_by_tag(&self, tag: &str) -> Result<Vec<LogcatLine>> {
         let sections = self
             .sections
             .iter()
-            .filter(|s| s.name == "SYSTEM LOG" || s.name == "EVENT LOG")
+            .filter(|s| is_log_section(&s.name))
             .collect::<Vec<&Section>>();
         let mut results = Vec::new();
         for section in sections {
+            self.get_content(section)?;
             if let Some(lines) = section.search_by_tag(tag) {
                 results.extend(lines);
             }
         }
-        Some(results)
+        results.sort_by_key(|line| line.timestamp);
+        Ok(results)
+    }
+
+    /// Runs `query` once over every log section, parsing them as needed, and returns the
+    /// matching lines in timestamp order. Lines with the same timestamp keep report order.
+    pub fn query(&self, query: &Query) -> Result<Vec<&LogcatLine>> {
+        let processes = if query.needs_processes() {
+            Some(self.get_process_table()?)
+        } else {
+            None
+        };
+        let mut results = Vec::new();
+        for section in self.sections.iter().filter(|s| is_log_section(&s.name)) {
+            if let Some(logcat) = self.get_content(section)?.as_logcat() {
+                results.extend(query.run(logcat, processes));
+            }
+        }
+        results.sort_by_key(|line| line.timestamp);
+        Ok(results)
+    }
+
+    /// Every log section merged into one time-ordered stream, each entry labelled with the
+    /// section it came from. Sections are parsed as needed.
+    pub fn timeline(&self) -> Result<Timeline<'_>> {
+        let mut timeline = Timeline::new();
+        for section in self.sections.iter().filter(|s| is_log_section(&s.name)) {
+            if let Some(logcat) = self.get_content(section)?.as_logcat() {
+                timeline.add_logcat(&section.name, logcat);
+            }
+        }
+        Ok(timeline)
+    }
+
+    /// Parses every log section and indexes it, see
+    /// [`LogcatSection::build_index`](super::logcat::LogcatSection::build_index). Returns the
+    /// approximate memory the indexes take, in bytes.
+    pub fn build_indexes(&self) -> Result<usize> {
+        let mut memory = 0;
+        for section in self.sections.iter().filter(|s| is_log_section(&s.name)) {
+            if let Some(logcat) = self.get_content(section)?.as_logcat() {
+                memory += logcat.build_index().memory_bytes();
+            }
+        }
+        Ok(memory)
+    }
+
+    /// Lines of every log section inside `window`, in timestamp order. Lines with the same
+    /// timestamp keep report order.
+    pub fn search_by_window(&self, window: &TimeWindow) -> Result<Vec<LogcatLine>> {
+        let mut results = Vec::new();
+        for section in self.sections.iter().filter(|s| is_log_section(&s.name)) {
+            if let Some(logcat) = self.get_content(section)?.as_logcat() {
+                results.extend(logcat.search_by_window(window));
+            }
+        }
+        results.sort_by_key(|line| line.timestamp);
+        Ok(results)
+    }
+
+    /// Lines of every log section read from `buffer`, e.g. the crash buffer, in timestamp order.
+    pub fn search_by_buffer(&self, buffer: LogBuffer) -> Result<Vec<LogcatLine>> {
+        let mut results = Vec::new();
+        for section in self.sections.iter().filter(|s| is_log_section(&s.name)) {
+            if let Some(logcat) = self.get_content(section)?.as_logcat() {
+                results.extend(logcat.search_by_buffer(buffer));
+            }
+        }
+        results.sort_by_key(|line| line.timestamp);
+        Ok(results)
+    }
+
+    /// Every EVENT LOG line with tag `tag`, e.g. `am_anr`, with its payload decoded.
+    pub fn search_events(&self, tag: &str) -> Result<Vec<EventRecord>> {
+        let mut events = Vec::new();
+        for section in self.sections.iter().filter(|s| s.name == "EVENT LOG") {
+            if let Some(logcat) = self.get_content(section)?.as_logcat() {
+                events.extend(
+                    logcat
+                        .search_by_tag(tag)
+                        .iter()
+                        .map(|line| EventRecord::decode(line, &self.event_tags)),
+                );
+            }
+        }
+        Ok(events)
+    }
+
+    /// The SYSTEM PROPERTIES section, parsed on first access. `None` if the report has none.
+    pub fn get_properties(&self) -> Result<Option<&Properties>> {
+        match self.sections.iter().find(|s| s.name == "SYSTEM PROPERTIES") {
+            Some(section) => Ok(self.get_content(section)?.as_properties()),
+            None => Ok(None),
+        }
+    }
+
+    /// The value of one system property, e.g. `property("ro.build.version.sdk")`.
+    pub fn property(&self, key: &str) -> Result<Option<&str>> {
+        Ok(self.get_properties()?.and_then(|properties| properties.get(key)))
+    }
+
+    /// The thread dumps of the VM TRACES AT LAST ANR section, or of VM TRACES JUST NOW when the
+    /// report has no ANR dump or it holds no process, parsed on first access. `None` if the
+    /// report has neither.
+    pub fn get_anr_traces(&self) -> Result<Option<&AnrTraces>> {
+        for name in ["VM TRACES AT LAST ANR", "VM TRACES JUST NOW"] {
+            if let Some(section) = self.sections.iter().find(|s| s.name == name) {
+                if let Some(traces) = self.get_content(section)?.as_traces() {
+                    if !traces.is_empty() {
+                        return Ok(Some(traces));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The binder transactions in flight when the report was taken, from the BINDER
+    /// TRANSACTIONS section or else BINDER STATE. `None` if the report has neither.
+    pub fn get_binder_transactions(&self) -> Result<Option<&BinderTransactions>> {
+        for name in ["BINDER TRANSACTIONS", "BINDER STATE"] {
+            if let Some(section) = self.sections.iter().find(|s| s.name == name) {
+                if let Some(binder) = self.get_content(section)?.as_binder() {
+                    if !binder.is_empty() {
+                        return Ok(Some(binder));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every process and thread listed by `ps`, built from the PROCESSES AND THREADS and
+    /// PROCESSES sections on first access. Empty if the report has neither.
+    pub fn get_process_table(&self) -> Result<&ProcessTable> {
+        if let Some(table) = self.process_table.get() {
+            return Ok(table);
+        }
+        let mut table = ProcessTable::new();
+        for section in self.sections.iter().filter(|s| is_process_section(&s.name)) {
+            if let Some(processes) = self.get_content(section)?.as_processes() {
+                table.merge(processes);
+            }
+        }
+        Ok(self.process_table.get_or_init(|| table))
     }
 
     pub fn get_metadata(&self) -> &Metadata {
@@ -140,7 +545,7 @@ impl BugreportTxt {
     }
 }
 
-pub fn test_setup_bugreport() -> io::Result<BugreportTxt> {
+pub fn test_setup_bugreport() -> Result<BugreportTxt> {
     let file_path = Path::new("tests/data/example.txt");
     if !Path::new(file_path).exists() {
         println!(
@@ -186,9 +591,11 @@ pub fn test_setup_bugreport() -> io::Result<BugreportTxt> {
 
 // TODO: write a bugreport setup function that calls `Bugreport::load()` inside
 
+#[cfg(test)]
 mod tests {
     use super::*;
-    use chrono::{NaiveDate, TimeZone};
+    use crate::bugreport::{dumpsys::Dumpsys, logcat::LogcatSection};
+    use chrono::NaiveDate;
 
     #[test]
     fn test_read_and_slice() {
@@ -196,14 +603,10 @@ mod tests {
         let matches = bugreport.read_and_slice().unwrap();
         assert_eq!(matches.len(), 274);
         assert_eq!(
-            bugreport.metadata.timestamp,
-            Local
-                .from_local_datetime(
-                    &NaiveDate::from_ymd_opt(2024, 8, 16)
-                        .unwrap()
-                        .and_hms_opt(10, 02, 11)
-                        .unwrap(),
-                )
+            bugreport.metadata.wall_time,
+            NaiveDate::from_ymd_opt(2024, 8, 16)
+                .unwrap()
+                .and_hms_opt(10, 02, 11)
                 .unwrap()
         );
     }
@@ -223,7 +626,7 @@ mod tests {
         let duration = start.elapsed();
         println!("Time taken: {:?}", duration);
         let start = std::time::Instant::now();
-        bugreport.pair_sections(&matches);
+        bugreport.pair_sections(&matches).unwrap();
         let duration = start.elapsed();
         println!("Time taken: {:?}", duration);
         assert_eq!(bugreport.sections.len(), 134);
@@ -231,27 +634,36 @@ mod tests {
         // find the section with the name "SYSTEM LOG"
         let system_log_section = bugreport.sections.iter().find(|s| s.name == "SYSTEM LOG");
         assert_eq!(
-            system_log_section.unwrap().content,
+            *bugreport.get_content(system_log_section.unwrap()).unwrap(),
             SectionContent::SystemLog(LogcatSection::new(Vec::new()))
         );
         // find the section with the name "EVENT LOG"
         let event_log_section = bugreport.sections.iter().find(|s| s.name == "EVENT LOG");
         assert_eq!(
-            event_log_section.unwrap().content,
+            *bugreport.get_content(event_log_section.unwrap()).unwrap(),
             SectionContent::EventLog(LogcatSection::new(Vec::new()))
         );
         // find the section with the name "DUMPSYS"
         let dumpsys_section = bugreport.sections.iter().find(|s| s.name == "DUMPSYS");
         assert_eq!(
-            dumpsys_section.unwrap().content,
+            *bugreport.get_content(dumpsys_section.unwrap()).unwrap(),
             SectionContent::Dumpsys(Dumpsys::new())
         );
         // find a section without the above names
         let other_section = bugreport
             .sections
             .iter()
-            .find(|s| s.name != "SYSTEM LOG" && s.name != "EVENT LOG" && s.name != "DUMPSYS");
-        assert_eq!(other_section.unwrap().content, SectionContent::Other);
+            .find(|s| {
+                s.name != "SYSTEM LOG"
+                    && s.name != "EVENT LOG"
+                    && !s.name.starts_with("DUMPSYS")
+                    && s.name != "SYSTEM PROPERTIES"
+                    && !s.name.starts_with("PROCESSES")
+            });
+        assert_eq!(
+            *bugreport.get_content(other_section.unwrap()).unwrap(),
+            SectionContent::Other
+        );
     }
 
     #[test]
@@ -264,35 +676,32 @@ mod tests {
                 return;
             }
         };
-        bugreport.pair_sections(&matches);
+        bugreport.pair_sections(&matches).unwrap();
 
         // find the second section with name "SYSTEM LOG"
         let system_log_sections = bugreport
             .sections
-            .iter_mut()
+            .iter()
             .filter(|s| s.name == "SYSTEM LOG")
-            .collect::<Vec<&mut Section>>();
+            .collect::<Vec<&Section>>();
 
         let system_log_section_1st = system_log_sections.get(0).unwrap();
-        let lines = match &system_log_section_1st.content {
+        let lines = match bugreport.get_content(system_log_section_1st).unwrap() {
             SectionContent::SystemLog(lines) => lines,
             _ => panic!("Expected SystemLog section type"),
         };
 
-        assert_eq!(lines.len(), system_log_section_1st.get_line_numbers() - 7);
-        // The seven lines that cannot be parsed are listed below:
-        // These three are as expected:
+        assert_eq!(lines.len(), system_log_section_1st.get_line_numbers() - 3);
+        assert_eq!(lines.get_dropped_lines(), 0);
+        // The three lines that are not records are the buffer markers:
         // No such line: "--------- beginning of system"
         // No such line: "--------- beginning of crash"
         // No such line: "--------- beginning of main"
-        // The following four do not contain a colon (WTF?!!):
-        // No such line: "08-16 10:01:26.784  1000  5098  5098 D QSRecord custom(com.google.android.as/com.google.android.apps.miphone.aiai.captions.quicset listening to true"
-        // No such line: "08-16 10:01:29.628  1000  5098  5098 D QSRecord custom(com.google.android.as/com.google.android.apps.miphone.aiai.captions.quicset listening to false"
-        // No such line: "08-16 10:01:29.976  1000  5098  5098 D QSRecord custom(com.google.android.as/com.google.android.apps.miphone.aiai.captions.quicset listening to true"
-        // No such line: "08-16 10:01:31.110  1000  5098  5098 D QSRecord custom(com.google.android.as/com.google.android.apps.miphone.aiai.captions.quicset listening to false"
+        // Four QSRecord lines lack the colon after the tag and are read as `Tag message`, e.g.
+        // "08-16 10:01:26.784  1000  5098  5098 D QSRecord custom(com.google.android.as/com.google.android.apps.miphone.aiai.captions.quicset listening to true"
 
         let system_log_section_2nd = system_log_sections.get(1).unwrap();
-        let lines = match &system_log_section_2nd.content {
+        let lines = match bugreport.get_content(system_log_section_2nd).unwrap() {
             SectionContent::SystemLog(lines) => lines,
             _ => panic!("Expected SystemLog section type"),
         };
@@ -304,10 +713,10 @@ mod tests {
 
         let event_log_section = bugreport
             .sections
-            .iter_mut()
+            .iter()
             .find(|s| s.name == "EVENT LOG")
             .unwrap();
-        let lines = match &event_log_section.content {
+        let lines = match bugreport.get_content(event_log_section).unwrap() {
             SectionContent::EventLog(lines) => lines,
             _ => panic!("Expected EventLog section type"),
         };
@@ -316,4 +725,221 @@ mod tests {
         // The one line that cannot be parsed is listed below:
         // No such line: "--------- beginning of events"
     }
+
+    const SMALL_REPORT: &[u8] = b"========================================================
+== dumpstate: 2024-08-16 10:02:11
+========================================================
+Build fingerprint: 'Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys'
+Uptime: up 0 weeks, 0 days, 0 hours, 32 minutes
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+--------- beginning of main
+08-16 10:01:30.003  1000  5098  5850 D LocalBluetoothAdapter: isSupportBluetoothRestrict = 0
+08-16 10:01:31.003 10160  5140  5140 D RecentsImpl: bad \xff byte
+08-16 10:01:32.003 10160  5140  5140 D NavStubView_Touch: setKeepHidden
+------ 0.052s was the duration of 'SYSTEM LOG' ------
+";
+
+    #[test]
+    fn test_kernel_log() {
+        let report = b"== dumpstate: 2024-08-16 10:02:11
+Uptime: up 0 weeks, 0 days, 0 hours, 32 minutes
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+08-16 10:01:30.003  1000  1500  1520 W Watchdog: Blocked in handler on main thread
+------ 0.052s was the duration of 'SYSTEM LOG' ------
+------ KERNEL LOG (dmesg) ------
+[ 1870.500000] INFO: task kworker/u16:3:1234 blocked for more than 120 seconds.
+[ 1880.000000] f2fs: f2fs_write_end_io: error -5
+------ 0.010s was the duration of 'KERNEL LOG' ------
+";
+        let mut bugreport = BugreportTxt::from_bytes(report.to_vec());
+        bugreport.load().unwrap();
+        let kernel = bugreport.get_content(&bugreport.sections[1]).unwrap();
+        assert_eq!(kernel.as_logcat().unwrap().len(), 2);
+
+        // Booted at 09:30:11, so the hung task comes before the watchdog and the f2fs error after
+        let timeline = bugreport.timeline().unwrap();
+        let entries: Vec<String> = timeline.iter().map(|entry| format!("{}", entry)).collect();
+        assert_eq!(entries[0].get(..40), Some("[KERNEL LOG] 2024-08-16 10:01:21.500000 "));
+        assert!(entries[1].starts_with("[SYSTEM LOG]"));
+        assert_eq!(bugreport.search_by_buffer(LogBuffer::Kernel).unwrap().len(), 2);
+        assert_eq!(bugreport.search_by_tag("f2fs").unwrap()[0].message, "f2fs_write_end_io: error -5");
+    }
+
+    #[test]
+    fn test_anr_traces() {
+        let report = format!(
+            "== dumpstate: 2024-08-16 10:02:11\n------ VM TRACES AT LAST ANR (/data/anr/anr_2024-08-16-10-01-30-123: 2024-08-16 10:01:30) ------\n{}------ 0.020s was the duration of 'VM TRACES AT LAST ANR' ------\n",
+            crate::bugreport::traces::TEST_TRACES
+        );
+        let mut bugreport = BugreportTxt::from_bytes(report.into_bytes());
+        bugreport.load().unwrap();
+        let traces = bugreport.get_anr_traces().unwrap().unwrap();
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces.get_process(1500).next().unwrap().threads.len(), 3);
+        assert!(BugreportTxt::from_bytes(SMALL_REPORT.to_vec()).get_anr_traces().unwrap().is_none());
+
+        // Without an ANR dump the one taken for the report is used
+        let report = format!(
+            "== dumpstate: 2024-08-16 10:02:11\n------ VM TRACES JUST NOW (/data/anr/dumptrace_1) ------\n{}------ 0.020s was the duration of 'VM TRACES JUST NOW' ------\n",
+            crate::bugreport::traces::TEST_TRACES
+        );
+        let mut bugreport = BugreportTxt::from_bytes(report.into_bytes());
+        bugreport.load().unwrap();
+        assert_eq!(bugreport.get_anr_traces().unwrap().unwrap().len(), 2);
+
+        // Nor when the ANR dump holds no process
+        let report = format!(
+            "== dumpstate: 2024-08-16 10:02:11\n------ VM TRACES AT LAST ANR (/data/anr/anr_2024-08-16-10-01-30-123: 2024-08-16 10:01:30) ------\n*** NO ANRS ***\n------ 0.001s was the duration of 'VM TRACES AT LAST ANR' ------\n------ VM TRACES JUST NOW (/data/anr/dumptrace_1) ------\n{}------ 0.020s was the duration of 'VM TRACES JUST NOW' ------\n",
+            crate::bugreport::traces::TEST_TRACES
+        );
+        let mut bugreport = BugreportTxt::from_bytes(report.into_bytes());
+        bugreport.load().unwrap();
+        assert_eq!(bugreport.get_anr_traces().unwrap().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_load_lossy() {
+        let mut bugreport = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        bugreport.load().unwrap();
+        // Nothing but the header is decoded until a section is accessed
+        assert!(bugreport.get_repaired_lines().is_empty());
+        assert!(!bugreport.sections[0].is_parsed());
+        let lines = match bugreport.get_content(&bugreport.sections[0]).unwrap() {
+            SectionContent::SystemLog(lines) => lines,
+            _ => panic!("Expected SystemLog section type"),
+        };
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines.get_line(1).unwrap().message, "bad \u{fffd} byte");
+        assert_eq!(bugreport.get_repaired_lines(), vec![9]);
+        assert_eq!(bugreport.search_by_buffer(LogBuffer::Main).unwrap().len(), 3);
+        assert!(bugreport.search_by_buffer(LogBuffer::Crash).unwrap().is_empty());
+        assert!(bugreport.build_indexes().unwrap() > 0);
+        assert_eq!(bugreport.search_by_tag("RecentsImpl").unwrap().len(), 1);
+
+        let mut bugreport = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        bugreport.set_decode_mode(DecodeMode::Strict);
+        bugreport.load().unwrap();
+        assert!(matches!(
+            bugreport.get_content(&bugreport.sections[0]),
+            Err(crate::error::Error::BadEncoding { line: Some(9), section: Some(name) }) if name == "SYSTEM LOG"
+        ));
+    }
+
+    #[test]
+    fn test_section_index() {
+        let mut bugreport = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        bugreport.load().unwrap();
+        assert_eq!(bugreport.sections.len(), 1);
+        let section = &bugreport.sections[0];
+        assert_eq!(section.name, "SYSTEM LOG");
+        assert_eq!(
+            section.command.as_deref(),
+            Some("logcat -v threadtime -v printable -v uid -d *:v")
+        );
+        assert_eq!(section.get_line_range(), 7..=10);
+        let content = &bugreport.raw_file[section.get_byte_range()];
+        assert!(content.starts_with(b"--------- beginning of main\n"));
+        assert!(content.ends_with(b"setKeepHidden\n"));
+    }
+
+    #[test]
+    fn test_device_timezone() {
+        let mut report = SMALL_REPORT.to_vec();
+        report.extend_from_slice(
+            b"------ SYSTEM PROPERTIES (getprop) ------
+[persist.sys.locale]: [zh-CN]
+[persist.sys.timezone]: [Asia/Shanghai]
+------ 0.010s was the duration of 'SYSTEM PROPERTIES' ------
+",
+        );
+
+        let mut bugreport = BugreportTxt::from_bytes(report.clone());
+        bugreport.load().unwrap();
+        assert_eq!(bugreport.metadata.clock.get_source(), TimezoneSource::Property);
+        assert_eq!(bugreport.metadata.timestamp.to_rfc3339(), "2024-08-16T10:02:11+08:00");
+        let lines = bugreport.search_by_tag("RecentsImpl").unwrap();
+        assert_eq!(lines[0].timestamp.to_rfc3339(), "2024-08-16T10:01:31.003+08:00");
+
+        let mut in_utc = BugreportTxt::from_bytes(report);
+        in_utc.set_time_display(TimeDisplay::Utc);
+        in_utc.load().unwrap();
+        assert_eq!(in_utc.metadata.timestamp.to_rfc3339(), "2024-08-16T02:02:11+00:00");
+        assert_eq!(in_utc.search_by_tag("RecentsImpl").unwrap()[0].timestamp, lines[0].timestamp);
+
+        assert_eq!(bugreport.property("persist.sys.locale").unwrap(), Some("zh-CN"));
+        assert_eq!(bugreport.property("ro.build.version.sdk").unwrap(), None);
+
+        // Without the property the report is read as UTC
+        let mut bugreport = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        bugreport.load().unwrap();
+        assert_eq!(bugreport.metadata.clock.get_timezone(), None);
+        assert!(bugreport.metadata.clock.is_guessed());
+        assert!(bugreport.get_properties().unwrap().is_none());
+        assert_eq!(bugreport.metadata.timestamp.to_rfc3339(), "2024-08-16T10:02:11+00:00");
+
+        // unless the header names the timezone
+        let header_end = SMALL_REPORT.iter().position(|&b| b == b'\n').unwrap() + 1;
+        let mut report = SMALL_REPORT.to_vec();
+        report.splice(header_end..header_end, b"Timezone: Asia/Shanghai\n".iter().copied());
+        let mut bugreport = BugreportTxt::from_bytes(report);
+        bugreport.load().unwrap();
+        assert_eq!(bugreport.metadata.clock.get_source(), TimezoneSource::Header);
+        assert_eq!(bugreport.metadata.timestamp.to_rfc3339(), "2024-08-16T10:02:11+08:00");
+    }
+
+    #[test]
+    fn test_process_table() {
+        let mut report = SMALL_REPORT.to_vec();
+        report.extend_from_slice(
+            b"------ PROCESSES AND THREADS (ps -A -T -Z -O pri,nice,rtprio,sched,pcy,time) ------
+LABEL                          USER           PID   TID  PPID     VSZ    RSS WCHAN            ADDR S PRI  NI RTPRIO SCH  PCY     TIME CMD
+u:r:platform_app:s0:c512,c768  u0_a160       5140  5140  1026 17562428 312456 do_epoll_wait     0 S  10 -10      -   0  ta 00:01:07 ndroid.systemui
+u:r:platform_app:s0:c512,c768  u0_a160       5140  5300  1026 17562428 312456 futex_wait_queue  0 S  16  -4      -   0  ta 00:00:21 RenderThread
+------ 0.120s was the duration of 'PROCESSES AND THREADS' ------
+------ PROCESSES (ps -A) ------
+USER           PID  PPID     VSZ    RSS WCHAN            ADDR S NAME
+u0_a160       5140  1026 17562428 312456 do_epoll_wait     0 S com.android.systemui
+------ 0.020s was the duration of 'PROCESSES' ------
+",
+        );
+        let mut bugreport = BugreportTxt::from_bytes(report);
+        bugreport.load().unwrap();
+        let processes = bugreport.get_process_table().unwrap();
+        let line = &bugreport.search_by_tag("RecentsImpl").unwrap()[0];
+        assert_eq!(line.process_name(processes), Some("com.android.systemui"));
+        assert_eq!(line.thread_name(processes), Some("ndroid.systemui"));
+        assert_eq!(processes.thread_name(5300), Some("RenderThread"));
+
+        let mut bugreport = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        bugreport.load().unwrap();
+        assert!(bugreport.get_process_table().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_cached() {
+        let dir = std::env::temp_dir().join(format!("bugreport_cache_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache_path = dir.join("bugreport.txt.parsecache");
+
+        let mut bugreport = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        assert!(!bugreport.load_cached(&cache_path).unwrap());
+        assert!(cache_path.exists());
+
+        let mut reopened = BugreportTxt::from_bytes(SMALL_REPORT.to_vec());
+        assert!(reopened.load_cached(&cache_path).unwrap());
+        assert_eq!(reopened.get_metadata().version, bugreport.get_metadata().version);
+        assert_eq!(reopened.get_repaired_lines(), vec![9]);
+        let section = &reopened.sections[0];
+        assert!(section.is_parsed());
+        assert_eq!(section.get_byte_range(), bugreport.sections[0].get_byte_range());
+        assert_eq!(reopened.search_by_tag("RecentsImpl").unwrap().len(), 1);
+
+        // Any change to the report invalidates the cache
+        let mut changed = SMALL_REPORT.to_vec();
+        changed.extend_from_slice(b"\n");
+        let mut changed = BugreportTxt::from_bytes(changed);
+        assert!(!changed.load_cached(&cache_path).unwrap());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
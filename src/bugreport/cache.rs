@@ -0,0 +1,125 @@
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use xxhash_rust::xxh3::xxh3_64;
+
+use super::clock::TimeDisplay;
+use super::decode::DecodeMode;
+use super::logcat::LogcatSection;
+use super::metadata::Metadata;
+use crate::error::{Error, Result};
+
+/// Bumped whenever the layout of the cache file or of a cached type changes.
+const CACHE_VERSION: u32 = 9;
+const CACHE_EXTENSION: &str = "parsecache";
+
+/// Identifies the report a cache file was written for. Any difference means the cache is stale.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    version: u32,
+    file_size: u64,
+    content_hash: u64,
+    decode_mode: DecodeMode,
+    time_display: TimeDisplay,
+}
+
+impl CacheKey {
+    pub fn new(raw: &[u8], decode_mode: DecodeMode, time_display: TimeDisplay) -> Self {
+        Self {
+            version: CACHE_VERSION,
+            file_size: raw.len() as u64,
+            content_hash: xxh3_64(raw),
+            decode_mode,
+            time_display,
+        }
+    }
+}
+
+/// One entry of the section index, plus the logcat rows when the section is a log.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedSection<'a> {
+    pub name: String,
+    pub command: Option<String>,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub byte_start: usize,
+    pub byte_end: usize,
+    pub duration_us: Option<i64>,
+    pub logcat: Option<Cow<'a, LogcatSection>>,
+    pub repaired_lines: Vec<usize>,
+}
+
+/// Everything `load()` and the logcat parser produce for one report. The key comes first so a
+/// stale cache is rejected without decoding the rest.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CachedReport<'a> {
+    pub key: CacheKey,
+    pub metadata: Cow<'a, Metadata>,
+    pub repaired_lines: Vec<usize>,
+    pub sections: Vec<CachedSection<'a>>,
+}
+
+impl CachedReport<'_> {
+    /// Reads the cache at `path`. Returns `None` when there is no cache or it belongs to a
+    /// different report, version, decode mode or time display.
+    pub fn read(path: &Path, key: &CacheKey) -> Option<CachedReport<'static>> {
+        let file = File::open(path).ok()?;
+        let mmap = unsafe { Mmap::map(&file).ok()? };
+        let cached_key: CacheKey = bincode::deserialize(&mmap).ok()?;
+        if &cached_key != key {
+            return None;
+        }
+        bincode::deserialize(&mmap).ok()
+    }
+
+    /// Writes the cache through a temporary file so readers never see a partial cache.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let bytes = bincode::serialize(self).map_err(|_| Error::malformed("cache"))?;
+        let temp_path = path.with_extension(format!("{}.tmp", CACHE_EXTENSION));
+        fs::write(&temp_path, bytes)?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+}
+
+/// Where the cache of `report` lives: next to it, or in `cache_dir` when one is given.
+pub fn cache_path_for(report: &Path, cache_dir: Option<&Path>) -> PathBuf {
+    let mut file_name = report.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".");
+    file_name.push(CACHE_EXTENSION);
+    match cache_dir {
+        Some(dir) => dir.join(file_name),
+        None => report.with_file_name(file_name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        let device = TimeDisplay::Device;
+        let key = CacheKey::new(b"report", DecodeMode::Replace, device);
+        assert_eq!(key, CacheKey::new(b"report", DecodeMode::Replace, device));
+        assert_ne!(key, CacheKey::new(b"report", DecodeMode::Skip, device));
+        assert_ne!(key, CacheKey::new(b"repost", DecodeMode::Replace, device));
+        assert_ne!(key, CacheKey::new(b"report", DecodeMode::Replace, TimeDisplay::Utc));
+    }
+
+    #[test]
+    fn test_cache_path_for() {
+        let report = Path::new("/data/reports/bugreport-1.zip");
+        assert_eq!(
+            cache_path_for(report, None),
+            PathBuf::from("/data/reports/bugreport-1.zip.parsecache")
+        );
+        assert_eq!(
+            cache_path_for(report, Some(Path::new("/tmp/cache"))),
+            PathBuf::from("/tmp/cache/bugreport-1.zip.parsecache")
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::{DateTime, Duration, FixedOffset, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// The system property holding the device's IANA timezone, e.g. `Asia/Shanghai`.
+pub const TIMEZONE_PROPERTY: &str = "persist.sys.timezone";
+
+/// Which offset the times read from a report are expressed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimeDisplay {
+    /// The device's own timezone, so times match what the device printed.
+    #[default]
+    Device,
+    /// UTC, to compare reports from devices in different timezones.
+    Utc,
+}
+
+/// Where the timezone of a [`ReportClock`] came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum TimezoneSource {
+    /// [`TIMEZONE_PROPERTY`] in SYSTEM PROPERTIES.
+    Property,
+    /// The `Timezone` line of the dumpstate header.
+    Header,
+    /// The report does not say, so times are read as UTC and may be off by the device's offset.
+    #[default]
+    Guessed,
+}
+
+/// Turns the wall-clock times printed in a report into instants. Android prints device-local
+/// time without an offset, so the timezone has to come from the report itself; the machine the
+/// report is analyzed on never matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ReportClock {
+    /// `None` when the report does not say; times are then read as UTC.
+    timezone: Option<Tz>,
+    #[serde(default)]
+    source: TimezoneSource,
+    display: TimeDisplay,
+    /// When the device booted, for times printed as seconds since boot.
+    boot_time: Option<DateTime<Utc>>,
+}
+
+impl ReportClock {
+    /// A clock with a known `timezone` counts it as read from [`TIMEZONE_PROPERTY`]; see
+    /// [`ReportClock::with_source`].
+    pub fn new(timezone: Option<Tz>, display: TimeDisplay) -> Self {
+        Self {
+            timezone,
+            source: match timezone {
+                Some(_) => TimezoneSource::Property,
+                None => TimezoneSource::Guessed,
+            },
+            display,
+            boot_time: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: TimezoneSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn with_boot_time(mut self, boot_time: Option<DateTime<Utc>>) -> Self {
+        self.boot_time = boot_time;
+        self
+    }
+
+    pub fn get_boot_time(&self) -> Option<DateTime<Utc>> {
+        self.boot_time
+    }
+
+    /// Parses the value of [`TIMEZONE_PROPERTY`].
+    pub fn parse_timezone(name: &str) -> Option<Tz> {
+        name.trim().parse().ok()
+    }
+
+    pub fn get_timezone(&self) -> Option<Tz> {
+        self.timezone
+    }
+
+    pub fn get_source(&self) -> TimezoneSource {
+        self.source
+    }
+
+    /// Whether the timezone is an assumption rather than read from the report.
+    pub fn is_guessed(&self) -> bool {
+        self.source == TimezoneSource::Guessed
+    }
+
+    pub fn get_display(&self) -> TimeDisplay {
+        self.display
+    }
+
+    /// Reads a wall-clock time printed by the device. An ambiguous time at the end of daylight
+    /// saving resolves to the earlier instant, and a time inside the gap at its start is read
+    /// with the offset in effect around it; neither fails.
+    pub fn localize(&self, naive: NaiveDateTime) -> DateTime<FixedOffset> {
+        let tz = self.timezone.unwrap_or(Tz::UTC);
+        let instant = match tz.from_local_datetime(&naive) {
+            LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.with_timezone(&Utc),
+            LocalResult::None => {
+                let offset = tz.offset_from_utc_datetime(&naive).fix();
+                Utc.from_utc_datetime(&(naive - offset))
+            }
+        };
+        self.render(instant)
+    }
+
+    /// Reads a time printed as seconds since boot, e.g. by `logcat -v monotonic`. `None` when
+    /// the boot time is not known.
+    pub fn since_boot(&self, uptime: Duration) -> Option<DateTime<FixedOffset>> {
+        self.boot_time.map(|boot_time| self.render(boot_time + uptime))
+    }
+
+    /// The device's wall-clock time at `instant`, as it would have printed it.
+    pub fn wall_time(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        instant
+            .with_timezone(&self.timezone.unwrap_or(Tz::UTC))
+            .naive_local()
+    }
+
+    /// Expresses `instant` in the chosen display offset.
+    pub fn render(&self, instant: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self.display {
+            TimeDisplay::Device => instant
+                .with_timezone(&self.timezone.unwrap_or(Tz::UTC))
+                .fixed_offset(),
+            TimeDisplay::Utc => instant.fixed_offset(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn test_localize() {
+        let shanghai = ReportClock::parse_timezone("Asia/Shanghai");
+        let clock = ReportClock::new(shanghai, TimeDisplay::Device);
+        let time = clock.localize(naive("2024-08-16 10:02:11"));
+        assert_eq!(time.to_rfc3339(), "2024-08-16T10:02:11+08:00");
+
+        let utc = ReportClock::new(shanghai, TimeDisplay::Utc).localize(naive("2024-08-16 10:02:11"));
+        assert_eq!(utc.to_rfc3339(), "2024-08-16T02:02:11+00:00");
+        assert_eq!(utc, time);
+
+        assert_eq!(clock.get_source(), TimezoneSource::Property);
+        assert!(!clock.is_guessed());
+
+        // Unknown timezone falls back to UTC
+        assert!(ReportClock::default().is_guessed());
+        let time = ReportClock::default().localize(naive("2024-08-16 10:02:11"));
+        assert_eq!(time.to_rfc3339(), "2024-08-16T10:02:11+00:00");
+    }
+
+    #[test]
+    fn test_localize_dst() {
+        let clock = ReportClock::new(ReportClock::parse_timezone("Europe/Berlin"), TimeDisplay::Device);
+        // Clocks went back from 03:00 to 02:00, the earlier instant wins
+        let ambiguous = clock.localize(naive("2024-10-27 02:30:00"));
+        assert_eq!(ambiguous.to_rfc3339(), "2024-10-27T02:30:00+02:00");
+        // Clocks jumped from 02:00 to 03:00, so 02:30 never existed
+        let skipped = clock.localize(naive("2024-03-31 02:30:00"));
+        assert_eq!(skipped.naive_utc(), naive("2024-03-31 00:30:00"));
+        assert!(ReportClock::parse_timezone("Mars/Olympus_Mons").is_none());
+    }
+
+    #[test]
+    fn test_since_boot() {
+        let clock = ReportClock::new(ReportClock::parse_timezone("Asia/Shanghai"), TimeDisplay::Device);
+        assert_eq!(clock.since_boot(Duration::seconds(5)), None);
+        let boot_time = clock.localize(naive("2024-08-16 09:30:00")).with_timezone(&Utc);
+        let clock = clock.with_boot_time(Some(boot_time));
+        let time = clock.since_boot(Duration::milliseconds(90_500)).unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-08-16T09:31:30.500+08:00");
+        assert_eq!(clock.wall_time(boot_time), naive("2024-08-16 09:30:00"));
+    }
+}
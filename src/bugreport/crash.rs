@@ -0,0 +1,187 @@
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, FixedOffset};
+
+use super::logcat::LogcatLine;
+use super::signature::StackSignature;
+
+const CRASH_TAG: &str = "AndroidRuntime";
+const FATAL_EXCEPTION: &str = "FATAL EXCEPTION: ";
+const CAUSED_BY: &str = "Caused by: ";
+
+/// A Java crash as `AndroidRuntime` logs it:
+///
+/// ```text
+/// FATAL EXCEPTION: main
+/// Process: com.android.systemui, PID: 5140
+/// java.lang.IllegalStateException: ...
+///     at com.android.systemui.Foo.bar(Foo.java:12)
+/// Caused by: java.lang.NullPointerException: ...
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JavaCrash {
+    pub timestamp: DateTime<FixedOffset>,
+    pub pid: u32,
+    pub thread: String,
+    pub process: String,
+    /// The innermost `Caused by`, or the thrown exception if it has no cause.
+    pub exception: String,
+    /// The stack of `exception`, top first.
+    pub frames: Vec<String>,
+}
+
+impl JavaCrash {
+    /// The crashes in `lines`, e.g. the crash buffer. Lines of other tags and pids may be
+    /// interleaved with a crash.
+    pub fn parse(lines: &[LogcatLine]) -> Vec<Self> {
+        let mut crashes: Vec<Self> = Vec::new();
+        // Index into `crashes` of the crash still being read, per pid
+        let mut open: Vec<(u32, usize)> = Vec::new();
+        for line in lines.iter().filter(|line| line.tag == CRASH_TAG) {
+            let message = line.message.trim_end();
+            if let Some(thread) = message.strip_prefix(FATAL_EXCEPTION) {
+                open.retain(|(pid, _)| *pid != line.pid);
+                open.push((line.pid, crashes.len()));
+                crashes.push(Self {
+                    timestamp: line.timestamp,
+                    pid: line.pid,
+                    thread: thread.to_string(),
+                    process: String::new(),
+                    exception: String::new(),
+                    frames: Vec::new(),
+                });
+                continue;
+            }
+            let Some(&(_, index)) = open.iter().find(|(pid, _)| *pid == line.pid) else {
+                continue;
+            };
+            let crash = &mut crashes[index];
+            let trimmed = message.trim_start();
+            if let Some(process) = message.strip_prefix("Process: ") {
+                crash.process = process.split(", PID:").next().unwrap_or(process).to_string();
+            } else if trimmed.starts_with("at ") {
+                crash.frames.push(trimmed.to_string());
+            } else if let Some(cause) = message.strip_prefix(CAUSED_BY) {
+                crash.exception = cause.to_string();
+                crash.frames.clear();
+            } else if crash.exception.is_empty() && !trimmed.is_empty() {
+                crash.exception = trimmed.to_string();
+            }
+        }
+        crashes
+    }
+
+    pub fn signature(&self, depth: usize) -> StackSignature {
+        StackSignature::new(Some(&self.exception), self.frames.iter().map(String::as_str), depth)
+    }
+}
+
+/// Crashes with the same [`StackSignature`], in the order they happened.
+#[derive(Debug)]
+pub struct CrashGroup<'c> {
+    pub signature: StackSignature,
+    pub crashes: Vec<&'c JavaCrash>,
+}
+
+impl<'c> CrashGroup<'c> {
+    /// Groups `crashes` by signature, the most frequent group first.
+    pub fn group(crashes: &'c [JavaCrash], depth: usize) -> Vec<Self> {
+        let mut groups: Vec<CrashGroup<'c>> = Vec::new();
+        for crash in crashes {
+            let signature = crash.signature(depth);
+            match groups.iter_mut().find(|group| group.signature == signature) {
+                Some(group) => group.crashes.push(crash),
+                None => groups.push(CrashGroup {
+                    signature,
+                    crashes: vec![crash],
+                }),
+            }
+        }
+        groups.sort_by_key(|group| std::cmp::Reverse(group.crashes.len()));
+        groups
+    }
+}
+
+impl Display for CrashGroup<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} x{}", self.signature, self.crashes.len())?;
+        for crash in &self.crashes {
+            writeln!(
+                f,
+                "  {} {} (pid {}) thread {}",
+                crash.timestamp.format("%m-%d %H:%M:%S%.3f"),
+                crash.process,
+                crash.pid,
+                crash.thread
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::clock::{ReportClock, TimeDisplay};
+    use crate::bugreport::logcat::LogcatSection;
+    use crate::bugreport::signature::DEFAULT_DEPTH;
+    use chrono::NaiveDate;
+
+    const CRASH_LOG: [&str; 16] = [
+        "08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: FATAL EXCEPTION: main",
+        "08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: Process: com.android.systemui, PID: 5140",
+        "08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: java.lang.RuntimeException: Unable to start activity",
+        "08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: \tat android.app.ActivityThread.performLaunchActivity(ActivityThread.java:3782)",
+        "08-16 10:01:30.001  1000  1500  1520 I ActivityManager: Showing crash dialog",
+        "08-16 10:01:30.001 10160  5140  5140 E AndroidRuntime: Caused by: java.lang.NullPointerException: Attempt to read length of 'id 42'",
+        "08-16 10:01:30.001 10160  5140  5140 E AndroidRuntime: \tat com.android.systemui.Dependency.get(Dependency.java:100)",
+        "08-16 10:01:30.001 10160  5140  5140 E AndroidRuntime: \tat com.android.systemui.SystemUIApplication.onCreate(SystemUIApplication.java:80)",
+        "08-16 10:01:30.001 10160  5140  5140 E AndroidRuntime: \t... 12 more",
+        "08-16 10:05:10.000 10160  6200  6200 E AndroidRuntime: FATAL EXCEPTION: main",
+        "08-16 10:05:10.000 10160  6200  6200 E AndroidRuntime: Process: com.android.systemui, PID: 6200",
+        "08-16 10:05:10.000 10160  6200  6200 E AndroidRuntime: java.lang.RuntimeException: Unable to start activity",
+        "08-16 10:05:10.001 10160  6200  6200 E AndroidRuntime: Caused by: java.lang.NullPointerException: Attempt to read length of 'id 7'",
+        "08-16 10:05:10.001 10160  6200  6200 E AndroidRuntime: \tat com.android.systemui.Dependency.get(Dependency.java:104)",
+        "08-16 10:05:10.001 10160  6200  6200 E AndroidRuntime: \tat com.android.systemui.SystemUIApplication.onCreate(SystemUIApplication.java:81)",
+        "08-16 10:06:00.000 10170  7000  7001 E AndroidRuntime: FATAL EXCEPTION: Thread-2",
+    ];
+
+    fn crashes() -> Vec<JavaCrash> {
+        let reference = NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_opt(10, 2, 11)
+            .unwrap();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&CRASH_LOG, &ReportClock::new(None, TimeDisplay::Device), reference);
+        JavaCrash::parse(section.lines())
+    }
+
+    #[test]
+    fn test_parse() {
+        let crashes = crashes();
+        assert_eq!(crashes.len(), 3);
+        let first = &crashes[0];
+        assert_eq!((first.pid, first.thread.as_str()), (5140, "main"));
+        assert_eq!(first.process, "com.android.systemui");
+        assert_eq!(first.exception, "java.lang.NullPointerException: Attempt to read length of 'id 42'");
+        assert_eq!(first.frames.len(), 2);
+        assert_eq!(crashes[2].thread, "Thread-2");
+        assert!(crashes[2].exception.is_empty());
+    }
+
+    #[test]
+    fn test_group() {
+        let crashes = crashes();
+        let groups = CrashGroup::group(&crashes, DEFAULT_DEPTH);
+        // The same NPE after a restart, on other lines, is one group
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].crashes.len(), 2);
+        assert_eq!(
+            groups[0].signature.get_key(),
+            "java.lang.NullPointerException at com.android.systemui.Dependency.get < com.android.systemui.SystemUIApplication.onCreate"
+        );
+        let text = groups[0].to_string();
+        assert!(text.starts_with(&format!("{} java.lang.NullPointerException", groups[0].signature.get_id())));
+        assert!(text.contains("x2\n  08-16 10:01:30.000 com.android.systemui (pid 5140) thread main\n"));
+    }
+}
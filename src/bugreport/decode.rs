@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// How bytes that are not valid UTF-8 are handled while reading a bugreport. Kernel logs and
+/// binary dumpsys output regularly contain such bytes, so decoding is done line by line and a
+/// bad line never affects its neighbours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DecodeMode {
+    /// Fail with [`Error::BadEncoding`] on the first invalid line.
+    Strict,
+    /// Replace every invalid sequence with U+FFFD.
+    #[default]
+    Replace,
+    /// Drop invalid sequences and keep the valid rest of the line.
+    Skip,
+}
+
+/// Splits raw bytes into lines the same way [`str::lines`] does: on `\n`, with a trailing `\r`
+/// stripped and no empty line after a final newline.
+pub struct RawLines<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> RawLines<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    /// Byte offset at which the next line starts.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for RawLines<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let rest = &self.bytes[self.offset..];
+        if rest.is_empty() {
+            return None;
+        }
+        let (line, consumed) = match memchr::memchr(b'\n', rest) {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        self.offset += consumed;
+        Some(line.strip_suffix(b"\r").unwrap_or(line))
+    }
+}
+
+/// Decodes one line. The flag is `true` when the line had to be repaired.
+pub fn decode_line(bytes: &[u8], mode: DecodeMode) -> Result<(Cow<'_, str>, bool)> {
+    if let Ok(line) = std::str::from_utf8(bytes) {
+        return Ok((Cow::Borrowed(line), false));
+    }
+    match mode {
+        DecodeMode::Strict => Err(Error::BadEncoding {
+            line: None,
+            section: None,
+        }),
+        DecodeMode::Replace => Ok((String::from_utf8_lossy(bytes), true)),
+        DecodeMode::Skip => {
+            let line = bytes.utf8_chunks().map(|chunk| chunk.valid()).collect::<String>();
+            Ok((Cow::Owned(line), true))
+        }
+    }
+}
+
+/// Decodes every line of `bytes`, numbering them from `first_line`. Returns the lines together
+/// with the numbers of the lines that were repaired; line numbering is never shifted by a repair.
+pub fn decode_lines(
+    bytes: &[u8],
+    mode: DecodeMode,
+    first_line: usize,
+) -> Result<(Vec<Cow<'_, str>>, Vec<usize>)> {
+    let mut lines = Vec::new();
+    let mut repaired = Vec::new();
+    for (index, raw) in RawLines::new(bytes).enumerate() {
+        let line_number = first_line + index;
+        let (line, was_repaired) = decode_line(raw, mode).map_err(|e| e.at_line(line_number))?;
+        if was_repaired {
+            repaired.push(line_number);
+        }
+        lines.push(line);
+    }
+    Ok((lines, repaired))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &[u8] = b"first\r\nbad \xff\xfe byte\n\nlast \xe4\xb8\xad\n";
+
+    #[test]
+    fn test_raw_lines_match_str_lines() {
+        let text = "a\r\nb\n\nc\nd";
+        let mut raw_lines = RawLines::new(text.as_bytes());
+        let first = raw_lines.next();
+        assert_eq!(raw_lines.offset(), 3);
+        let raw: Vec<&[u8]> = first.into_iter().chain(raw_lines).collect();
+        let expected: Vec<&[u8]> = text.lines().map(str::as_bytes).collect();
+        assert_eq!(raw, expected);
+    }
+
+    #[test]
+    fn test_decode_modes() {
+        let (lines, repaired) = decode_lines(RAW, DecodeMode::Replace, 1).unwrap();
+        assert_eq!(lines, vec!["first", "bad \u{fffd}\u{fffd} byte", "", "last 中"]);
+        assert_eq!(repaired, vec![2]);
+
+        let (lines, repaired) = decode_lines(RAW, DecodeMode::Skip, 1).unwrap();
+        assert_eq!(lines, vec!["first", "bad  byte", "", "last 中"]);
+        assert_eq!(repaired, vec![2]);
+
+        assert!(matches!(
+            decode_lines(RAW, DecodeMode::Strict, 1),
+            Err(Error::BadEncoding { line: Some(2), .. })
+        ));
+    }
+}
@@ -0,0 +1,59 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{DumpsysParser, ServiceModel};
+use crate::error::{Error, Result};
+
+lazy_static! {
+    //   ResumedActivity: ActivityRecord{2b5e7f2 u0 com.android.launcher3/.Launcher t12}
+    static ref RESUMED: Regex =
+        Regex::new(r"(?:ResumedActivity: |mResumedActivity: |topResumedActivity=)ActivityRecord\{\S+ u\d+ (\S+)").unwrap();
+}
+
+/// The resumed activities from `dumpsys activity`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActivityState {
+    /// Components such as `com.android.launcher3/.Launcher`, one per display, without
+    /// duplicates.
+    pub resumed_activities: Vec<String>,
+}
+
+pub struct ActivityParser;
+
+impl DumpsysParser for ActivityParser {
+    fn service(&self) -> &str {
+        "activity"
+    }
+
+    fn parse(&self, data: &str) -> Result<ServiceModel> {
+        let mut resumed_activities: Vec<String> = Vec::new();
+        for caps in RESUMED.captures_iter(data) {
+            let component = caps.get(1).unwrap().as_str().trim_end_matches('}');
+            if !resumed_activities.iter().any(|known| known == component) {
+                resumed_activities.push(component.to_string());
+            }
+        }
+        if !data.contains("ACTIVITY MANAGER") {
+            return Err(Error::malformed("dumpsys activity"));
+        }
+        Ok(Box::new(ActivityState { resumed_activities }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = "DUMP OF SERVICE activity:
+ACTIVITY MANAGER ACTIVITIES (dumpsys activity activities)
+Display #0 (activities from top to bottom):
+  ResumedActivity: ActivityRecord{2b5e7f2 u0 com.android.launcher3/.Launcher t12}
+  mResumedActivity: ActivityRecord{2b5e7f2 u0 com.android.launcher3/.Launcher t12}
+";
+        let model = ActivityParser.parse(data).unwrap();
+        let activity = model.downcast_ref::<ActivityState>().unwrap();
+        assert_eq!(activity.resumed_activities, vec!["com.android.launcher3/.Launcher"]);
+    }
+}
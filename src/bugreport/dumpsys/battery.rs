@@ -0,0 +1,77 @@
+use super::{field, DumpsysParser, ServiceModel};
+use crate::error::{Error, Result};
+
+/// `dumpsys battery`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatteryState {
+    pub ac_powered: bool,
+    pub usb_powered: bool,
+    pub wireless_powered: bool,
+    pub status: Option<u32>,
+    pub health: Option<u32>,
+    pub level: Option<u32>,
+    pub scale: Option<u32>,
+    pub voltage_mv: Option<u32>,
+    /// Tenths of a degree Celsius, as the service prints it.
+    pub temperature: Option<i32>,
+    pub technology: Option<String>,
+}
+
+pub struct BatteryParser;
+
+impl DumpsysParser for BatteryParser {
+    fn service(&self) -> &str {
+        "battery"
+    }
+
+    fn parse(&self, data: &str) -> Result<ServiceModel> {
+        if field(data, "level").is_none() {
+            return Err(Error::malformed("dumpsys battery"));
+        }
+        let flag = |key: &str| field(data, key) == Some("true");
+        let number = |key: &str| field(data, key).and_then(|value| value.parse().ok());
+        Ok(Box::new(BatteryState {
+            ac_powered: flag("AC powered"),
+            usb_powered: flag("USB powered"),
+            wireless_powered: flag("Wireless powered"),
+            status: number("status"),
+            health: number("health"),
+            level: number("level"),
+            scale: number("scale"),
+            voltage_mv: number("voltage"),
+            temperature: field(data, "temperature").and_then(|value| value.parse().ok()),
+            technology: field(data, "technology").map(str::to_string),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = "DUMP OF SERVICE battery:
+Current Battery Service state:
+  AC powered: false
+  USB powered: true
+  Wireless powered: false
+  status: 2
+  health: 2
+  present: true
+  level: 87
+  scale: 100
+  voltage: 4312
+  temperature: 305
+  technology: Li-poly
+";
+        let model = BatteryParser.parse(data).unwrap();
+        let battery = model.downcast_ref::<BatteryState>().unwrap();
+        assert!(battery.usb_powered && !battery.ac_powered);
+        assert_eq!(battery.level, Some(87));
+        assert_eq!(battery.voltage_mv, Some(4312));
+        assert_eq!(battery.temperature, Some(305));
+        assert_eq!(battery.technology.as_deref(), Some("Li-poly"));
+        assert!(BatteryParser.parse("Can't find service: battery").is_err());
+    }
+}
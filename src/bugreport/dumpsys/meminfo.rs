@@ -0,0 +1,105 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{field, DumpsysParser, ServiceModel};
+use crate::error::{Error, Result};
+
+lazy_static! {
+    //     312,456K: com.android.systemui (pid 5140 / activities)
+    static ref PROCESS_PSS: Regex = Regex::new(r"^\s*([\d,]+)K: (\S+) \(pid (\d+)").unwrap();
+}
+
+/// PSS of one process from the `Total PSS by process` table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessMemory {
+    pub pid: u32,
+    pub name: String,
+    pub pss_kb: u64,
+}
+
+/// `dumpsys meminfo`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemInfo {
+    pub total_ram_kb: Option<u64>,
+    pub free_ram_kb: Option<u64>,
+    pub used_ram_kb: Option<u64>,
+    pub lost_ram_kb: Option<u64>,
+    /// Largest first, as dumpsys lists them.
+    pub processes: Vec<ProcessMemory>,
+}
+
+/// `11,699,716K (status normal)` -> `11699716`.
+fn kilobytes(value: &str) -> Option<u64> {
+    let number = value.split('K').next()?;
+    number.replace(',', "").trim().parse().ok()
+}
+
+pub struct MeminfoParser;
+
+impl DumpsysParser for MeminfoParser {
+    fn service(&self) -> &str {
+        "meminfo"
+    }
+
+    fn parse(&self, data: &str) -> Result<ServiceModel> {
+        let processes: Vec<ProcessMemory> = data
+            .lines()
+            .skip_while(|line| !line.starts_with("Total PSS by process"))
+            .skip(1)
+            .take_while(|line| !line.trim().is_empty())
+            .filter_map(|line| {
+                let caps = PROCESS_PSS.captures(line)?;
+                Some(ProcessMemory {
+                    pid: caps.get(3).unwrap().as_str().parse().ok()?,
+                    name: caps.get(2).unwrap().as_str().to_string(),
+                    pss_kb: kilobytes(caps.get(1).unwrap().as_str())?,
+                })
+            })
+            .collect();
+        let total_ram_kb = field(data, "Total RAM").and_then(kilobytes);
+        if total_ram_kb.is_none() && processes.is_empty() {
+            return Err(Error::malformed("dumpsys meminfo"));
+        }
+        Ok(Box::new(MemInfo {
+            total_ram_kb,
+            free_ram_kb: field(data, "Free RAM").and_then(kilobytes),
+            used_ram_kb: field(data, "Used RAM").and_then(kilobytes),
+            lost_ram_kb: field(data, "Lost RAM").and_then(kilobytes),
+            processes,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = "DUMP OF SERVICE meminfo:
+Applications Memory Usage (in Kilobytes):
+Uptime: 1920000 Realtime: 1920000
+
+Total PSS by process:
+    312,456K: com.android.systemui (pid 5140 / activities)
+     98,765K: system (pid 2270)
+
+Total PSS by OOM adjustment:
+    411,221K: Native
+
+Total RAM: 11,699,716K (status normal)
+ Free RAM: 5,123,456K (  123,456K cached pss +   5,000,000K cached kernel)
+ Used RAM: 6,000,000K (5,000,000K used pss + 1,000,000K kernel)
+ Lost RAM:   576,260K
+";
+        let model = MeminfoParser.parse(data).unwrap();
+        let meminfo = model.downcast_ref::<MemInfo>().unwrap();
+        assert_eq!(meminfo.total_ram_kb, Some(11_699_716));
+        assert_eq!(meminfo.free_ram_kb, Some(5_123_456));
+        assert_eq!(meminfo.lost_ram_kb, Some(576_260));
+        assert_eq!(meminfo.processes.len(), 2);
+        assert_eq!(meminfo.processes[0].name, "com.android.systemui");
+        assert_eq!(meminfo.processes[0].pid, 5140);
+        assert_eq!(meminfo.processes[0].pss_kb, 312_456);
+    }
+}
@@ -0,0 +1,222 @@
+use lazy_static::lazy_static;
+use chrono::Duration;
+use regex::Regex;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::section::{is_service_timeout, parse_seconds};
+use crate::error::Result;
+
+pub mod activity;
+pub mod battery;
+pub mod meminfo;
+pub mod package;
+pub mod power;
+pub mod window;
+
+lazy_static::lazy_static!(
+    static ref DUMPSYS: Regex= Regex::new(r"-{9} (\d+\.\d+)s was the duration of dumpsys (.*), ending at").unwrap();
+);
+
+/// A typed model built from the output of one dumpsys service.
+pub type ServiceModel = Box<dyn Any + Send + Sync>;
+
+/// Turns the raw output of one dumpsys service into a typed model.
+pub trait DumpsysParser: Send + Sync {
+    /// The service name as dumpsys prints it, e.g. `meminfo`.
+    fn service(&self) -> &str;
+    fn parse(&self, data: &str) -> Result<ServiceModel>;
+}
+
+// Global dumpsys parser repository, keyed by service name
+lazy_static! {
+    static ref DUMPSYS_PARSERS: Mutex<HashMap<String, Arc<dyn DumpsysParser>>> = {
+        let builtin: [Arc<dyn DumpsysParser>; 6] = [
+            Arc::new(activity::ActivityParser),
+            Arc::new(battery::BatteryParser),
+            Arc::new(meminfo::MeminfoParser),
+            Arc::new(package::PackageParser),
+            Arc::new(power::PowerParser),
+            Arc::new(window::WindowParser),
+        ];
+        let parsers = builtin
+            .into_iter()
+            .map(|parser| (parser.service().to_string(), parser))
+            .collect();
+        Mutex::new(parsers)
+    };
+}
+
+// Dumpsys parser repository manager
+pub struct DumpsysParserRepo;
+
+impl DumpsysParserRepo {
+    /// Register a parser, replacing any earlier parser for the same service
+    pub fn register(parser: Arc<dyn DumpsysParser>) {
+        DUMPSYS_PARSERS
+            .lock()
+            .unwrap()
+            .insert(parser.service().to_string(), parser);
+    }
+
+    /// Find the parser for a service
+    pub fn find(service: &str) -> Option<Arc<dyn DumpsysParser>> {
+        DUMPSYS_PARSERS.lock().unwrap().get(service).map(Arc::clone)
+    }
+
+    /// Names of every service with a registered parser
+    pub fn services() -> Vec<String> {
+        let mut services: Vec<String> = DUMPSYS_PARSERS.lock().unwrap().keys().cloned().collect();
+        services.sort();
+        services
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Dumpsys(Vec<DumpsysEntry>);
+
+pub struct DumpsysEntry {
+    pub name: String,
+    pub data: String,
+    /// How long dumpsys spent on the service.
+    pub duration: Option<Duration>,
+    /// dumpsys gave up on the service, so `data` is cut short.
+    pub timed_out: bool,
+    /// `None` when no parser is registered for the service or the parser failed; `data` is
+    /// always there to fall back on.
+    model: Option<ServiceModel>,
+}
+
+impl std::fmt::Debug for DumpsysEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DumpsysEntry")
+            .field("name", &self.name)
+            .field("data", &self.data)
+            .field("duration", &self.duration)
+            .field("timed_out", &self.timed_out)
+            .field("parsed", &self.model.is_some())
+            .finish()
+    }
+}
+
+impl DumpsysEntry {
+    fn new(name: String, data: String, duration: Option<Duration>) -> Self {
+        let timed_out = data.lines().any(|line| is_service_timeout(line.as_bytes()));
+        let model = DumpsysParserRepo::find(&name).and_then(|parser| parser.parse(&data).ok());
+        Self {
+            name,
+            data,
+            duration,
+            timed_out,
+            model,
+        }
+    }
+
+    /// The typed model, if the service was parsed into a `T`.
+    pub fn model<T: 'static>(&self) -> Option<&T> {
+        self.model.as_ref()?.downcast_ref()
+    }
+
+    pub fn is_parsed(&self) -> bool {
+        self.model.is_some()
+    }
+}
+
+impl Dumpsys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, lines: &[&str]) {
+        let mut temp = String::new();
+        for line in lines {
+            if let Some(captures) = DUMPSYS.captures(line) {
+                let duration = parse_seconds(&captures[1]);
+                let name = captures[2].to_string();
+                self.0.push(DumpsysEntry::new(name, temp.clone(), duration));
+                temp.clear();
+            } else {
+                temp.push_str(line);
+                temp.push('\n');
+            }
+        }
+    }
+
+    pub fn get_entries(&self) -> &[DumpsysEntry] {
+        &self.0
+    }
+
+    pub fn get_service(&self, service: &str) -> Option<&DumpsysEntry> {
+        self.0.iter().find(|entry| entry.name == service)
+    }
+
+    /// The typed model of `service`, e.g. `get_model::<MemInfo>("meminfo")`.
+    pub fn get_model<T: 'static>(&self, service: &str) -> Option<&T> {
+        self.get_service(service)?.model()
+    }
+
+    /// The raw output of `service`, whether or not it was parsed.
+    pub fn get_raw(&self, service: &str) -> Option<&str> {
+        self.get_service(service).map(|entry| entry.data.as_str())
+    }
+}
+
+/// `Key: value` and `key=value` lines are how most services print their state.
+pub(crate) fn field<'a>(data: &'a str, key: &str) -> Option<&'a str> {
+    data.lines().find_map(|line| {
+        let line = line.trim_start();
+        let rest = line.strip_prefix(key)?;
+        rest.strip_prefix(": ")
+            .or_else(|| rest.strip_prefix('='))
+            .map(str::trim)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct UptimeParser;
+
+    impl DumpsysParser for UptimeParser {
+        fn service(&self) -> &str {
+            "test_uptime"
+        }
+
+        fn parse(&self, data: &str) -> Result<ServiceModel> {
+            let seconds: u64 = field(data, "Uptime")
+                .and_then(|value| value.parse().ok())
+                .ok_or(crate::error::Error::malformed("uptime"))?;
+            Ok(Box::new(seconds))
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        DumpsysParserRepo::register(Arc::new(UptimeParser));
+        assert!(DumpsysParserRepo::services().contains(&"test_uptime".to_string()));
+
+        let lines = [
+            "-------------------------------------------------------------------------------",
+            "DUMP OF SERVICE test_uptime:",
+            "Uptime: 1920",
+            "--------- 0.001s was the duration of dumpsys test_uptime, ending at: 2024-08-16 10:02:40",
+            "DUMP OF SERVICE unknown_service:",
+            "whatever it prints",
+            "*** SERVICE 'unknown_service' DUMP TIMEOUT (10000ms) EXPIRED ***",
+            "--------- 10.003s was the duration of dumpsys unknown_service, ending at: 2024-08-16 10:02:50",
+        ];
+        let mut dumpsys = Dumpsys::new();
+        dumpsys.parse(&lines);
+        assert_eq!(dumpsys.get_entries().len(), 2);
+        assert_eq!(dumpsys.get_model::<u64>("test_uptime"), Some(&1920));
+        assert_eq!(dumpsys.get_model::<String>("test_uptime"), None);
+        let unknown = dumpsys.get_service("unknown_service").unwrap();
+        assert!(!unknown.is_parsed());
+        assert!(unknown.timed_out);
+        assert_eq!(unknown.duration, Some(Duration::milliseconds(10003)));
+        assert!(!dumpsys.get_service("test_uptime").unwrap().timed_out);
+        assert!(dumpsys.get_raw("unknown_service").unwrap().contains("whatever it prints"));
+    }
+}
@@ -0,0 +1,89 @@
+use super::{DumpsysParser, ServiceModel};
+use crate::error::{Error, Result};
+
+/// One entry of the `Packages:` list in `dumpsys package`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PackageInfo {
+    pub name: String,
+    pub version_code: Option<u64>,
+    pub version_name: Option<String>,
+}
+
+/// `dumpsys package`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Packages(pub Vec<PackageInfo>);
+
+impl Packages {
+    pub fn get(&self, name: &str) -> Option<&PackageInfo> {
+        self.0.iter().find(|package| package.name == name)
+    }
+}
+
+pub struct PackageParser;
+
+impl DumpsysParser for PackageParser {
+    fn service(&self) -> &str {
+        "package"
+    }
+
+    fn parse(&self, data: &str) -> Result<ServiceModel> {
+        let mut packages: Vec<PackageInfo> = Vec::new();
+        for line in data.lines() {
+            let line = line.trim();
+            // Package [com.android.systemui] (8a0c2f1):
+            if let Some(rest) = line.strip_prefix("Package [") {
+                if let Some((name, _)) = rest.split_once(']') {
+                    packages.push(PackageInfo {
+                        name: name.to_string(),
+                        ..Default::default()
+                    });
+                }
+                continue;
+            }
+            let Some(package) = packages.last_mut() else {
+                continue;
+            };
+            // versionCode=35 minSdk=35 targetSdk=35
+            if let Some(rest) = line.strip_prefix("versionCode=") {
+                if package.version_code.is_none() {
+                    let code = rest.split_whitespace().next().unwrap_or_default();
+                    package.version_code = code.parse().ok();
+                }
+            } else if let Some(rest) = line.strip_prefix("versionName=") {
+                if package.version_name.is_none() {
+                    package.version_name = Some(rest.to_string());
+                }
+            }
+        }
+        if packages.is_empty() {
+            return Err(Error::malformed("dumpsys package"));
+        }
+        Ok(Box::new(Packages(packages)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = "DUMP OF SERVICE package:
+Packages:
+  Package [com.android.systemui] (8a0c2f1):
+    userId=10160
+    versionCode=35 minSdk=35 targetSdk=35
+    versionName=15
+  Package [com.android.launcher3] (1f2e3d4):
+    versionCode=1000 minSdk=34 targetSdk=35
+    versionName=1.0.0.1
+";
+        let model = PackageParser.parse(data).unwrap();
+        let packages = model.downcast_ref::<Packages>().unwrap();
+        assert_eq!(packages.0.len(), 2);
+        let systemui = packages.get("com.android.systemui").unwrap();
+        assert_eq!(systemui.version_code, Some(35));
+        assert_eq!(systemui.version_name.as_deref(), Some("15"));
+        assert_eq!(packages.get("com.android.launcher3").unwrap().version_code, Some(1000));
+    }
+}
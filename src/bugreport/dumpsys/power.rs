@@ -0,0 +1,92 @@
+use super::{field, DumpsysParser, ServiceModel};
+use crate::error::{Error, Result};
+
+/// One held wake lock, e.g. `PARTIAL_WAKE_LOCK 'AudioMix' ACQ=-1s577ms (uid=1041 pid=1234)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WakeLock {
+    pub kind: String,
+    pub tag: String,
+    pub uid: Option<u32>,
+    pub pid: Option<u32>,
+}
+
+/// `dumpsys power`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PowerState {
+    /// `Awake`, `Asleep`, `Dozing` or `Dreaming`.
+    pub wakefulness: Option<String>,
+    pub wake_locks: Vec<WakeLock>,
+}
+
+fn parse_wake_lock(line: &str) -> Option<WakeLock> {
+    let line = line.trim();
+    let (kind, rest) = line.split_once(char::is_whitespace)?;
+    let tag = rest.trim_start().strip_prefix('\'')?.split('\'').next()?;
+    let id = |key: &str| {
+        let start = rest.find(key)? + key.len();
+        let digits: String = rest[start..].chars().take_while(char::is_ascii_digit).collect();
+        digits.parse().ok()
+    };
+    Some(WakeLock {
+        kind: kind.to_string(),
+        tag: tag.to_string(),
+        uid: id("uid="),
+        pid: id("pid="),
+    })
+}
+
+pub struct PowerParser;
+
+impl DumpsysParser for PowerParser {
+    fn service(&self) -> &str {
+        "power"
+    }
+
+    fn parse(&self, data: &str) -> Result<ServiceModel> {
+        let wakefulness = field(data, "mWakefulness").map(str::to_string);
+        if wakefulness.is_none() {
+            return Err(Error::malformed("dumpsys power"));
+        }
+        let wake_locks = data
+            .lines()
+            .skip_while(|line| !line.starts_with("Wake Locks: size="))
+            .skip(1)
+            .take_while(|line| !line.trim().is_empty())
+            .filter_map(parse_wake_lock)
+            .collect();
+        Ok(Box::new(PowerState {
+            wakefulness,
+            wake_locks,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = "DUMP OF SERVICE power:
+POWER MANAGER (dumpsys power)
+
+Power Manager State:
+  mWakefulness=Awake
+  mWakefulnessChanging=false
+
+Wake Locks: size=2
+  PARTIAL_WAKE_LOCK              'AudioMix' ACQ=-1s577ms (uid=1041 pid=1234)
+  SCREEN_BRIGHT_WAKE_LOCK        'WindowManager' ON_AFTER_RELEASE ACQ=-3m2s (uid=1000 pid=2270)
+
+Suspend Blockers: size=4
+";
+        let model = PowerParser.parse(data).unwrap();
+        let power = model.downcast_ref::<PowerState>().unwrap();
+        assert_eq!(power.wakefulness.as_deref(), Some("Awake"));
+        assert_eq!(power.wake_locks.len(), 2);
+        assert_eq!(power.wake_locks[0].kind, "PARTIAL_WAKE_LOCK");
+        assert_eq!(power.wake_locks[0].tag, "AudioMix");
+        assert_eq!(power.wake_locks[0].uid, Some(1041));
+        assert_eq!(power.wake_locks[1].pid, Some(2270));
+    }
+}
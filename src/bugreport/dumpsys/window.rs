@@ -0,0 +1,48 @@
+use super::{field, DumpsysParser, ServiceModel};
+use crate::error::{Error, Result};
+
+/// The focus state from `dumpsys window`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowState {
+    /// e.g. `Window{6c1a3b u0 com.android.launcher3/com.android.launcher3.Launcher}`.
+    pub current_focus: Option<String>,
+    /// e.g. `ActivityRecord{2b5e7f2 u0 com.android.launcher3/.Launcher t12}`.
+    pub focused_app: Option<String>,
+}
+
+pub struct WindowParser;
+
+impl DumpsysParser for WindowParser {
+    fn service(&self) -> &str {
+        "window"
+    }
+
+    fn parse(&self, data: &str) -> Result<ServiceModel> {
+        let state = WindowState {
+            current_focus: field(data, "mCurrentFocus").map(str::to_string),
+            focused_app: field(data, "mFocusedApp").map(str::to_string),
+        };
+        if state == WindowState::default() {
+            return Err(Error::malformed("dumpsys window"));
+        }
+        Ok(Box::new(state))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = "DUMP OF SERVICE window:
+WINDOW MANAGER WINDOWS (dumpsys window windows)
+  mCurrentFocus=Window{6c1a3b u0 com.android.launcher3/com.android.launcher3.Launcher}
+  mFocusedApp=ActivityRecord{2b5e7f2 u0 com.android.launcher3/.Launcher t12}
+";
+        let model = WindowParser.parse(data).unwrap();
+        let window = model.downcast_ref::<WindowState>().unwrap();
+        assert!(window.current_focus.as_deref().unwrap().contains("Launcher}"));
+        assert!(window.focused_app.as_deref().unwrap().starts_with("ActivityRecord{2b5e7f2"));
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt::{self, Display, Formatter};
+
+use super::traces::{AnrTraces, LockKind, LockRef, ProcessDump, StackFrame, ThreadDump, ThreadState};
+
+/// Chains with at least this many waits are worth reporting on their own.
+pub const LONG_CHAIN: usize = 2;
+
+/// What the thread at the end of a chain is doing instead of releasing its lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HolderState {
+    /// Waiting for the reply of an outgoing binder call.
+    BinderCall,
+    /// Running or blocked in native code, e.g. I/O or a HAL call.
+    Native,
+    Running,
+    /// In `Object.wait()` or parked, waiting for some other thread to signal it.
+    Waiting,
+    Sleeping,
+    /// Blocked on a lock whose owner the dump does not tell.
+    Blocked,
+    Unknown,
+}
+
+impl HolderState {
+    pub fn of(thread: &ThreadDump) -> Self {
+        let in_binder_call = thread.frames.iter().any(|frame| match frame {
+            StackFrame::Java { method, .. } => method == "android.os.BinderProxy.transactNative",
+            StackFrame::Native { symbol: Some(symbol), .. } => {
+                symbol.contains("IPCThreadState::transact") || symbol.contains("IPCThreadState::waitForResponse")
+            }
+            _ => false,
+        });
+        if in_binder_call {
+            return HolderState::BinderCall;
+        }
+        match thread.state {
+            ThreadState::Native => HolderState::Native,
+            ThreadState::Runnable => HolderState::Running,
+            ThreadState::Waiting | ThreadState::TimedWaiting => HolderState::Waiting,
+            ThreadState::Sleeping => HolderState::Sleeping,
+            ThreadState::Blocked => HolderState::Blocked,
+            _ => match thread.frames.first() {
+                Some(StackFrame::Native { .. }) => HolderState::Native,
+                _ => HolderState::Unknown,
+            },
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            HolderState::BinderCall => "in a binder call",
+            HolderState::Native => "in native code",
+            HolderState::Running => "running",
+            HolderState::Waiting => "waiting",
+            HolderState::Sleeping => "sleeping",
+            HolderState::Blocked => "blocked",
+            HolderState::Unknown => "in an unknown state",
+        }
+    }
+}
+
+impl Display for HolderState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A thread blocked on a lock another thread of the same process holds.
+#[derive(Debug, Clone, Copy)]
+pub struct WaitEdge<'p> {
+    /// Position of the holder in [`ProcessDump::threads`].
+    pub holder: usize,
+    pub lock: &'p LockRef,
+}
+
+/// Who waits for whom within one process dump. A blocked thread waits for one lock, so every
+/// thread has at most one outgoing edge and following them never branches.
+#[derive(Debug)]
+pub struct LockGraph<'p> {
+    process: &'p ProcessDump,
+    waits: Vec<Option<WaitEdge<'p>>>,
+}
+
+/// Threads that wait for each other in a cycle and will never make progress.
+#[derive(Debug)]
+pub struct Deadlock<'p> {
+    pub process: &'p ProcessDump,
+    /// Each thread waits for a lock the next one holds; the last waits for the first.
+    pub threads: Vec<&'p ThreadDump>,
+    /// `locks[i]` is the lock `threads[i]` waits for.
+    pub locks: Vec<&'p LockRef>,
+}
+
+/// Threads that wait for each other in a line, ending at a thread that holds a lock but waits
+/// for none.
+#[derive(Debug)]
+pub struct LockChain<'p> {
+    pub process: &'p ProcessDump,
+    /// The first thread waits for a lock the second holds, and so on; the last is the root.
+    pub threads: Vec<&'p ThreadDump>,
+    /// `locks[i]` is the lock `threads[i]` waits for.
+    pub locks: Vec<&'p LockRef>,
+    pub root_state: HolderState,
+}
+
+impl<'p> LockGraph<'p> {
+    /// Monitors (`waiting to lock`) and `java.util.concurrent` locks (`parking to wait for`)
+    /// become edges when their holder can be found, see [`ProcessDump::lock_holder`].
+    /// `waiting on` is left out: a thread in `Object.wait()` gave its monitor up.
+    pub fn build(process: &'p ProcessDump) -> Self {
+        let position = |holder: &ThreadDump| process.threads.iter().position(|t| std::ptr::eq(t, holder));
+        let waits = process
+            .threads
+            .iter()
+            .map(|thread| {
+                let lock = thread.waiting_lock()?;
+                let holder = position(process.lock_holder(lock)?)?;
+                Some(WaitEdge { holder, lock })
+            })
+            .collect();
+        Self { process, waits }
+    }
+
+    pub fn get_process(&self) -> &'p ProcessDump {
+        self.process
+    }
+
+    /// The edge leaving the thread at `position`, if it waits for a known holder.
+    pub fn waits_for(&self, position: usize) -> Option<WaitEdge<'p>> {
+        self.waits.get(position).copied().flatten()
+    }
+
+    /// Position of `thread` in the process, which must be the graph's.
+    pub fn position(&self, thread: &ThreadDump) -> Option<usize> {
+        self.process.threads.iter().position(|t| std::ptr::eq(t, thread))
+    }
+
+    /// Follows the waits from the thread at `position` until a thread that waits for nothing.
+    /// `None` if the thread waits for nothing itself or the path runs into a cycle.
+    pub fn chain_from(&self, position: usize) -> Option<LockChain<'p>> {
+        let mut threads = vec![&self.process.threads[position]];
+        let mut locks = Vec::new();
+        let mut visited = vec![false; self.waits.len()];
+        let mut current = position;
+        visited[current] = true;
+        while let Some(edge) = self.waits_for(current) {
+            if visited[edge.holder] {
+                return None;
+            }
+            visited[edge.holder] = true;
+            threads.push(&self.process.threads[edge.holder]);
+            locks.push(edge.lock);
+            current = edge.holder;
+        }
+        let root = *threads.last()?;
+        (!locks.is_empty()).then(|| LockChain {
+            process: self.process,
+            threads,
+            locks,
+            root_state: HolderState::of(root),
+        })
+    }
+
+    /// Every cycle of waits, each reported once.
+    pub fn deadlocks(&self) -> Vec<Deadlock<'p>> {
+        // 0: not seen, 1: on the path being walked, 2: done
+        let mut state = vec![0u8; self.waits.len()];
+        let mut deadlocks = Vec::new();
+        for start in 0..self.waits.len() {
+            let mut path = Vec::new();
+            let mut current = Some(start);
+            while let Some(position) = current.filter(|&position| state[position] == 0) {
+                state[position] = 1;
+                path.push(position);
+                current = self.waits_for(position).map(|edge| edge.holder);
+            }
+            if let Some(position) = current.filter(|&position| state[position] == 1) {
+                let cycle = &path[path.iter().position(|&p| p == position).unwrap()..];
+                deadlocks.push(Deadlock {
+                    process: self.process,
+                    threads: cycle.iter().map(|&p| &self.process.threads[p]).collect(),
+                    locks: cycle.iter().map(|&p| self.waits_for(p).unwrap().lock).collect(),
+                });
+            }
+            for position in path {
+                state[position] = 2;
+            }
+        }
+        deadlocks
+    }
+
+    /// The chains of at least `min_waits` waits, one per thread that nobody waits for. Chains
+    /// that run into a deadlock are left to [`LockGraph::deadlocks`].
+    pub fn chains(&self, min_waits: usize) -> Vec<LockChain<'p>> {
+        let mut waited_for = vec![false; self.waits.len()];
+        for edge in self.waits.iter().flatten() {
+            waited_for[edge.holder] = true;
+        }
+        (0..self.waits.len())
+            .filter(|&position| !waited_for[position])
+            .filter_map(|position| self.chain_from(position))
+            .filter(|chain| chain.locks.len() >= min_waits)
+            .collect()
+    }
+}
+
+impl LockChain<'_> {
+    /// The thread everybody in the chain is waiting for.
+    pub fn root(&self) -> &ThreadDump {
+        self.threads[self.threads.len() - 1]
+    }
+}
+
+fn describe(thread: &ThreadDump) -> String {
+    match (thread.tid, thread.sys_tid) {
+        (Some(tid), Some(sys_tid)) => format!("\"{}\" tid={} sysTid={}", thread.name, tid, sys_tid),
+        (Some(tid), None) => format!("\"{}\" tid={}", thread.name, tid),
+        (None, Some(sys_tid)) => format!("\"{}\" sysTid={}", thread.name, sys_tid),
+        (None, None) => format!("\"{}\"", thread.name),
+    }
+}
+
+fn describe_lock(lock: &LockRef) -> String {
+    let kind = match lock.kind {
+        LockKind::ParkingToWaitFor => "parked on",
+        _ => "waits for",
+    };
+    match &lock.class_name {
+        Some(class_name) => format!("{} <{}> ({})", kind, lock.address, class_name),
+        None => format!("{} <{}>", kind, lock.address),
+    }
+}
+
+fn process_name(process: &ProcessDump) -> &str {
+    process.cmd_line.as_deref().unwrap_or("?")
+}
+
+impl Display for Deadlock<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Deadlock in {} (pid {}):", process_name(self.process), self.process.pid)?;
+        for (i, (thread, lock)) in self.threads.iter().zip(&self.locks).enumerate() {
+            let next = self.threads[(i + 1) % self.threads.len()];
+            writeln!(f, "  {} {} held by {}", describe(thread), describe_lock(lock), describe(next))?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for LockChain<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Lock chain of {} in {} (pid {}):",
+            self.locks.len(),
+            process_name(self.process),
+            self.process.pid
+        )?;
+        for (thread, lock) in self.threads.iter().zip(&self.locks) {
+            writeln!(f, "  {} {}", describe(thread), describe_lock(lock))?;
+        }
+        writeln!(f, "  root {} is {}", describe(self.root()), self.root_state)
+    }
+}
+
+/// Deadlocks and long lock chains across every process of a traces file.
+#[derive(Debug)]
+pub struct LockReport<'t> {
+    pub deadlocks: Vec<Deadlock<'t>>,
+    pub chains: Vec<LockChain<'t>>,
+}
+
+impl<'t> LockReport<'t> {
+    pub fn build(traces: &'t AnrTraces, min_waits: usize) -> Self {
+        let mut report = LockReport {
+            deadlocks: Vec::new(),
+            chains: Vec::new(),
+        };
+        for process in traces.processes() {
+            let graph = LockGraph::build(process);
+            report.deadlocks.extend(graph.deadlocks());
+            report.chains.extend(graph.chains(min_waits));
+        }
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deadlocks.is_empty() && self.chains.is_empty()
+    }
+}
+
+impl Display for LockReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No deadlocks or long lock chains");
+        }
+        for deadlock in &self.deadlocks {
+            write!(f, "{}", deadlock)?;
+        }
+        for chain in &self.chains {
+            write!(f, "{}", chain)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::traces::TEST_TRACES;
+
+    const DEADLOCK: &str = r#"
+----- pid 1500 at 2024-08-16 10:01:30.000000000+0800 -----
+Cmd line: system_server
+
+"main" prio=5 tid=1 Blocked
+  | sysTid=1500 nice=-2 cgrp=foreground
+  at com.android.server.am.ActivityManagerService.broadcastIntent(ActivityManagerService.java:14010)
+  - waiting to lock <0x100> (a com.android.server.am.ActivityManagerService) held by thread 20
+
+"android.display" prio=5 tid=20 Blocked
+  | sysTid=1520 nice=-2 cgrp=foreground
+  at com.android.server.wm.WindowManagerService.relayoutWindow(WindowManagerService.java:2300)
+  - waiting to lock <0x200> (a com.android.server.wm.WindowManagerGlobalLock) held by thread 30
+  - locked <0x100> (a com.android.server.am.ActivityManagerService)
+
+"Binder:1500_1" prio=5 tid=30 Waiting
+  | sysTid=1530 nice=0 cgrp=foreground
+  at jdk.internal.misc.Unsafe.park(Native method)
+  - parking to wait for  <0x300> (a java.util.concurrent.locks.ReentrantLock$NonfairSync) held by thread 20
+  at com.android.server.pm.PackageManagerService.getPackageInfo(PackageManagerService.java:900)
+  - locked <0x200> (a com.android.server.wm.WindowManagerGlobalLock)
+
+"Binder:1500_2" prio=5 tid=40 Blocked
+  | sysTid=1540 nice=0 cgrp=foreground
+  at com.android.server.am.ActivityManagerService.getRunningAppProcesses(ActivityManagerService.java:9000)
+  - waiting to lock <0x200> (a com.android.server.wm.WindowManagerGlobalLock) held by thread 30
+
+"Binder:1500_3" prio=5 tid=50 Waiting
+  | sysTid=1550 nice=0 cgrp=foreground
+  at java.lang.Object.wait(Native method)
+  - waiting on <0x400> (a java.lang.Object)
+
+----- end 1500 -----
+"#;
+
+    #[test]
+    fn test_chain() {
+        let traces = AnrTraces::from_bytes(TEST_TRACES.as_bytes()).unwrap();
+        let process = traces.get_process(1500).next().unwrap();
+        let graph = LockGraph::build(process);
+        assert!(graph.deadlocks().is_empty());
+        let chain = graph.chain_from(0).unwrap();
+        assert_eq!(chain.root().name, "Binder:1500_3");
+        assert_eq!(chain.root_state, HolderState::BinderCall);
+        assert_eq!(chain.locks[0].address, "0x0c2a1b3f");
+        assert!(graph.chain_from(1).is_none());
+        assert_eq!(graph.chains(1).len(), 1);
+        assert!(graph.chains(LONG_CHAIN).is_empty());
+
+        let report = LockReport::build(&traces, 1);
+        assert_eq!(
+            report.to_string(),
+            "Lock chain of 1 in system_server (pid 1500):\n  \"main\" tid=1 sysTid=1500 waits for <0x0c2a1b3f> (com.android.server.am.ActivityManagerService)\n  root \"Binder:1500_3\" tid=42 sysTid=1532 is in a binder call\n"
+        );
+    }
+
+    #[test]
+    fn test_deadlock() {
+        let traces = AnrTraces::from_bytes(DEADLOCK.as_bytes()).unwrap();
+        let process = traces.get_process(1500).next().unwrap();
+        let graph = LockGraph::build(process);
+        let deadlocks = graph.deadlocks();
+        assert_eq!(deadlocks.len(), 1);
+        let names: Vec<&str> = deadlocks[0].threads.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["android.display", "Binder:1500_1"]);
+        assert_eq!(deadlocks[0].locks[1].kind, LockKind::ParkingToWaitFor);
+        // Threads stuck behind the cycle form no chain of their own
+        assert!(graph.chains(1).is_empty());
+        assert!(graph.chain_from(0).is_none());
+        assert!(graph.waits_for(4).is_none());
+
+        let report = LockReport::build(&traces, LONG_CHAIN);
+        assert!(report.to_string().starts_with("Deadlock in system_server (pid 1500):\n  \"android.display\" tid=20 sysTid=1520 waits for <0x200>"));
+        assert!(report.to_string().contains("parked on <0x300>"));
+    }
+}
@@ -0,0 +1,431 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::LogcatLine;
+
+/// Definitions of the events we look at most, in `event-log-tags` syntax. The device's own
+/// `/system/etc/event-log-tags` takes precedence when the zip has it.
+const BUILTIN_TAGS: &str = "\
+2728 power_screen_state (offOrOn|1|5),(becauseOfUser|1|5),(totalTouchDownTime|2|3),(touchCycles|1|1),(latency|1|3)
+30008 am_anr (User|1|5),(pid|1|5),(Package Name|3),(Flags|1|5),(reason|3)
+30009 am_activity_launch_time (User|1|5),(Token|1|5),(Component Name|3),(time|2|3)
+30010 am_proc_bound (User|1|5),(PID|1|5),(Process Name|3)
+30011 am_proc_died (User|1|5),(PID|1|5),(Process Name|3),(OomAdj|1|5),(ProcState|1|5)
+30014 am_proc_start (User|1|5),(PID|1|5),(UID|1|5),(Process Name|3),(Type|3),(Component|3)
+30017 am_low_memory (Num Processes|1|1)
+30021 wm_on_paused_called (Token|1|5),(Component Name|3),(Reason|3),(time|2|3)
+30022 wm_on_resume_called (Token|1|5),(Component Name|3),(Reason|3),(time|2|3)
+30023 am_kill (User|1|5),(PID|1|5),(Process Name|3),(OomAdj|1|5),(Reason|3)
+30039 am_crash (User|1|5),(PID|1|5),(Process Name|3),(Flags|1|5),(Exception|3),(Message|3),(File|3),(Line|1|5),(Recoverable|1|5)
+30040 am_wtf (User|1|5),(PID|1|5),(Process Name|3),(Flags|1|5),(Tag|3),(Message|3)
+30043 wm_set_resumed_activity (User|1|5),(Component Name|3),(Reason|3)
+30049 wm_on_stop_called (Token|1|5),(Component Name|3),(Reason|3),(time|2|3)
+30057 wm_on_create_called (Token|1|5),(Component Name|3),(Reason|3),(time|2|3)
+30059 wm_on_start_called (Token|1|5),(Component Name|3),(Reason|3),(time|2|3)
+30060 wm_on_destroy_called (Token|1|5),(Component Name|3),(Reason|3),(time|2|3)
+62001 input_focus (window|3),(reason|3)
+70000 screen_toggled (screen_state|1|5)
+";
+
+lazy_static! {
+    static ref TAG_LINE: Regex = Regex::new(r"^(\d+)\s+(\S+)(?:\s+(.*))?$").unwrap();
+    static ref TAG_FIELD: Regex = Regex::new(r"\(([^|()]*)\|(\d)(?:\|\d+)?\)").unwrap();
+    static ref BUILTIN: EventLogTags = EventLogTags::parse(BUILTIN_TAGS);
+}
+
+/// The data types of `event-log-tags`, by their number in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventValueKind {
+    Int,
+    Long,
+    String,
+    List,
+    Float,
+}
+
+impl EventValueKind {
+    fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "1" => Some(EventValueKind::Int),
+            "2" => Some(EventValueKind::Long),
+            "3" => Some(EventValueKind::String),
+            "4" => Some(EventValueKind::List),
+            "5" => Some(EventValueKind::Float),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventTagField {
+    pub name: String,
+    pub kind: EventValueKind,
+}
+
+/// One line of `event-log-tags`: `30008 am_anr (User|1|5),(pid|1|5),...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventTagDef {
+    pub number: u32,
+    pub name: String,
+    pub fields: Vec<EventTagField>,
+}
+
+/// Event tag definitions keyed by tag name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLogTags(HashMap<String, EventTagDef>);
+
+impl Default for EventLogTags {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl EventLogTags {
+    /// The built-in definitions of the common `am_*`, `wm_*` and input events.
+    pub fn builtin() -> Self {
+        BUILTIN.clone()
+    }
+
+    /// Reads an `event-log-tags` file. Comments and lines that are not definitions are skipped.
+    pub fn parse(text: &str) -> Self {
+        let definitions = text
+            .lines()
+            .filter_map(|line| {
+                let caps = TAG_LINE.captures(line.trim())?;
+                let fields = caps.get(3).map_or_else(Vec::new, |fields| {
+                    TAG_FIELD
+                        .captures_iter(fields.as_str())
+                        .filter_map(|field| {
+                            Some(EventTagField {
+                                name: field[1].trim().to_string(),
+                                kind: EventValueKind::from_code(&field[2])?,
+                            })
+                        })
+                        .collect()
+                });
+                let definition = EventTagDef {
+                    number: caps[1].parse().ok()?,
+                    name: caps[2].to_string(),
+                    fields,
+                };
+                Some((definition.name.clone(), definition))
+            })
+            .collect();
+        Self(definitions)
+    }
+
+    /// Adds the definitions of `other`, replacing ours for the same tag.
+    pub fn extend(&mut self, other: EventLogTags) {
+        self.0.extend(other.0);
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&EventTagDef> {
+        self.0.get(tag)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventValue {
+    Int(i32),
+    Long(i64),
+    String(String),
+    Float(f32),
+    List(Vec<EventValue>),
+}
+
+impl EventValue {
+    /// Reads a value of a known type; `None` if `raw` is not one.
+    fn parse(raw: &str, kind: EventValueKind) -> Option<Self> {
+        match kind {
+            EventValueKind::Int => raw.parse().ok().map(EventValue::Int),
+            EventValueKind::Long => raw.parse().ok().map(EventValue::Long),
+            EventValueKind::Float => raw.parse().ok().map(EventValue::Float),
+            EventValueKind::String => Some(EventValue::String(raw.to_string())),
+            EventValueKind::List => Some(Self::infer(raw)),
+        }
+    }
+
+    /// Reads a value without a definition: a number if it looks like one, else a string.
+    fn infer(raw: &str) -> Self {
+        if let Some(inner) = raw.strip_prefix('[').and_then(|raw| raw.strip_suffix(']')) {
+            return EventValue::List(split_top_level(inner).into_iter().map(Self::infer).collect());
+        }
+        if let Ok(value) = raw.parse() {
+            return EventValue::Long(value);
+        }
+        match raw.parse() {
+            Ok(value) if raw.contains('.') => EventValue::Float(value),
+            _ => EventValue::String(raw.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            EventValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Ints and longs, widened.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            EventValue::Int(value) => Some(*value as i64),
+            EventValue::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            EventValue::Float(value) => Some(*value as f64),
+            _ => self.as_i64().map(|value| value as f64),
+        }
+    }
+}
+
+impl Display for EventValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            EventValue::Int(value) => write!(f, "{}", value),
+            EventValue::Long(value) => write!(f, "{}", value),
+            EventValue::String(value) => f.write_str(value),
+            EventValue::Float(value) => write!(f, "{}", value),
+            EventValue::List(values) => {
+                let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+                write!(f, "[{}]", values.join(","))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventField {
+    /// `None` when the tag has no definition.
+    pub name: Option<String>,
+    pub value: EventValue,
+}
+
+/// An EVENT LOG line with its payload decoded.
+#[derive(Debug, Clone)]
+pub struct EventRecord {
+    pub line: LogcatLine,
+    pub fields: Vec<EventField>,
+}
+
+impl EventRecord {
+    /// Decodes the payload of `line`, e.g. `[0,5140,com.android.systemui,...]` for `am_anr`,
+    /// with the definition of its tag. Tags without a definition still get their values, in
+    /// order and without names.
+    pub fn decode(line: &LogcatLine, tags: &EventLogTags) -> Self {
+        let fields = match tags.get(&line.tag) {
+            Some(definition) => decode_fields(&line.message, &definition.fields),
+            None => payload_parts(&line.message)
+                .into_iter()
+                .map(|raw| EventField {
+                    name: None,
+                    value: EventValue::infer(raw),
+                })
+                .collect(),
+        };
+        Self {
+            line: line.clone(),
+            fields,
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.line.tag
+    }
+
+    /// The field called `name`, e.g. `event.get("reason")`.
+    pub fn get(&self, name: &str) -> Option<&EventValue> {
+        self.fields
+            .iter()
+            .find(|field| field.name.as_deref() == Some(name))
+            .map(|field| &field.value)
+    }
+
+    pub fn get_index(&self, index: usize) -> Option<&EventValue> {
+        self.fields.get(index).map(|field| &field.value)
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name)?.as_str()
+    }
+
+    pub fn get_i64(&self, name: &str) -> Option<i64> {
+        self.get(name)?.as_i64()
+    }
+}
+
+/// Splits on the commas that are not inside a nested list.
+fn split_top_level(payload: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in payload.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&payload[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&payload[start..]);
+    parts
+}
+
+/// `[a,b,c]` holds several values, anything else is a single one.
+fn payload_parts(message: &str) -> Vec<&str> {
+    match message.strip_prefix('[').and_then(|m| m.strip_suffix(']')) {
+        Some(inner) => split_top_level(inner),
+        None => vec![message],
+    }
+}
+
+fn decode_fields(message: &str, definition: &[EventTagField]) -> Vec<EventField> {
+    // A single string may contain commas, so it is never split
+    let parts = match definition {
+        [field] if field.kind != EventValueKind::List => vec![message],
+        _ => payload_parts(message),
+    };
+
+    // More parts than fields means a string value contained commas. Give the surplus to the
+    // last string field after which every other value still has its declared type.
+    let surplus = parts.len().saturating_sub(definition.len());
+    let strings = definition
+        .iter()
+        .enumerate()
+        .filter(|(_, field)| field.kind == EventValueKind::String)
+        .map(|(i, _)| i);
+    let absorbers: Vec<Option<usize>> = if surplus == 0 {
+        vec![None]
+    } else {
+        strings.rev().map(Some).collect()
+    };
+    for absorber in &absorbers {
+        if let Some(fields) = assign(&parts, definition, *absorber, surplus, false) {
+            return fields;
+        }
+    }
+    let absorber = absorbers.first().copied().flatten();
+    assign(&parts, definition, absorber, surplus, true).unwrap_or_default()
+}
+
+/// Pairs values with fields, joining `surplus` extra parts into the `absorber` field. Returns
+/// `None` if a value does not have its declared type, unless `lenient`, in which case such a
+/// value is kept as a string.
+fn assign(
+    parts: &[&str],
+    definition: &[EventTagField],
+    absorber: Option<usize>,
+    surplus: usize,
+    lenient: bool,
+) -> Option<Vec<EventField>> {
+    let mut fields = Vec::new();
+    let mut next = 0;
+    for (i, field) in definition.iter().enumerate() {
+        if next >= parts.len() {
+            break;
+        }
+        let take = if absorber == Some(i) { surplus + 1 } else { 1 };
+        let end = (next + take).min(parts.len());
+        let raw = parts[next..end].join(",");
+        next = end;
+        let value = match EventValue::parse(&raw, field.kind) {
+            Some(value) => value,
+            None if lenient => EventValue::String(raw),
+            None => return None,
+        };
+        fields.push(EventField {
+            name: Some(field.name.clone()),
+            value,
+        });
+    }
+    // Values beyond the definition, e.g. from a newer platform
+    fields.extend(parts[next..].iter().map(|raw| EventField {
+        name: None,
+        value: EventValue::infer(raw),
+    }));
+    Some(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+
+    fn event(tag: &str, message: &str) -> LogcatLine {
+        let timestamp = FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 8, 16, 10, 1, 30)
+            .unwrap();
+        LogcatLine::new(timestamp, "1000".to_string(), 2270, 2270, 'I', tag.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let tags = EventLogTags::parse(
+            "# comment\n42 answer (to life|1|5),(the universe|3)\n1004 no_fields\nnot a tag\n",
+        );
+        assert_eq!(tags.len(), 2);
+        let answer = tags.get("answer").unwrap();
+        assert_eq!(answer.number, 42);
+        assert_eq!(answer.fields[0].name, "to life");
+        assert_eq!(answer.fields[1].kind, EventValueKind::String);
+        assert!(EventLogTags::builtin().get("am_anr").is_some());
+    }
+
+    #[test]
+    fn test_decode() {
+        let tags = EventLogTags::builtin();
+        let anr = EventRecord::decode(
+            &event("am_anr", "[0,5140,com.android.systemui,952745541,Input dispatching timed out (a, b)]"),
+            &tags,
+        );
+        assert_eq!(anr.get_i64("pid"), Some(5140));
+        assert_eq!(anr.get_str("Package Name"), Some("com.android.systemui"));
+        assert_eq!(anr.get_str("reason"), Some("Input dispatching timed out (a, b)"));
+
+        let resumed = EventRecord::decode(
+            &event("wm_on_resume_called", "[97467843,com.android.settings.Settings,RESUME_ACTIVITY,12]"),
+            &tags,
+        );
+        assert_eq!(resumed.get("time"), Some(&EventValue::Long(12)));
+
+        let toggled = EventRecord::decode(&event("screen_toggled", "1"), &tags);
+        assert_eq!(toggled.get("screen_state"), Some(&EventValue::Int(1)));
+
+        let unknown = EventRecord::decode(&event("custom_event", "[1,2.5,text,[3,4]]"), &tags);
+        assert_eq!(unknown.get_index(0), Some(&EventValue::Long(1)));
+        assert_eq!(unknown.get_index(1), Some(&EventValue::Float(2.5)));
+        assert_eq!(unknown.get_index(2), Some(&EventValue::String("text".to_string())));
+        assert_eq!(
+            unknown.get_index(3),
+            Some(&EventValue::List(vec![EventValue::Long(3), EventValue::Long(4)]))
+        );
+    }
+
+    #[test]
+    fn test_device_tags_override() {
+        let mut tags = EventLogTags::builtin();
+        tags.extend(EventLogTags::parse("70000 screen_toggled (state|1|5)\n"));
+        let toggled = EventRecord::decode(&event("screen_toggled", "0"), &tags);
+        assert_eq!(toggled.get_i64("state"), Some(0));
+        assert_eq!(toggled.get("screen_state"), None);
+    }
+}
@@ -0,0 +1,242 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+use crate::bugreport::clock::ReportClock;
+use crate::error::{Error, Result};
+
+/// The timestamp every layout shares. The `-v` modifiers only change this part: `year` adds the
+/// year, `usec` and `nsec` print 6 and 9 fraction digits, `epoch` and `monotonic` print seconds
+/// and `zone` appends the UTC offset.
+const TIME: &str =
+    r"(?P<time>(?:\d{4}-)?\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d+|\d+\.\d+)(?: (?P<zone>[+-]\d{4}))?";
+
+/// `D/Tag( 1234): message`, or `D/Tag( 1000: 1234): message` with `-v uid`.
+const BRIEF_BODY: &str =
+    r"(?P<level>[VDIWEFA])/(?P<tag>[^(]*?) *\( *(?:(?P<uid>[^:()]+?): *)?(?P<pid>\d+)\):(?P<message>.*)";
+
+lazy_static! {
+    static ref THREADTIME: Regex = Regex::new(&format!(
+        r"^ *{TIME} +(?:(?P<uid>\S+) +)?(?P<pid>\d+) +(?P<tid>\d+) (?P<level>[VDIWEFA]) (?:(?P<tag>[^:]*?) *:(?P<message>.*)|(?P<bare_tag>\S+) *(?P<bare_message>.*))$"
+    ))
+    .unwrap();
+    static ref TIME_LINE: Regex = Regex::new(&format!(r"^ *{TIME} +{BRIEF_BODY}$")).unwrap();
+    static ref BRIEF: Regex = Regex::new(&format!(r"^{BRIEF_BODY}$")).unwrap();
+    static ref LONG_HEADER: Regex = Regex::new(&format!(
+        r"^\[ +{TIME} +(?:(?P<uid>\S+) +)?(?P<pid>\d+): *(?P<tid>\d+) (?P<level>[VDIWEFA])/(?P<tag>.*?) *\]$"
+    ))
+    .unwrap();
+}
+
+/// Seconds printed by `-v epoch` and `-v monotonic` look alike; a device would need 30 years
+/// of uptime to count this far since boot.
+const EPOCH_SECONDS_MIN: i64 = 1_000_000_000;
+
+/// The line layouts `logcat -v` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogFormat {
+    /// `D/Tag( 1234): message`, without a time.
+    Brief,
+    /// `08-16 10:01:30.003 D/Tag( 1234): message`.
+    Time,
+    /// `08-16 10:01:30.003  1000  5098  5850 D Tag: message`, what dumpstate uses. A line
+    /// without the colon after the tag is read as `Tag message`.
+    ThreadTime,
+    /// `[ 08-16 10:01:30.003  5098: 5850 D/Tag ]` followed by the message lines and a blank line.
+    Long,
+}
+
+impl LogFormat {
+    /// The format of the first line that has one. Lines such as `--------- beginning of main`
+    /// are skipped.
+    pub fn detect(lines: &[&str]) -> Option<Self> {
+        lines.iter().find_map(|line| Self::of_line(line))
+    }
+
+    pub fn of_line(line: &str) -> Option<Self> {
+        [
+            LogFormat::ThreadTime,
+            LogFormat::Long,
+            LogFormat::Time,
+            LogFormat::Brief,
+        ]
+        .into_iter()
+        .find(|format| format.regex().is_match(line))
+    }
+
+    /// For [`LogFormat::Long`] this matches the header line only.
+    pub(crate) fn captures(self, line: &str) -> Option<Captures<'_>> {
+        self.regex().captures(line)
+    }
+
+    fn regex(self) -> &'static Regex {
+        match self {
+            LogFormat::Brief => &BRIEF,
+            LogFormat::Time => &TIME_LINE,
+            LogFormat::ThreadTime => &THREADTIME,
+            LogFormat::Long => &LONG_HEADER,
+        }
+    }
+}
+
+/// How many fraction digits the source printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum TimePrecision {
+    /// The line has no time at all; its timestamp is only the capture time.
+    None,
+    #[default]
+    Milli,
+    Micro,
+    Nano,
+}
+
+impl TimePrecision {
+    fn from_digits(digits: usize) -> Option<Self> {
+        match digits {
+            3 => Some(TimePrecision::Milli),
+            6 => Some(TimePrecision::Micro),
+            9 => Some(TimePrecision::Nano),
+            _ => None,
+        }
+    }
+
+    /// The `strftime` pattern that prints a time the way the source did.
+    pub fn time_format(self) -> &'static str {
+        match self {
+            TimePrecision::Micro => "%Y-%m-%d %H:%M:%S%.6f",
+            TimePrecision::Nano => "%Y-%m-%d %H:%M:%S%.9f",
+            TimePrecision::None | TimePrecision::Milli => "%Y-%m-%d %H:%M:%S%.3f",
+        }
+    }
+}
+
+/// Reads the timestamp out of a matched line. Lines without a time get `reference`.
+pub(crate) fn parse_time(
+    caps: &Captures,
+    clock: &ReportClock,
+    reference: NaiveDateTime,
+) -> Result<(DateTime<FixedOffset>, TimePrecision)> {
+    let Some(time) = caps.name("time") else {
+        return Ok((clock.localize(reference), TimePrecision::None));
+    };
+    let time_str = time.as_str();
+    let bad = || Error::bad_timestamp(time_str);
+    let (whole, fraction) = time_str.split_once('.').ok_or_else(bad)?;
+    let precision = TimePrecision::from_digits(fraction.len()).ok_or_else(bad)?;
+
+    if !whole.contains(' ') {
+        let seconds: i64 = whole.parse().map_err(|_| bad())?;
+        let nanos: i64 = format!("{fraction:0<9}").parse().map_err(|_| bad())?;
+        let elapsed = Duration::try_seconds(seconds).ok_or_else(bad)? + Duration::nanoseconds(nanos);
+        let timestamp = if seconds >= EPOCH_SECONDS_MIN {
+            DateTime::UNIX_EPOCH
+                .checked_add_signed(elapsed)
+                .map(|instant| clock.render(instant))
+        } else {
+            clock.since_boot(elapsed)
+        };
+        return timestamp.map(|t| (t, precision)).ok_or_else(bad);
+    }
+
+    let naive = parse_wall_time(time_str, reference).ok_or_else(bad)?;
+    let timestamp = match caps.name("zone") {
+        Some(zone) => {
+            let offset = parse_zone(zone.as_str()).ok_or_else(bad)?;
+            let instant = offset.from_local_datetime(&naive).single().ok_or_else(bad)?;
+            clock.render(instant.with_timezone(&Utc))
+        }
+        None => clock.localize(naive),
+    };
+    Ok((timestamp, precision))
+}
+
+/// `-v year` lines carry their own year. For `MM-DD` lines the year is the one that puts the
+/// line closest to `reference`, so a report captured on January 2nd keeps its December 31st
+/// lines in the previous year.
+fn parse_wall_time(time_str: &str, reference: NaiveDateTime) -> Option<NaiveDateTime> {
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+    if time_str.as_bytes().get(4) == Some(&b'-') {
+        return NaiveDateTime::parse_from_str(time_str, FORMAT).ok();
+    }
+
+    (reference.year() - 1..=reference.year() + 1)
+        .filter_map(|year| NaiveDateTime::parse_from_str(&format!("{year}-{time_str}"), FORMAT).ok())
+        .min_by_key(|candidate| (*candidate - reference).num_milliseconds().abs())
+}
+
+/// `+0800` -> UTC+8.
+fn parse_zone(zone: &str) -> Option<FixedOffset> {
+    let sign = if zone.starts_with('-') { -1 } else { 1 };
+    let hours: i32 = zone.get(1..3)?.parse().ok()?;
+    let minutes: i32 = zone.get(3..5)?.parse().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::clock::TimeDisplay;
+    use chrono::NaiveDate;
+
+    fn clock() -> ReportClock {
+        ReportClock::new(ReportClock::parse_timezone("Asia/Shanghai"), TimeDisplay::Device)
+    }
+
+    fn reference() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_opt(10, 2, 11)
+            .unwrap()
+    }
+
+    fn time_of(line: &str, clock: &ReportClock) -> Result<(String, TimePrecision)> {
+        let format = LogFormat::of_line(line).unwrap();
+        let caps = format.captures(line).unwrap();
+        parse_time(&caps, clock, reference()).map(|(time, precision)| (time.to_rfc3339(), precision))
+    }
+
+    #[test]
+    fn test_detect() {
+        let cases = [
+            ("08-16 10:01:30.003  1000  5098  5850 D Tag: message", LogFormat::ThreadTime),
+            ("08-16 10:01:30.003  5098  5850 D Tag: message", LogFormat::ThreadTime),
+            ("08-16 10:01:30.003  5098  5850 D QSRecord listening to true", LogFormat::ThreadTime),
+            ("1723773690.003  5098  5850 D Tag: message", LogFormat::ThreadTime),
+            ("08-16 10:01:30.003 D/Tag     ( 5098): message", LogFormat::Time),
+            ("D/Tag     ( 1000: 5098): message", LogFormat::Brief),
+            ("[ 08-16 10:01:30.003  5098: 5850 D/Tag      ]", LogFormat::Long),
+        ];
+        for (line, format) in cases {
+            assert_eq!(LogFormat::of_line(line), Some(format), "{line}");
+        }
+        assert_eq!(LogFormat::of_line("--------- beginning of main"), None);
+        let lines = ["--------- beginning of main", "D/Tag( 5098): message"];
+        assert_eq!(LogFormat::detect(&lines), Some(LogFormat::Brief));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let cases = [
+            ("08-16 10:01:30.003  5098  5850 D Tag: m", "2024-08-16T10:01:30.003+08:00", TimePrecision::Milli),
+            ("2023-12-31 23:59:59.000123  5098  5850 D Tag: m", "2023-12-31T23:59:59.000123+08:00", TimePrecision::Micro),
+            ("08-16 10:01:30.003000001  5098  5850 D Tag: m", "2024-08-16T10:01:30.003000001+08:00", TimePrecision::Nano),
+            ("08-16 02:01:30.003 +0000  5098  5850 D Tag: m", "2024-08-16T10:01:30.003+08:00", TimePrecision::Milli),
+            ("1723773690.003  5098  5850 D Tag: m", "2024-08-16T10:01:30.003+08:00", TimePrecision::Milli),
+            ("D/Tag( 5098): m", "2024-08-16T10:02:11+08:00", TimePrecision::None),
+        ];
+        for (line, time, precision) in cases {
+            assert_eq!(time_of(line, &clock()).unwrap(), (time.to_string(), precision), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_monotonic() {
+        let line = "   95.250  5098  5850 D Tag: message";
+        assert!(matches!(time_of(line, &clock()), Err(Error::BadTimestamp { .. })));
+        let boot_time = clock().localize(reference()).with_timezone(&Utc);
+        let booted = clock().with_boot_time(Some(boot_time));
+        let (time, _) = time_of(line, &booted).unwrap();
+        assert_eq!(time, "2024-08-16T10:03:46.250+08:00");
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use super::LogcatLine;
+
+/// Maps tags, pids and message tokens to the positions of the lines that have them, so lookups
+/// do not scan the whole section. Positions are `u32` to keep the index small.
+#[derive(Debug, Clone, Default)]
+pub struct LogIndex {
+    tags: HashMap<String, Vec<u32>>,
+    pids: HashMap<u32, Vec<u32>>,
+    /// Id of every distinct token, an index into `postings`.
+    tokens: HashMap<String, u32>,
+    postings: Vec<Vec<u32>>,
+    /// Byte trigrams of the tokens to the ids of the tokens that have them, so a word can be
+    /// found inside longer tokens without going through all of them.
+    grams: HashMap<[u8; 3], Vec<u32>>,
+}
+
+/// Shorter words have no trigram and are left to the caller's scan.
+const GRAM: usize = 3;
+
+/// Words of a message: runs of letters, digits and `_`.
+pub(crate) fn tokens(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|token| !token.is_empty())
+}
+
+/// Positions are pushed in increasing order, so a line is already listed if it is the last.
+fn push_once(positions: &mut Vec<u32>, position: u32) {
+    if positions.last() != Some(&position) {
+        positions.push(position);
+    }
+}
+
+/// Positions present in both sorted lists.
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut both = Vec::new();
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                both.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    both
+}
+
+impl LogIndex {
+    pub fn build(lines: &[LogcatLine]) -> Self {
+        let mut index = Self::default();
+        for (position, line) in lines.iter().enumerate() {
+            let position = position as u32;
+            index.tags.entry(line.tag.clone()).or_default().push(position);
+            index.pids.entry(line.pid).or_default().push(position);
+            for token in tokens(&line.message) {
+                let id = match index.tokens.get(token) {
+                    Some(&id) => id,
+                    None => index.add_token(token),
+                };
+                push_once(&mut index.postings[id as usize], position);
+            }
+        }
+        index
+    }
+
+    fn add_token(&mut self, token: &str) -> u32 {
+        let id = self.postings.len() as u32;
+        self.tokens.insert(token.to_string(), id);
+        self.postings.push(Vec::new());
+        for gram in token.as_bytes().windows(GRAM) {
+            push_once(self.grams.entry([gram[0], gram[1], gram[2]]).or_default(), id);
+        }
+        id
+    }
+
+    /// Positions of the lines with exactly this tag.
+    pub fn tag(&self, tag: &str) -> &[u32] {
+        self.tags.get(tag).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn pid(&self, pid: u32) -> &[u32] {
+        self.pids.get(&pid).map_or(&[], Vec::as_slice)
+    }
+
+    /// Positions of the lines whose message has `word` as a whole token.
+    pub fn token(&self, word: &str) -> &[u32] {
+        match self.tokens.get(word) {
+            Some(&id) => &self.postings[id as usize],
+            None => &[],
+        }
+    }
+
+    /// Positions of the lines with a token that has every trigram of `word`, which includes
+    /// every token containing it. `None` if `word` is too short to have a trigram.
+    fn substring(&self, word: &str) -> Option<Vec<u32>> {
+        let mut ids: Option<Vec<u32>> = None;
+        for gram in word.as_bytes().windows(GRAM) {
+            let Some(with_gram) = self.grams.get(&[gram[0], gram[1], gram[2]]) else {
+                return Some(Vec::new());
+            };
+            ids = Some(match ids {
+                Some(ids) => intersect(&ids, with_gram),
+                None => with_gram.clone(),
+            });
+        }
+        let mut positions: Vec<u32> = ids?
+            .iter()
+            .flat_map(|&id| self.postings[id as usize].iter().copied())
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        Some(positions)
+    }
+
+    /// Positions of the lines whose message may contain `keyword`. A word with separators on
+    /// both sides in `keyword` must be a whole token of the line; the first and last may also
+    /// be part of a longer one. The caller still has to check the message. `None` if no word of
+    /// `keyword` can be looked up, e.g. `": "` or `"ui"`.
+    pub fn keyword_candidates(&self, keyword: &str) -> Option<Vec<u32>> {
+        let mut lists: Vec<Vec<u32>> = Vec::new();
+        for word in tokens(keyword) {
+            let start = word.as_ptr() as usize - keyword.as_ptr() as usize;
+            if start > 0 && start + word.len() < keyword.len() {
+                lists.push(self.token(word).to_vec());
+            } else if let Some(positions) = self.substring(word) {
+                lists.push(positions);
+            }
+        }
+        // Start from the rarest word so the intersections stay small
+        lists.sort_by_key(Vec::len);
+        let mut lists = lists.into_iter();
+        let first = lists.next()?;
+        Some(lists.fold(first, |candidates, positions| intersect(&candidates, &positions)))
+    }
+
+    /// Approximate heap size of the index in bytes.
+    pub fn memory_bytes(&self) -> usize {
+        fn list_bytes(positions: &Vec<u32>) -> usize {
+            positions.capacity() * size_of::<u32>()
+        }
+        fn map_bytes<K, V>(map: &HashMap<K, V>, heap: impl Fn(&K, &V) -> usize) -> usize {
+            let slots = map.capacity() * (size_of::<K>() + size_of::<V>() + 1);
+            let entries: usize = map.iter().map(|(key, value)| heap(key, value)).sum();
+            slots + entries
+        }
+        map_bytes(&self.tags, |tag, positions| tag.capacity() + list_bytes(positions))
+            + map_bytes(&self.pids, |_, positions| list_bytes(positions))
+            + map_bytes(&self.tokens, |token, _| token.capacity())
+            + self.postings.capacity() * size_of::<Vec<u32>>()
+            + self.postings.iter().map(list_bytes).sum::<usize>()
+            + map_bytes(&self.grams, |_, ids| list_bytes(ids))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, TimeZone};
+
+    fn line(pid: u32, tag: &str, message: &str) -> LogcatLine {
+        let timestamp = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2024, 8, 16, 10, 1, 30)
+            .unwrap();
+        LogcatLine::new(timestamp, String::new(), pid, pid, 'D', tag.to_string(), message.to_string())
+    }
+
+    #[test]
+    fn test_index() {
+        let lines = [
+            line(1500, "BinderProxy", "Binder call took 2100ms"),
+            line(1500, "ActivityManager", "Start proc 5140:com.android.systemui"),
+            line(5140, "BinderProxy", "binder_ioctl failed, Binder Binder"),
+        ];
+        let index = LogIndex::build(&lines);
+        assert_eq!(index.tag("BinderProxy"), &[0, 2]);
+        assert_eq!(index.tag("Missing"), &[] as &[u32]);
+        assert_eq!(index.pid(1500), &[0, 1]);
+        assert_eq!(index.token("Binder"), &[0, 2]);
+        assert_eq!(index.token("com"), &[1]);
+        assert_eq!(index.keyword_candidates("systemui"), Some(vec![1]));
+        assert_eq!(index.keyword_candidates("call took"), Some(vec![0]));
+        assert_eq!(index.keyword_candidates("inder"), Some(vec![0, 2]));
+        assert_eq!(index.keyword_candidates("proc 5140:com.android"), Some(vec![1]));
+        // Between separators a word is only looked up as a whole token
+        assert_eq!(index.keyword_candidates("x inder x"), Some(vec![]));
+        assert_eq!(index.keyword_candidates("Binder call"), Some(vec![0]));
+        assert_eq!(index.keyword_candidates(": "), None);
+        assert_eq!(index.keyword_candidates("ui"), None);
+        assert!(index.memory_bytes() > 0);
+    }
+}
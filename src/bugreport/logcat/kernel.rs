@@ -0,0 +1,180 @@
+use chrono::{Duration, NaiveDateTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::format::TimePrecision;
+use super::{LogBuffer, LogcatLine, LogcatSection};
+use crate::bugreport::clock::ReportClock;
+use crate::error::{Error, Result};
+
+lazy_static! {
+    /// `[ 1234.567890] text`, optionally with a `<3>` priority (`dmesg -r`), a `kern  :err   : `
+    /// facility and level (`dmesg -x`) or a `[ T1234]` caller (`CONFIG_PRINTK_CALLER`).
+    static ref KERNEL_LINE: Regex = Regex::new(
+        r"^(?:<(?P<priority>[0-7])>|(?:\w+) *:(?P<level>\w+) *: )?\[ *(?P<seconds>\d+)\.(?P<fraction>\d+)\](?:\[ *(?P<caller>[TC])(?P<id>\d+)\])? ?(?P<text>.*)$"
+    )
+    .unwrap();
+    /// `binder: ...`, `thermal thermal_zone3: ...` or `F2FS-fs (dm-45): ...`.
+    static ref SUBSYSTEM: Regex =
+        Regex::new(r"^(?P<subsystem>[A-Za-z][\w.-]*)(?: (?P<device>[^\s:]+))?: (?P<rest>.*)$").unwrap();
+}
+
+/// The tag of kernel lines that do not start with a subsystem.
+pub const KERNEL_TAG: &str = "kernel";
+
+/// The level names `dmesg -x` prints, in priority order.
+const LEVEL_NAMES: [&str; 8] = ["emerg", "alert", "crit", "err", "warn", "notice", "info", "debug"];
+
+/// The logcat level closest to a printk priority; lines without one read as `I`.
+fn level_of(priority: Option<usize>) -> char {
+    match priority {
+        Some(0..=2) => 'F',
+        Some(3) => 'E',
+        Some(4) => 'W',
+        Some(7) => 'D',
+        _ => 'I',
+    }
+}
+
+impl LogcatLine {
+    /// Parses a `dmesg` line. The time since boot is placed on the wall clock with
+    /// [`ReportClock::since_boot`]; without a boot time the line gets `reference`, like lines
+    /// that print no time. The subsystem prefix becomes the tag and the printk priority the
+    /// level, so kernel lines can be searched like any other log line.
+    pub fn parse_kernel(line: &str, clock: &ReportClock, reference: NaiveDateTime) -> Result<Self> {
+        let caps = KERNEL_LINE
+            .captures(line)
+            .ok_or_else(|| Error::malformed("kernel line"))?;
+        let bad = || Error::bad_timestamp(&caps["seconds"]);
+        let seconds: i64 = caps["seconds"].parse().map_err(|_| bad())?;
+        let fraction = &caps["fraction"];
+        let nanos: i64 = format!("{:0<9}", &fraction[..fraction.len().min(9)])
+            .parse()
+            .map_err(|_| bad())?;
+        let uptime = Duration::try_seconds(seconds).ok_or_else(bad)? + Duration::nanoseconds(nanos);
+        let (timestamp, precision) = match clock.since_boot(uptime) {
+            Some(timestamp) => (timestamp, TimePrecision::Micro),
+            None => (clock.localize(reference), TimePrecision::None),
+        };
+
+        let priority = match (caps.name("priority"), caps.name("level")) {
+            (Some(priority), _) => priority.as_str().parse().ok(),
+            (None, Some(level)) => LEVEL_NAMES.iter().position(|name| *name == level.as_str()),
+            (None, None) => None,
+        };
+        // A `T` caller is the thread that logged; `C` callers are CPUs in interrupt context
+        let pid = match (caps.name("caller"), caps.name("id")) {
+            (Some(caller), Some(id)) if caller.as_str() == "T" => id.as_str().parse().unwrap_or(0),
+            _ => 0,
+        };
+
+        let text = &caps["text"];
+        let (tag, message) = match SUBSYSTEM.captures(text) {
+            Some(parts) => {
+                let message = match parts.name("device") {
+                    Some(device) => format!("{}: {}", device.as_str(), &parts["rest"]),
+                    None => parts["rest"].to_string(),
+                };
+                (parts["subsystem"].to_string(), message)
+            }
+            None => (KERNEL_TAG.to_string(), text.to_string()),
+        };
+
+        Ok(Self {
+            timestamp,
+            precision,
+            user: String::new(),
+            pid,
+            tid: pid,
+            level: level_of(priority),
+            tag,
+            message,
+            buffer: Some(LogBuffer::Kernel),
+        })
+    }
+}
+
+impl LogcatSection {
+    /// Parses the output of `dmesg`, see [`LogcatLine::parse_kernel`]. Lines without a time
+    /// are appended to the line before them.
+    pub fn parse_kernel(&mut self, lines: &[&str], clock: &ReportClock, reference: NaiveDateTime) {
+        self.index.take();
+        for line in lines {
+            match LogcatLine::parse_kernel(line, clock, reference) {
+                Ok(parsed) => self.lines.push(parsed),
+                Err(_) if line.is_empty() => {}
+                Err(_) if !KERNEL_LINE.is_match(line) && !self.lines.is_empty() => {
+                    let last = self.lines.last_mut().unwrap();
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+                Err(_) => self.dropped += 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::clock::TimeDisplay;
+    use chrono::{NaiveDate, Utc};
+
+    fn reference() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_opt(10, 2, 11)
+            .unwrap()
+    }
+
+    /// A device in Shanghai that booted 1000s before the report.
+    fn clock() -> ReportClock {
+        let clock = ReportClock::new(ReportClock::parse_timezone("Asia/Shanghai"), TimeDisplay::Device);
+        let boot_time = clock.localize(reference()).with_timezone(&Utc) - Duration::seconds(1000);
+        clock.with_boot_time(Some(boot_time))
+    }
+
+    #[test]
+    fn test_parse_kernel() {
+        let lines = [
+            "[  990.123456] binder: 1500:1520 transaction failed 29189/-22, size 0-0 line 3034",
+            "<2>[  995.500000][ T1234] thermal thermal_zone3: critical temperature reached (118 C), shutting down",
+            "kern  :err   : [  998.000001] F2FS-fs (dm-45): inconsistent node block",
+            "[  999.000000][    C4] INFO: task kworker/u16:3:1234 blocked for more than 120 seconds.",
+            "      Tainted: G        W  OE     6.1.75",
+            "[    0.000000] Booting Linux on physical CPU 0x0000000000 [0x411fd050]",
+            "",
+        ];
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse_kernel(&lines, &clock(), reference());
+        assert_eq!(section.len(), 5);
+        assert_eq!(section.get_dropped_lines(), 0);
+
+        let binder = section.get_line(0).unwrap();
+        assert_eq!(binder.timestamp.to_rfc3339(), "2024-08-16T10:02:01.123456+08:00");
+        assert_eq!((binder.tag.as_str(), binder.level, binder.pid), ("binder", 'I', 0));
+        assert_eq!(binder.message, "1500:1520 transaction failed 29189/-22, size 0-0 line 3034");
+        assert_eq!(binder.buffer, Some(LogBuffer::Kernel));
+
+        let thermal = section.get_line(1).unwrap();
+        assert_eq!((thermal.tag.as_str(), thermal.level, thermal.pid), ("thermal", 'F', 1234));
+        assert!(thermal.message.starts_with("thermal_zone3: critical temperature"));
+        let f2fs = section.get_line(2).unwrap();
+        assert_eq!((f2fs.tag.as_str(), f2fs.level), ("F2FS-fs", 'E'));
+        assert_eq!(f2fs.message, "(dm-45): inconsistent node block");
+        let hung = section.get_line(3).unwrap();
+        assert_eq!((hung.tag.as_str(), hung.pid), ("INFO", 0));
+        assert!(hung.message.ends_with("seconds.\n      Tainted: G        W  OE     6.1.75"));
+        assert_eq!(section.get_line(4).unwrap().tag, KERNEL_TAG);
+        assert_eq!(format!("{}", section.get_line(4).unwrap()).get(..26), Some("2024-08-16 09:45:31.000000"));
+    }
+
+    #[test]
+    fn test_unknown_boot_time() {
+        let clock = ReportClock::new(None, TimeDisplay::Device);
+        let line = LogcatLine::parse_kernel("[  990.123456] binder: x", &clock, reference()).unwrap();
+        assert_eq!(line.timestamp, clock.localize(reference()));
+        assert_eq!(line.precision, TimePrecision::None);
+        assert!(LogcatLine::parse_kernel("binder: x", &clock, reference()).is_err());
+    }
+}
@@ -0,0 +1,722 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Utc};
+use rayon::prelude::*;
+use regex::Captures;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use super::clock::ReportClock;
+use super::decode::{self, DecodeMode};
+use super::process::ProcessTable;
+use crate::error::{Error, Result};
+
+pub mod event;
+pub mod format;
+pub mod index;
+pub mod kernel;
+pub mod window;
+
+use format::{LogFormat, TimePrecision};
+use index::LogIndex;
+use window::TimeWindow;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogcatLine {
+    pub timestamp: DateTime<FixedOffset>,
+    /// How precise `timestamp` is, i.e. how many fraction digits the source printed.
+    pub precision: TimePrecision,
+    /// The uid column, empty unless the log was printed with `-v uid`.
+    pub user: String,
+    pub pid: u32,
+    /// 0 when the format does not print it (`brief`, `time`).
+    pub tid: u32,
+    pub level: char,
+    pub tag: String,
+    pub message: String,
+    /// The buffer the line was read from, `None` if no buffer marker came before it.
+    pub buffer: Option<LogBuffer>,
+}
+
+/// The logcat ring buffers, as named by `--------- beginning of <buffer>` markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LogBuffer {
+    Main,
+    System,
+    Crash,
+    Radio,
+    Events,
+    Kernel,
+    Security,
+    Stats,
+}
+
+impl LogBuffer {
+    /// The buffer a `--------- beginning of crash` or `--------- switch to main` line starts.
+    pub fn from_marker(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix("--------- ")?;
+        let name = rest
+            .strip_prefix("beginning of ")
+            .or_else(|| rest.strip_prefix("switch to "))?;
+        name.trim().parse().ok()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogBuffer::Main => "main",
+            LogBuffer::System => "system",
+            LogBuffer::Crash => "crash",
+            LogBuffer::Radio => "radio",
+            LogBuffer::Events => "events",
+            LogBuffer::Kernel => "kernel",
+            LogBuffer::Security => "security",
+            LogBuffer::Stats => "stats",
+        }
+    }
+}
+
+impl std::str::FromStr for LogBuffer {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "main" => Ok(LogBuffer::Main),
+            "system" => Ok(LogBuffer::System),
+            "crash" => Ok(LogBuffer::Crash),
+            "radio" => Ok(LogBuffer::Radio),
+            "events" => Ok(LogBuffer::Events),
+            "kernel" => Ok(LogBuffer::Kernel),
+            "security" => Ok(LogBuffer::Security),
+            "stats" => Ok(LogBuffer::Stats),
+            _ => Err(Error::malformed("log buffer")),
+        }
+    }
+}
+
+impl Display for LogBuffer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogcatSection {
+    lines: Vec<LogcatLine>,
+    /// Lines that could not be read as a record nor attached to one.
+    dropped: usize,
+    /// Built on request by [`LogcatSection::build_index`]; lookups scan the lines without it.
+    #[serde(skip)]
+    index: OnceLock<LogIndex>,
+}
+
+/// `--------- beginning of main` and `--------- switch to system` separate the buffers.
+fn is_buffer_marker(line: &str) -> bool {
+    line.starts_with("--------- ")
+}
+
+impl LogcatLine {
+    #[rustfmt::skip]
+    pub fn new(
+        timestamp: DateTime<FixedOffset>,
+        user: String,
+        pid: u32,
+        tid: u32,
+        level: char,
+        tag: String,
+        message: String,
+    ) -> Self {
+        let precision = TimePrecision::default();
+        Self { timestamp, precision, user, pid, tid, level, tag, message, buffer: None }
+    }
+
+    /// Parses one line in any single-line [`LogFormat`], or the header of a `long` entry.
+    /// `reference` is the device wall-clock time the log was captured at, normally the dumpstate
+    /// timestamp; it supplies the year when the line does not carry one. `clock` says which
+    /// timezone the line was printed in.
+    pub fn parse_line(line: &str, clock: &ReportClock, reference: NaiveDateTime) -> Result<Self> {
+        let format = LogFormat::of_line(line).ok_or(Error::malformed("logcat line"))?;
+        Self::parse_with(format, line, clock, reference)
+    }
+
+    /// Parses one line known to be in `format`.
+    pub fn parse_with(
+        format: LogFormat,
+        line: &str,
+        clock: &ReportClock,
+        reference: NaiveDateTime,
+    ) -> Result<Self> {
+        let caps = format
+            .captures(line)
+            .ok_or(Error::malformed("logcat line"))?;
+        Self::from_captures(&caps, clock, reference)
+    }
+
+    fn from_captures(caps: &Captures, clock: &ReportClock, reference: NaiveDateTime) -> Result<Self> {
+        let (timestamp, precision) = format::parse_time(caps, clock, reference)?;
+        let text = |name: &str| caps.name(name).map_or("", |m| m.as_str().trim());
+        let id = |name: &str, what: &'static str| match caps.name(name) {
+            Some(m) => m.as_str().parse::<u32>().map_err(|_| Error::malformed(what)),
+            None => Ok(0),
+        };
+        Ok(Self {
+            timestamp,
+            precision,
+            user: text("uid").to_string(),
+            pid: id("pid", "pid")?,
+            tid: id("tid", "tid")?,
+            level: text("level").chars().next().unwrap_or('?'),
+            tag: text("tag").to_string() + text("bare_tag"),
+            message: text("message").to_string() + text("bare_message"),
+            buffer: None,
+        })
+    }
+}
+
+impl LogcatLine {
+    /// Name of the process that logged this line, e.g. `com.android.systemui`.
+    pub fn process_name<'t>(&self, processes: &'t ProcessTable) -> Option<&'t str> {
+        processes.process_name(self.pid)
+    }
+
+    /// Name of the thread that logged this line, e.g. `RenderThread`.
+    pub fn thread_name<'t>(&self, processes: &'t ProcessTable) -> Option<&'t str> {
+        processes.thread_name(self.tid)
+    }
+}
+
+impl Display for LogcatLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}: {}",
+            self.timestamp.format(self.precision.time_format()),
+            self.user,
+            self.pid,
+            self.tid,
+            self.level,
+            self.tag,
+            self.message
+        )
+    }
+}
+
+impl LogcatSection {
+    pub fn new(lines: Vec<LogcatLine>) -> Self {
+        Self {
+            lines,
+            dropped: 0,
+            index: OnceLock::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    pub fn get_line(&self, index: usize) -> Option<&LogcatLine> {
+        self.lines.get(index)
+    }
+
+    /// Every line, in the order logcat printed them.
+    pub fn lines(&self) -> &[LogcatLine] {
+        &self.lines
+    }
+
+    /// How many non-empty lines were lost: lines before the first record of a buffer and lines
+    /// that look like a record but carry a bad time or id. Buffer markers are not counted.
+    pub fn get_dropped_lines(&self) -> usize {
+        self.dropped
+    }
+
+    /// Parses `lines` in whichever [`LogFormat`] the first log line is in. A line that is not a
+    /// record, e.g. a stack frame logcat printed without a header, is appended to the message
+    /// of the record before it.
+    pub fn parse(&mut self, lines: &[&str], clock: &ReportClock, reference: NaiveDateTime) {
+        // Positions are about to change
+        self.index.take();
+        match LogFormat::detect(lines) {
+            Some(LogFormat::Long) => self.parse_long(lines, clock, reference),
+            Some(format) => self.parse_records(format, lines, clock, reference),
+            None => {
+                self.dropped += lines
+                    .iter()
+                    .filter(|line| !line.is_empty() && !is_buffer_marker(line))
+                    .count();
+            }
+        }
+    }
+
+    fn parse_records(
+        &mut self,
+        format: LogFormat,
+        lines: &[&str],
+        clock: &ReportClock,
+        reference: NaiveDateTime,
+    ) {
+        let parsed_lines: Vec<_> = lines
+            .par_iter()
+            .map(|line| LogcatLine::parse_with(format, line, clock, reference).ok())
+            .collect();
+        // Continuations never cross into another buffer
+        let mut open = false;
+        let mut buffer = None;
+        for (line, parsed) in lines.iter().zip(parsed_lines) {
+            match parsed {
+                Some(mut parsed) => {
+                    parsed.buffer = buffer;
+                    self.lines.push(parsed);
+                    open = true;
+                }
+                None if line.is_empty() => {}
+                None if is_buffer_marker(line) => {
+                    open = false;
+                    buffer = LogBuffer::from_marker(line);
+                }
+                None if open && format.captures(line).is_none() => {
+                    let last = self.lines.last_mut().unwrap();
+                    last.message.push('\n');
+                    last.message.push_str(line);
+                }
+                None => self.dropped += 1,
+            }
+        }
+    }
+
+    /// A `long` entry is its header followed by the message lines up to a blank line.
+    fn parse_long(&mut self, lines: &[&str], clock: &ReportClock, reference: NaiveDateTime) {
+        fn finish(entry: Option<(LogcatLine, Vec<&str>)>, parsed: &mut Vec<LogcatLine>) {
+            if let Some((mut line, mut message)) = entry {
+                while message.last().is_some_and(|line| line.is_empty()) {
+                    message.pop();
+                }
+                line.message = message.join("\n");
+                parsed.push(line);
+            }
+        }
+
+        let mut entry: Option<(LogcatLine, Vec<&str>)> = None;
+        let mut buffer = None;
+        for line in lines {
+            if let Some(caps) = LogFormat::Long.captures(line) {
+                finish(entry.take(), &mut self.lines);
+                entry = LogcatLine::from_captures(&caps, clock, reference)
+                    .ok()
+                    .map(|header| (LogcatLine { buffer, ..header }, Vec::new()));
+                if entry.is_none() {
+                    self.dropped += 1;
+                }
+            } else if is_buffer_marker(line) {
+                finish(entry.take(), &mut self.lines);
+                buffer = LogBuffer::from_marker(line);
+            } else if let Some((_, message)) = &mut entry {
+                message.push(line);
+            } else if !line.is_empty() {
+                self.dropped += 1;
+            }
+        }
+        finish(entry, &mut self.lines);
+    }
+
+    /// Loads a logcat captured outside a bugreport, e.g. with `adb logcat -v epoch > log.txt`.
+    /// Without a dumpstate timestamp, the file's modification time stands in as the capture
+    /// time.
+    pub fn load(path: &Path, clock: &ReportClock) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let modified = std::fs::metadata(path)?.modified()?;
+        let reference = clock.wall_time(DateTime::<Utc>::from(modified));
+        Self::from_bytes(&bytes, clock, reference)
+    }
+
+    /// Parses a logcat capture held in memory. Invalid UTF-8 is replaced.
+    pub fn from_bytes(bytes: &[u8], clock: &ReportClock, reference: NaiveDateTime) -> Result<Self> {
+        let (lines, _) = decode::decode_lines(bytes, DecodeMode::Replace, 1)?;
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_ref()).collect();
+        let mut section = Self::new(Vec::new());
+        section.parse(&lines, clock, reference);
+        Ok(section)
+    }
+
+    /// Indexes tags, pids and message tokens so later lookups skip the scan.
+    pub fn build_index(&self) -> &LogIndex {
+        self.index.get_or_init(|| LogIndex::build(&self.lines))
+    }
+
+    pub fn get_index(&self) -> Option<&LogIndex> {
+        self.index.get()
+    }
+
+    fn at_positions(&self, positions: &[u32]) -> Vec<LogcatLine> {
+        positions
+            .iter()
+            .map(|&position| self.lines[position as usize].clone())
+            .collect()
+    }
+
+    pub fn search_by_tag(&self, tag: &str) -> Vec<LogcatLine> {
+        if let Some(index) = self.index.get() {
+            return self.at_positions(index.tag(tag));
+        }
+        self.lines
+            .par_iter()
+            .filter(|line| line.tag == tag)
+            .cloned()
+            .collect()
+    }
+
+    /// Lines within a second of `time`, which is read as device wall-clock time, see
+    /// [`window::parse_time`]. Use [`LogcatSection::search_by_window`] for wider spans.
+    pub fn search_by_time(&self, time: &str, clock: &ReportClock) -> Result<Vec<LogcatLine>> {
+        let time = window::parse_time(time, clock)?;
+        let second = Duration::seconds(1);
+        Ok(self.search_by_window(&TimeWindow::around(time, second, second)))
+    }
+
+    /// Lines whose timestamp falls inside `window`, in section order.
+    pub fn search_by_window(&self, window: &TimeWindow) -> Vec<LogcatLine> {
+        self.lines
+            .par_iter()
+            .filter(|line| window.contains(line.timestamp))
+            .cloned()
+            .collect()
+    }
+
+    /// Line `index` with up to `before` lines ahead of it and `after` lines past it. Empty if
+    /// `index` is out of range.
+    pub fn context_lines(&self, index: usize, before: usize, after: usize) -> &[LogcatLine] {
+        if index >= self.lines.len() {
+            return &[];
+        }
+        let start = index.saturating_sub(before);
+        let end = index.saturating_add(after).saturating_add(1).min(self.lines.len());
+        &self.lines[start..end]
+    }
+
+    /// Lines logged from `before` ahead of line `index` to `after` past it. Empty if `index`
+    /// is out of range.
+    pub fn context_window(&self, index: usize, before: Duration, after: Duration) -> Vec<LogcatLine> {
+        match self.lines.get(index) {
+            Some(line) => self.search_by_window(&TimeWindow::around(line.timestamp, before, after)),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn search_by_level(&self, level: char) -> Vec<LogcatLine> {
+        self.lines
+            .par_iter()
+            .filter(|line| line.level == level)
+            .cloned()
+            .collect()
+    }
+
+    /// Lines whose message has `word` as a whole token, e.g. `Binder` but not `BinderProxy`.
+    pub fn search_by_token(&self, word: &str) -> Vec<LogcatLine> {
+        match self.index.get() {
+            Some(index) => self.at_positions(index.token(word)),
+            None => self
+                .lines
+                .par_iter()
+                .filter(|line| index::tokens(&line.message).any(|token| token == word))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Lines read from `buffer`, e.g. everything after `--------- beginning of crash`.
+    pub fn search_by_buffer(&self, buffer: LogBuffer) -> Vec<LogcatLine> {
+        self.lines
+            .par_iter()
+            .filter(|line| line.buffer == Some(buffer))
+            .cloned()
+            .collect()
+    }
+
+    pub fn search_by_keyword(&self, keyword: &str) -> Vec<LogcatLine> {
+        if let Some(candidates) = self.index.get().and_then(|index| index.keyword_candidates(keyword)) {
+            return candidates
+                .iter()
+                .map(|&position| &self.lines[position as usize])
+                .filter(|line| line.message.contains(keyword))
+                .cloned()
+                .collect();
+        }
+        self.lines
+            .par_iter()
+            .filter(|line| line.message.contains(keyword))
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::clock::TimeDisplay;
+    use chrono::NaiveDate;
+
+    fn clock() -> ReportClock {
+        ReportClock::new(ReportClock::parse_timezone("Asia/Shanghai"), TimeDisplay::Device)
+    }
+
+    fn reference() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_opt(10, 2, 11)
+            .unwrap()
+    }
+
+    fn get_test_lines() -> Vec<&'static str> {
+        r#"
+08-16 10:01:30.003  1000  5098  5850 D LocalBluetoothAdapter: isSupportBluetoothRestrict = 0
+08-16 10:01:31.003 10160  5140  5140 D RecentsImpl: hideNavStubView
+08-16 10:01:32.003 10160  5140  5140 D NavStubView_Touch: setKeepHidden    old=false   new=true
+08-16 10:01:33.003 10160  5140  5300 D GestureStubView_Touch: setKeepHidden    old=false   new=false
+08-16 10:01:34.003  1000  2270  5305 D PerfShielderService: com.android.systemui|StatusBar|171|1389485333739|171|0|1
+08-16 10:01:35.003 10160  5140  5300 W GestureStubView: adaptRotation   currentRotation=0   mRotation=0
+08-16 10:01:36.003 10160  5140  5300 D GestureStubView: resetRenderProperty: showGestureStub   isLayoutParamChanged=false
+08-16 10:01:37.003 10160  5140  5300 D GestureStubView_Touch: disableTouch    old=false   new=false
+08-16 10:01:38.003 10160  5140  5300 D GestureStubView: showGestureStub
+08-16 10:01:39.003 10160  5140  5300 D GestureStubView_Touch: setKeepHidden    old=false   new=false
+"#.trim().lines().collect::<Vec<&str>>()
+    }
+
+    #[test]
+    fn test_logcat_line() {
+        let timestamp = clock().localize(reference());
+        let logcat_line = LogcatLine::new(
+            timestamp,
+            "user".to_string(),
+            1234,
+            5678,
+            'I',
+            "tag".to_string(),
+            "message".to_string(),
+        );
+
+        #[rustfmt::skip]
+        assert_eq!(
+            format!("{}", logcat_line),
+            format!("{} user 1234 5678 I tag: message", timestamp.format("%Y-%m-%d %H:%M:%S%.3f"))
+        );
+    }
+
+    #[test]
+    fn test_search_by_tag() {
+        let logcat = get_test_lines();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&logcat, &clock(), reference());
+        let result = section.search_by_tag("GestureStubView");
+        println!("{:?}", result.clone());
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn test_search_by_time() {
+        let logcat = get_test_lines();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&logcat, &clock(), reference());
+        let result = section.search_by_time("2024-08-16 10:01:34", &clock()).unwrap();
+        println!("{:?}", result.clone());
+        assert_eq!(result.len(), 2);
+        assert!(section.search_by_time("2024-08-16 10:01", &clock()).is_err());
+    }
+
+    #[test]
+    fn test_time_windows() {
+        let logcat = get_test_lines();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&logcat, &clock(), reference());
+        let window = TimeWindow::parse("2024-08-16 10:01:31.500", "2024-08-16 10:01:34.003", &clock()).unwrap();
+        let tags: Vec<String> = section.search_by_window(&window).into_iter().map(|l| l.tag).collect();
+        assert_eq!(tags, vec!["NavStubView_Touch", "GestureStubView_Touch", "PerfShielderService"]);
+
+        let anr = section.get_line(9).unwrap().timestamp;
+        let before = TimeWindow::before(anr, window::parse_offset("2.5s").unwrap());
+        assert_eq!(section.search_by_window(&before).len(), 3);
+
+        assert_eq!(section.context_lines(1, 3, 2).len(), 4);
+        assert_eq!(section.context_lines(9, 1, 5).len(), 2);
+        assert!(section.context_lines(10, 1, 1).is_empty());
+        let around = section.context_window(4, Duration::seconds(2), Duration::milliseconds(999));
+        assert_eq!(around.len(), 3);
+    }
+
+    #[test]
+    fn test_search_by_level() {
+        let logcat = get_test_lines();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&logcat, &clock(), reference());
+        let result = section.search_by_level('D');
+        println!("{:?}", result.clone());
+        assert_eq!(result.len(), 9);
+    }
+
+    #[test]
+    fn test_parse_line_errors() {
+        assert!(matches!(
+            LogcatLine::parse_line("--------- beginning of main", &clock(), reference()),
+            Err(Error::Malformed { what: "logcat line", .. })
+        ));
+        assert!(matches!(
+            LogcatLine::parse_line("02-30 10:01:30.003  1000  5098  5850 D Tag: message", &clock(), reference()),
+            Err(Error::BadTimestamp { .. })
+        ));
+        assert!(matches!(
+            LogcatLine::parse_line("08-16 10:01:30.003  1000  99999999999  5850 D Tag: message", &clock(), reference()),
+            Err(Error::Malformed { what: "pid", .. })
+        ));
+    }
+
+    #[test]
+    fn test_year_rollover() {
+        use chrono::Datelike;
+
+        let new_year = NaiveDate::from_ymd_opt(2025, 1, 2)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+        let line = "12-31 23:59:59.500  1000  5098  5850 D Tag: message";
+        let parsed = LogcatLine::parse_line(line, &clock(), new_year).unwrap();
+        assert_eq!(parsed.timestamp.year(), 2024);
+        let line = "01-02 08:59:59.500  1000  5098  5850 D Tag: message";
+        let parsed = LogcatLine::parse_line(line, &clock(), new_year).unwrap();
+        assert_eq!(parsed.timestamp.year(), 2025);
+
+        // A report captured on New Year's Eve whose logcat runs past midnight
+        let new_years_eve = new_year - Duration::days(1) - Duration::hours(9) - Duration::seconds(30);
+        let line = "01-01 00:00:10.000  1000  5098  5850 D Tag: message";
+        let parsed = LogcatLine::parse_line(line, &clock(), new_years_eve).unwrap();
+        assert_eq!(parsed.timestamp.year(), 2025);
+
+        // `-v year` keeps the year logcat printed
+        let line = "2023-12-31 23:59:59.500  1000  5098  5850 D Tag: message";
+        let parsed = LogcatLine::parse_line(line, &clock(), new_year).unwrap();
+        assert_eq!(parsed.timestamp.year(), 2023);
+        assert_eq!(parsed.tag, "Tag");
+    }
+
+    #[test]
+    fn test_device_timezone() {
+        let line = "08-16 10:01:30.003  1000  5098  5850 D Tag: message";
+        let parsed = LogcatLine::parse_line(line, &clock(), reference()).unwrap();
+        assert_eq!(parsed.timestamp.to_rfc3339(), "2024-08-16T10:01:30.003+08:00");
+
+        let utc = ReportClock::new(clock().get_timezone(), TimeDisplay::Utc);
+        let in_utc = LogcatLine::parse_line(line, &utc, reference()).unwrap();
+        assert_eq!(in_utc.timestamp.to_rfc3339(), "2024-08-16T02:01:30.003+00:00");
+        assert_eq!(in_utc.timestamp, parsed.timestamp);
+    }
+
+    #[test]
+    fn test_other_formats() {
+        let brief = b"--------- beginning of main\nD/Tag     ( 1000: 5098): first\nI/Other   ( 5140): second\n";
+        let section = LogcatSection::from_bytes(brief, &clock(), reference()).unwrap();
+        assert_eq!(section.len(), 2);
+        let first = section.get_line(0).unwrap();
+        assert_eq!((first.user.as_str(), first.pid, first.tid), ("1000", 5098, 0));
+        assert_eq!((first.tag.as_str(), first.message.as_str()), ("Tag", "first"));
+        assert_eq!(first.precision, TimePrecision::None);
+
+        let usec = b"08-16 10:01:30.003123 D/Tag( 5098): message\n";
+        let section = LogcatSection::from_bytes(usec, &clock(), reference()).unwrap();
+        let line = section.get_line(0).unwrap();
+        assert_eq!(line.precision, TimePrecision::Micro);
+        assert!(line.to_string().starts_with("2024-08-16 10:01:30.003123 "));
+
+        let long = b"[ 08-16 10:01:30.003  1000  5098: 5850 D/Tag      ]
+first line
+second line
+
+[ 08-16 10:01:31.003  5098: 5098 I/Other    ]
+only line
+
+";
+        let section = LogcatSection::from_bytes(long, &clock(), reference()).unwrap();
+        assert_eq!(section.len(), 2);
+        let first = section.get_line(0).unwrap();
+        assert_eq!((first.user.as_str(), first.pid, first.tid), ("1000", 5098, 5850));
+        assert_eq!(first.message, "first line\nsecond line");
+        assert_eq!(section.get_line(1).unwrap().message, "only line");
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let lines = [
+            "orphan line before any record",
+            "08-16 10:01:30.003  1000  5098  5098 D QSRecord custom(com.google.android.as) listening to true",
+            "08-16 10:01:31.003 10160  5140  5140 E AndroidRuntime: FATAL EXCEPTION: main",
+            "java.lang.NullPointerException",
+            "\tat com.android.systemui.Foo.bar(Foo.java:42)",
+            "",
+            "08-16 10:01:32.003 10160  5140  5140 D Tag: next record",
+            "--------- beginning of crash",
+            "\tat lost.After.marker(Marker.java:1)",
+            "02-30 10:01:33.003 10160  5140  5140 D Tag: impossible date",
+        ];
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&lines, &clock(), reference());
+        assert_eq!(section.len(), 3);
+
+        let qs_record = section.get_line(0).unwrap();
+        assert_eq!(qs_record.tag, "QSRecord");
+        assert_eq!(qs_record.message, "custom(com.google.android.as) listening to true");
+
+        let crash = section.get_line(1).unwrap();
+        assert_eq!(
+            crash.message,
+            "FATAL EXCEPTION: main\njava.lang.NullPointerException\n\tat com.android.systemui.Foo.bar(Foo.java:42)"
+        );
+        assert_eq!(section.get_line(2).unwrap().message, "next record");
+        assert_eq!(section.get_dropped_lines(), 3);
+    }
+
+    #[test]
+    fn test_buffers() {
+        let lines = [
+            "08-16 10:01:29.003  1000  5098  5098 D Before: no marker yet",
+            "--------- beginning of crash",
+            "08-16 10:01:30.003 10160  5140  5140 E AndroidRuntime: FATAL EXCEPTION: main",
+            "--------- beginning of main",
+            "08-16 10:01:31.003 10160  5140  5140 D Tag: main line",
+            "--------- switch to system",
+            "08-16 10:01:32.003  1000  2270  2270 I ActivityManager: system line",
+        ];
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&lines, &clock(), reference());
+        let buffers: Vec<_> = (0..section.len())
+            .map(|i| section.get_line(i).unwrap().buffer)
+            .collect();
+        assert_eq!(
+            buffers,
+            vec![None, Some(LogBuffer::Crash), Some(LogBuffer::Main), Some(LogBuffer::System)]
+        );
+        let crash = section.search_by_buffer(LogBuffer::Crash);
+        assert_eq!(crash.len(), 1);
+        assert_eq!(crash[0].tag, "AndroidRuntime");
+        assert_eq!(LogBuffer::from_marker("--------- beginning of unknown"), None);
+    }
+
+    #[test]
+    fn test_indexed_search() {
+        let logcat = get_test_lines();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(&logcat, &clock(), reference());
+        let scanned = (
+            section.search_by_tag("GestureStubView").len(),
+            section.search_by_keyword("Hidden    old").len(),
+            section.search_by_token("showGestureStub").len(),
+        );
+        assert!(section.get_index().is_none());
+        section.build_index();
+        let indexed = (
+            section.search_by_tag("GestureStubView").len(),
+            section.search_by_keyword("Hidden    old").len(),
+            section.search_by_token("showGestureStub").len(),
+        );
+        assert_eq!(scanned, (3, 3, 2));
+        assert_eq!(indexed, scanned);
+    }
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime};
+
+use crate::bugreport::clock::ReportClock;
+use crate::error::{Error, Result};
+
+/// A span of time with both ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+impl TimeWindow {
+    /// The ends are swapped if `end` comes first.
+    pub fn new(start: DateTime<FixedOffset>, end: DateTime<FixedOffset>) -> Self {
+        if end < start {
+            return Self { start: end, end: start };
+        }
+        Self { start, end }
+    }
+
+    /// From `before` ahead of `center` to `after` past it.
+    pub fn around(center: DateTime<FixedOffset>, before: Duration, after: Duration) -> Self {
+        Self::new(center - before, center + after)
+    }
+
+    /// The `span` leading up to `moment`, e.g. the 30s before an ANR.
+    pub fn before(moment: DateTime<FixedOffset>, span: Duration) -> Self {
+        Self::new(moment - span, moment)
+    }
+
+    /// The `span` following `moment`.
+    pub fn after(moment: DateTime<FixedOffset>, span: Duration) -> Self {
+        Self::new(moment, moment + span)
+    }
+
+    /// Offsets relative to `anchor`, e.g. `relative(anr, -30s, +5s)`.
+    pub fn relative(anchor: DateTime<FixedOffset>, from: Duration, to: Duration) -> Self {
+        Self::new(anchor + from, anchor + to)
+    }
+
+    /// Reads both ends as device wall-clock times, see [`parse_time`].
+    pub fn parse(start: &str, end: &str, clock: &ReportClock) -> Result<Self> {
+        Ok(Self::new(parse_time(start, clock)?, parse_time(end, clock)?))
+    }
+
+    pub fn contains(&self, time: DateTime<FixedOffset>) -> bool {
+        self.start <= time && time <= self.end
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Reads a device wall-clock time such as `2024-08-16 10:01:30` or `2024-08-16 10:01:30.250`;
+/// fractions down to nanoseconds and a `T` separator are accepted.
+pub fn parse_time(input: &str, clock: &ReportClock) -> Result<DateTime<FixedOffset>> {
+    let input = input.trim();
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+        .map(|naive| clock.localize(naive))
+        .ok_or_else(|| Error::bad_timestamp(input))
+}
+
+/// Reads an offset such as `30s`, `-500ms`, `+2m`, `1h` or `1.5s`.
+pub fn parse_offset(input: &str) -> Result<Duration> {
+    let bad = || Error::malformed("time offset");
+    let input = input.trim();
+    let (sign, number) = match input.strip_prefix('-') {
+        Some(rest) => (-1.0, rest),
+        None => (1.0, input.strip_prefix('+').unwrap_or(input)),
+    };
+    let split = number
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(bad)?;
+    let (value, unit) = number.split_at(split);
+    let value: f64 = value.parse().map_err(|_| bad())?;
+    let nanos_per_unit = match unit {
+        "ns" => 1.0,
+        "us" => 1e3,
+        "ms" => 1e6,
+        "s" => 1e9,
+        "m" | "min" => 60e9,
+        "h" => 3600e9,
+        _ => return Err(bad()),
+    };
+    Ok(Duration::nanoseconds((sign * value * nanos_per_unit).round() as i64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::bugreport_txt::BugreportTxt;
+    use crate::bugreport::clock::TimeDisplay;
+
+    const REPORT: &[u8] = b"== dumpstate: 2024-08-16 10:02:11
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+08-16 10:00:59.000  1000  1500  1520 W BinderProxy: Binder transaction too early
+08-16 10:01:10.000  1000  1500  1520 W BinderProxy: Binder call took 2100ms
+08-16 10:01:20.000  1000  1500  1521 D BinderProxy: Binder call took 12ms
+08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: Binder died in systemui
+------ 0.052s was the duration of 'SYSTEM LOG' ------
+------ EVENT LOG (logcat -b events -v threadtime -v printable -v uid -d *:v) ------
+08-16 10:01:15.000  1000  1500  1530 I am_anr: [0,5140,com.android.systemui,952745541,Binder timeout]
+------ 0.010s was the duration of 'EVENT LOG' ------
+";
+
+    fn clock() -> ReportClock {
+        ReportClock::new(ReportClock::parse_timezone("Asia/Shanghai"), TimeDisplay::Device)
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let time = parse_time("2024-08-16 10:01:30.250", &clock()).unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-08-16T10:01:30.250+08:00");
+        let time = parse_time("2024-08-16T10:01:30", &clock()).unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-08-16T10:01:30+08:00");
+        assert!(matches!(parse_time("2024-08-16 10:01", &clock()), Err(Error::BadTimestamp { .. })));
+    }
+
+    #[test]
+    fn test_parse_offset() {
+        assert_eq!(parse_offset("30s").unwrap(), Duration::seconds(30));
+        assert_eq!(parse_offset("-500ms").unwrap(), Duration::milliseconds(-500));
+        assert_eq!(parse_offset("+2m").unwrap(), Duration::minutes(2));
+        assert_eq!(parse_offset("1.5s").unwrap(), Duration::milliseconds(1500));
+        assert!(parse_offset("30").is_err());
+        assert!(parse_offset("s").is_err());
+        assert!(parse_offset("3 days").is_err());
+    }
+
+    #[test]
+    fn test_windows() {
+        let anr = parse_time("2024-08-16 10:01:30", &clock()).unwrap();
+        let before = TimeWindow::before(anr, parse_offset("30s").unwrap());
+        assert_eq!(before, TimeWindow::relative(anr, Duration::seconds(-30), Duration::zero()));
+        assert!(before.contains(anr) && before.contains(anr - Duration::seconds(30)));
+        assert!(!before.contains(anr + Duration::milliseconds(1)));
+        assert_eq!(TimeWindow::around(anr, Duration::seconds(1), Duration::seconds(2)).duration(), Duration::seconds(3));
+
+        let parsed = TimeWindow::parse("2024-08-16 10:02:00", "2024-08-16 10:01:00", &clock()).unwrap();
+        assert!(parsed.start < parsed.end);
+    }
+
+    #[test]
+    fn test_search_by_window() {
+        let mut bugreport = BugreportTxt::from_bytes(REPORT.to_vec());
+        bugreport.load().unwrap();
+        // Both ends are included, unlike Query::between
+        let clock = ReportClock::new(None, TimeDisplay::Device);
+        let window = TimeWindow::parse("2024-08-16 10:01:10", "2024-08-16 10:01:20", &clock).unwrap();
+        let tags: Vec<String> = bugreport.search_by_window(&window).unwrap().into_iter().map(|l| l.tag).collect();
+        assert_eq!(tags, vec!["BinderProxy", "am_anr", "BinderProxy"]);
+    }
+}
@@ -1,122 +1,319 @@
-use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Duration, FixedOffset, NaiveDateTime, Utc};
 use regex::Regex;
-use std::{io, str::Lines};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use super::clock::ReportClock;
+use crate::error::{Error, Result};
 
 lazy_static::lazy_static!(
-    // Uptime: up 0 weeks, 0 days, 1 hour, 59 minutes,
-    static ref UPTIME_REGEX: Regex= Regex::new(r"up (\d+) weeks?, (\d+) days?, (\d+) hours?, (\d+) minutes?").unwrap();
-    // Build fingerprint: 'Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys'
-    static ref VERSION_REGEX: Regex= Regex::new(r"Build fingerprint: '(.*)/(.*)/(.*)/(.*)/(.*):(.*)/(.*)'").unwrap();
+    // Uptime: up 0 weeks, 0 days, 1 hour, 59 minutes, 12 seconds
+    static ref UPTIME_UNIT_REGEX: Regex = Regex::new(r"(\d+) (week|day|hour|min|sec)").unwrap();
+    // Uptime: up time: 1 days, 00:32:10, idle time: ...
+    static ref UPTIME_CLOCK_REGEX: Regex = Regex::new(r"(\d+):(\d{2}):(\d{2})").unwrap();
+    // Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys
+    static ref FINGERPRINT_REGEX: Regex =
+        Regex::new(r"^([^/]+)/([^/]+)/([^:]+):([^/]+)/([^/]+)/([^:]+):([^/]+)/(.+)$").unwrap();
+    // Dumpstate info: id=1 pid=12345 dry_run=0 parallel_run=1 args=-SR bugreport_mode=BUGREPORT_FULL
+    static ref DUMPSTATE_INFO_REGEX: Regex =
+        Regex::new(r"^(?:id=(\d+) )?(?:pid=(\d+) )?.*?(?:args=(.*?))?(?: bugreport_mode=(\S*))?$").unwrap();
 );
 
-#[derive(Debug)]
+/// The components of `Build fingerprint`, `brand/product/device:release/id/incremental:type/tags`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub brand: String,
+    pub product: String,
+    pub device: String,
+    pub release: String,
+    pub build_id: String,
+    pub incremental: String,
+    pub build_type: String,
+    pub tags: String,
+}
+
+impl Fingerprint {
+    pub fn parse(fingerprint: &str) -> Option<Self> {
+        let caps = FINGERPRINT_REGEX.captures(fingerprint.trim())?;
+        let field = |i: usize| caps.get(i).unwrap().as_str().to_string();
+        Some(Fingerprint {
+            brand: field(1),
+            product: field(2),
+            device: field(3),
+            release: field(4),
+            build_id: field(5),
+            incremental: field(6),
+            build_type: field(7),
+            tags: field(8),
+        })
+    }
+}
+
+impl Display for Fingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}/{}:{}/{}/{}:{}/{}",
+            self.brand,
+            self.product,
+            self.device,
+            self.release,
+            self.build_id,
+            self.incremental,
+            self.build_type,
+            self.tags
+        )
+    }
+}
+
+/// The `Dumpstate info` line: how this run of dumpstate was started.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpstateInfo {
+    pub id: Option<u32>,
+    pub pid: Option<u32>,
+    pub args: Option<String>,
+    pub bugreport_mode: Option<String>,
+}
+
+impl DumpstateInfo {
+    fn parse(info: &str) -> Self {
+        let Some(caps) = DUMPSTATE_INFO_REGEX.captures(info.trim()) else {
+            return Self::default();
+        };
+        let text = |i: usize| {
+            caps.get(i)
+                .map(|m| m.as_str().trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        DumpstateInfo {
+            id: text(1).and_then(|id| id.parse().ok()),
+            pid: text(2).and_then(|pid| pid.parse().ok()),
+            args: text(3),
+            bugreport_mode: text(4),
+        }
+    }
+}
+
+/// Everything dumpstate prints before the first section. Only the timestamp is required; any
+/// other line that is missing or unreadable leaves its field `None`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
-    pub timestamp: DateTime<Local>,
+    /// When dumpstate ran, in the offset chosen by `clock`.
+    pub timestamp: DateTime<FixedOffset>,
+    /// The dumpstate time exactly as the device printed it.
+    pub wall_time: NaiveDateTime,
+    pub clock: ReportClock,
+    /// The incremental of the build fingerprint, e.g. `OS2.0.107.0.VOBCNXM`.
     pub version: String,
-    pub uptime: Duration,
+    pub build: Option<String>,
+    pub fingerprint: Option<Fingerprint>,
+    pub bootloader: Option<String>,
+    pub radio: Option<String>,
+    pub network: Option<String>,
+    pub serial: Option<String>,
+    /// The contents of `/proc/version`.
+    pub kernel: Option<String>,
+    /// Version of the dumpstate output format, e.g. `2.0`.
+    pub format_version: Option<String>,
+    /// The IANA timezone some dumpstate builds print in the header, e.g. `Asia/Shanghai`.
+    pub timezone: Option<String>,
+    pub dumpstate_info: Option<DumpstateInfo>,
+    #[serde(with = "duration_millis")]
+    pub uptime: Option<Duration>,
+    /// Number of header lines, i.e. the line the first section starts after.
     pub lines_passed: usize,
 }
 
 impl Metadata {
     pub fn new() -> Self {
+        let now = Utc::now();
         Metadata {
-            timestamp: Local::now(),
+            timestamp: now.fixed_offset(),
+            wall_time: now.naive_utc(),
+            clock: ReportClock::default(),
             version: String::new(),
-            uptime: Duration::seconds(0),
+            build: None,
+            fingerprint: None,
+            bootloader: None,
+            radio: None,
+            network: None,
+            serial: None,
+            kernel: None,
+            format_version: None,
+            timezone: None,
+            dumpstate_info: None,
+            uptime: None,
             lines_passed: 0usize,
         }
     }
 
-    pub fn parse(&mut self, lines: &mut Lines) -> io::Result<()> {
-        loop {
-            let line = self.advance_line(lines).unwrap_or("");
-            if line.starts_with("== dumpstate: ") {
-                self.timestamp = Self::parse_timestamp(&line)?;
-            } else if line.starts_with("Build fingerprint:") {
-                self.version = Self::parse_version(line)?;
-            } else if line.starts_with("Uptime:") {
-                self.uptime = Self::parse_uptime(&line)?;
-                break; // Stop parsing here, though there are two more unnecessary lines
+    /// Reads header lines until the first section marker or the end of `lines`. Fails only when
+    /// there is no usable `== dumpstate:` timestamp.
+    pub fn parse<S: AsRef<str>>(&mut self, lines: &mut impl Iterator<Item = S>) -> Result<()> {
+        let mut has_timestamp = false;
+        while let Some(line) = self.advance_line(lines) {
+            let line = line.as_ref();
+            if line.starts_with("------ ") {
+                // The marker belongs to the first section, not to the header
+                self.lines_passed -= 1;
+                break;
+            }
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                "== dumpstate" => {
+                    let line_number = self.lines_passed;
+                    self.wall_time =
+                        Self::parse_timestamp(value).map_err(|e| e.at_line(line_number))?;
+                    self.timestamp = self.clock.localize(self.wall_time);
+                    has_timestamp = true;
+                }
+                "Build" => self.build = non_empty(value),
+                "Build fingerprint" => {
+                    self.fingerprint = Fingerprint::parse(value.trim_matches('\''));
+                    if let Some(fingerprint) = &self.fingerprint {
+                        self.version = fingerprint.incremental.clone();
+                    }
+                }
+                "Bootloader" => self.bootloader = non_empty(value),
+                "Radio" => self.radio = non_empty(value),
+                "Network" => self.network = non_empty(value.trim_end_matches(',')),
+                "Serial" | "Serial number" => self.serial = non_empty(value),
+                "Kernel" => self.kernel = non_empty(value),
+                "Uptime" => self.uptime = Self::parse_uptime(value),
+                "Bugreport format version" => self.format_version = non_empty(value),
+                "Timezone" => self.timezone = non_empty(value),
+                "Dumpstate info" => self.dumpstate_info = Some(DumpstateInfo::parse(value)),
+                _ => {}
             }
         }
 
+        if !has_timestamp {
+            return Err(Error::TruncatedHeader {
+                line: self.lines_passed,
+                missing: "dumpstate timestamp",
+            });
+        }
         Ok(())
     }
 
-    fn advance_line<'a>(&mut self, lines: &'a mut Lines) -> Option<&'a str> {
-        self.lines_passed += 1;
-        lines.next()
+    /// Switches to `clock` once the device timezone is known, re-reads the timestamp with it and
+    /// tells it the boot time so times printed since boot can be read too.
+    pub fn set_clock(&mut self, clock: ReportClock) {
+        self.timestamp = clock.localize(self.wall_time);
+        let boot_time = self.get_boot_time().map(|boot_time| boot_time.with_timezone(&Utc));
+        self.clock = clock.with_boot_time(boot_time);
+    }
+
+    /// When the device booted, derived from the dumpstate timestamp and the uptime.
+    pub fn get_boot_time(&self) -> Option<DateTime<FixedOffset>> {
+        self.uptime.map(|uptime| self.timestamp - uptime)
+    }
+
+    fn advance_line<S>(&mut self, lines: &mut impl Iterator<Item = S>) -> Option<S> {
+        let line = lines.next();
+        if line.is_some() {
+            self.lines_passed += 1;
+        }
+        line
     }
 
-    fn parse_timestamp(line: &str) -> io::Result<DateTime<Local>> {
-        let timestamp_str = line.trim_start_matches("== dumpstate: ").trim();
+    fn parse_timestamp(timestamp_str: &str) -> Result<NaiveDateTime> {
         NaiveDateTime::parse_from_str(timestamp_str, "%Y-%m-%d %H:%M:%S")
-            .map(|naive_dt| Local.from_local_datetime(&naive_dt).unwrap())
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
-    }
-
-    fn parse_version(line: &str) -> io::Result<String> {
-        // let version_str = line.trim_start_matches("Build fingerprint: ").trim();
-        if let Some(caps) = VERSION_REGEX.captures(line) {
-            let version_str = caps.get(5).unwrap().as_str();
-            Ok(version_str.to_string())
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid version string".to_string(),
-            ))
+            .map_err(|_| Error::bad_timestamp(timestamp_str))
+    }
+
+    /// Understands both `up 0 weeks, 0 days, 1 hour, 59 minutes` as printed by `uptime -p` and
+    /// the `1 days, 00:32:10` form, down to the second.
+    fn parse_uptime(uptime_str: &str) -> Option<Duration> {
+        let mut duration = Duration::zero();
+        let mut found = false;
+        for caps in UPTIME_UNIT_REGEX.captures_iter(uptime_str) {
+            let value = caps.get(1).unwrap().as_str().parse::<i64>().ok()?;
+            duration += match caps.get(2).unwrap().as_str() {
+                "week" => Duration::weeks(value),
+                "day" => Duration::days(value),
+                "hour" => Duration::hours(value),
+                "min" => Duration::minutes(value),
+                _ => Duration::seconds(value),
+            };
+            found = true;
+        }
+        if let Some(caps) = UPTIME_CLOCK_REGEX.captures(uptime_str) {
+            let field = |i: usize| caps.get(i).unwrap().as_str().parse::<i64>().ok();
+            duration += Duration::hours(field(1)?)
+                + Duration::minutes(field(2)?)
+                + Duration::seconds(field(3)?);
+            found = true;
         }
+        found.then_some(duration)
     }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
 
-    fn parse_uptime(line: &str) -> io::Result<Duration> {
-        let uptime_str = line.trim_start_matches("Uptime: ").trim();
-        if let Some(caps) = UPTIME_REGEX.captures(uptime_str) {
-            let weeks = caps.get(1).unwrap().as_str().parse().unwrap();
-            let days = caps.get(2).unwrap().as_str().parse().unwrap();
-            let hours = caps.get(3).unwrap().as_str().parse().unwrap();
-            let minutes = caps.get(4).unwrap().as_str().parse().unwrap();
-            println!(
-                "{} weeks, {} days, {} hours, {} minutes",
-                weeks, days, hours, minutes
-            );
-            // create a duration from the uptime string
-            let duration = Duration::weeks(weeks)
-                + Duration::days(days)
-                + Duration::hours(hours)
-                + Duration::minutes(minutes);
-            Ok(duration)
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid uptime string".to_string(),
-            ))
+/// `chrono::Duration` has no serde support of its own.
+mod duration_millis {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => serializer.serialize_some(&duration.num_milliseconds()),
+            None => serializer.serialize_none(),
         }
     }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<i64>::deserialize(deserializer).map(|millis| millis.map(Duration::milliseconds))
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bugreport::clock::TimeDisplay;
     use std::{fs, path::PathBuf};
 
     #[test]
     fn test_parse_timestamp() {
-        let line = "== dumpstate: 2022-03-14 10:00:00";
-        let timestamp = Metadata::parse_timestamp(line).unwrap();
-        assert_eq!(timestamp.to_rfc3339(), "2022-03-14T10:00:00+08:00");
+        let timestamp = Metadata::parse_timestamp("2022-03-14 10:00:00").unwrap();
+        assert_eq!(timestamp.to_string(), "2022-03-14 10:00:00");
     }
 
     #[test]
-    fn test_parse_version() {
-        let line = "Build fingerprint: 'Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys'";
-        let version = Metadata::parse_version(line).unwrap();
-        assert_eq!(version, "OS2.0.107.0.VOBCNXM");
+    fn test_parse_fingerprint() {
+        let fingerprint = "Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys";
+        let parsed = Fingerprint::parse(fingerprint).unwrap();
+        assert_eq!(parsed.brand, "Xiaomi");
+        assert_eq!(parsed.product, "haotian");
+        assert_eq!(parsed.device, "haotian");
+        assert_eq!(parsed.release, "15");
+        assert_eq!(parsed.build_id, "AQ3A.240812.002");
+        assert_eq!(parsed.incremental, "OS2.0.107.0.VOBCNXM");
+        assert_eq!(parsed.build_type, "userdebug");
+        assert_eq!(parsed.tags, "test-keys");
+        assert_eq!(parsed.to_string(), fingerprint);
+        assert!(Fingerprint::parse("not a fingerprint").is_none());
     }
 
     #[test]
     fn test_parse_uptime() {
-        let line = "Uptime: up 0 weeks, 0 days, 1 hour, 59 minutes";
-        let uptime = Metadata::parse_uptime(line).unwrap();
+        let uptime = Metadata::parse_uptime("up 0 weeks, 0 days, 1 hour, 59 minutes").unwrap();
         assert_eq!(uptime.num_minutes(), 119);
+        let uptime = Metadata::parse_uptime("up 1 week, 2 days, 1 hour, 59 minutes, 7 seconds");
+        assert_eq!(uptime.unwrap().num_seconds(), ((9 * 24 + 1) * 60 + 59) * 60 + 7);
+        let uptime = Metadata::parse_uptime("up time: 1 days, 00:32:10, idle time: 00:10:00");
+        assert_eq!(uptime.unwrap().num_seconds(), 24 * 3600 + 32 * 60 + 10);
+        assert!(Metadata::parse_uptime("unknown").is_none());
     }
 
     #[test]
@@ -125,9 +322,96 @@ mod tests {
         let mut lines = binding.lines();
         let mut metadata = Metadata::new();
         metadata.parse(&mut lines).unwrap();
-        assert_eq!(metadata.timestamp.to_rfc3339(), "2024-08-16T10:02:11+08:00");
+        // The timezone is only known once SYSTEM PROPERTIES is read
+        assert_eq!(metadata.timestamp.to_rfc3339(), "2024-08-16T10:02:11+00:00");
         assert_eq!(metadata.version, "V816.0.12.0.UNCMIXM");
-        assert_eq!(metadata.uptime.num_minutes(), 32);
-        assert_eq!(metadata.lines_passed, 50);
+        assert_eq!(metadata.uptime.unwrap().num_minutes(), 32);
+        // Uptime is line 50, followed by the format version, Dumpstate info and a blank line
+        assert_eq!(metadata.lines_passed, 53);
+    }
+
+    #[test]
+    fn test_set_clock() {
+        let header = "== dumpstate: 2024-08-16 10:02:11\nUptime: up 0 weeks, 0 days, 0 hours, 32 minutes\n";
+        let mut metadata = Metadata::new();
+        metadata.parse(&mut header.lines()).unwrap();
+        let shanghai = ReportClock::parse_timezone("Asia/Shanghai");
+        metadata.set_clock(ReportClock::new(shanghai, TimeDisplay::Device));
+        assert_eq!(metadata.timestamp.to_rfc3339(), "2024-08-16T10:02:11+08:00");
+        metadata.set_clock(ReportClock::new(shanghai, TimeDisplay::Utc));
+        assert_eq!(metadata.timestamp.to_rfc3339(), "2024-08-16T02:02:11+00:00");
+    }
+
+    const HEADER: &str = "========================================================
+== dumpstate: 2024-08-16 10:02:11
+========================================================
+
+Build: AQ3A.240812.002 test-keys
+Build fingerprint: 'Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys'
+Bootloader: unknown
+Radio: 3.1.0.2-0813_1130_8b3b3b6
+Network: China Mobile,
+Module Metadata version: 351010000
+Kernel: Linux version 6.1.75-android14-11-o-g9b5fd3b2b3e4 (builder@host) #1 SMP PREEMPT Fri Aug 9 2024
+Command line: console=ttyMSM0
+Uptime: up 0 weeks, 0 days, 0 hours, 32 minutes, 5 seconds
+Bugreport format version: 2.0
+Dumpstate info: id=1 pid=12345 dry_run=0 parallel_run=1 args=-S -d -z bugreport_mode=BUGREPORT_FULL
+
+------ DUMPSYS CRITICAL (/system/bin/dumpsys) ------
+";
+
+    #[test]
+    fn test_parse_header() {
+        let mut metadata = Metadata::new();
+        metadata.parse(&mut HEADER.lines()).unwrap();
+        assert_eq!(metadata.wall_time.to_string(), "2024-08-16 10:02:11");
+        assert_eq!(metadata.version, "OS2.0.107.0.VOBCNXM");
+        assert_eq!(metadata.build.as_deref(), Some("AQ3A.240812.002 test-keys"));
+        assert_eq!(metadata.fingerprint.as_ref().unwrap().device, "haotian");
+        assert_eq!(metadata.bootloader.as_deref(), Some("unknown"));
+        assert_eq!(metadata.radio.as_deref(), Some("3.1.0.2-0813_1130_8b3b3b6"));
+        assert_eq!(metadata.network.as_deref(), Some("China Mobile"));
+        assert_eq!(metadata.serial, None);
+        assert!(metadata.kernel.as_deref().unwrap().starts_with("Linux version 6.1.75"));
+        assert_eq!(metadata.format_version.as_deref(), Some("2.0"));
+        let info = metadata.dumpstate_info.as_ref().unwrap();
+        assert_eq!(info.id, Some(1));
+        assert_eq!(info.pid, Some(12345));
+        assert_eq!(info.args.as_deref(), Some("-S -d -z"));
+        assert_eq!(info.bugreport_mode.as_deref(), Some("BUGREPORT_FULL"));
+        assert_eq!(metadata.uptime.unwrap().num_seconds(), 32 * 60 + 5);
+        assert_eq!(
+            metadata.get_boot_time().unwrap().naive_local().to_string(),
+            "2024-08-16 09:30:06"
+        );
+        assert_eq!(metadata.lines_passed, 16);
+    }
+
+    #[test]
+    fn test_parse_truncated_header() {
+        // Only the timestamp is required
+        let header = "== dumpstate: 2024-08-16 10:02:11\nBuild fingerprint: 'a/b/c:15/d/e:user/release-keys'\n";
+        let mut metadata = Metadata::new();
+        metadata.parse(&mut header.lines()).unwrap();
+        assert_eq!(metadata.version, "e");
+        assert!(metadata.uptime.is_none());
+        assert!(metadata.get_boot_time().is_none());
+        assert_eq!(metadata.lines_passed, 2);
+
+        let mut metadata = Metadata::new();
+        match metadata.parse(&mut "Build: AQ3A.240812.002\n------ UPTIME (uptime) ------".lines()) {
+            Err(Error::TruncatedHeader { line, missing }) => {
+                assert_eq!(line, 1);
+                assert_eq!(missing, "dumpstate timestamp");
+            }
+            other => panic!("Expected a truncated header, got {:?}", other),
+        }
+
+        let mut metadata = Metadata::new();
+        match metadata.parse(&mut "== dumpstate: 2024-13-16 10:02:11".lines()) {
+            Err(Error::BadTimestamp { line, .. }) => assert_eq!(line, Some(1)),
+            other => panic!("Expected a bad timestamp, got {:?}", other),
+        }
     }
 }
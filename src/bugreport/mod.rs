@@ -1,6 +1,19 @@
+pub mod binder;
 pub mod bugreport;
 pub mod bugreport_txt;
+pub mod cache;
+pub mod clock;
+pub mod crash;
+pub mod decode;
 pub mod dumpsys;
+pub mod locks;
 pub mod logcat;
+pub mod process;
+pub mod properties;
+pub mod query;
 pub mod section;
+pub mod signature;
+pub mod timeline;
+pub mod timing;
+pub mod traces;
 pub mod metadata;
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, HashMap};
+
+/// Uids per Android user; user 10's uids start at 1000000.
+const PER_USER_RANGE: u32 = 100000;
+const FIRST_APPLICATION_UID: u32 = 10000;
+const FIRST_ISOLATED_UID: u32 = 90000;
+
+/// The fixed uids `ps` prints by name, from `android_filesystem_config.h`.
+const SYSTEM_UIDS: [(&str, u32); 26] = [
+    ("root", 0),
+    ("system", 1000),
+    ("radio", 1001),
+    ("bluetooth", 1002),
+    ("graphics", 1003),
+    ("input", 1004),
+    ("audio", 1005),
+    ("camera", 1006),
+    ("log", 1007),
+    ("compass", 1008),
+    ("mount", 1009),
+    ("wifi", 1010),
+    ("adb", 1011),
+    ("install", 1012),
+    ("media", 1013),
+    ("dhcp", 1014),
+    ("sdcard_rw", 1015),
+    ("vpn", 1016),
+    ("keystore", 1017),
+    ("usb", 1018),
+    ("drm", 1019),
+    ("nfc", 1027),
+    ("shell", 2000),
+    ("cache", 2001),
+    ("diag", 2002),
+    ("nobody", 9999),
+];
+
+/// One process as listed by `ps`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub ppid: u32,
+    /// The USER column, e.g. `u0_a160` or `system`.
+    pub user: String,
+    /// The UID column when `ps` prints it, else the uid `user` stands for, e.g. 10160 for
+    /// `u0_a160`. `None` for user names without a known uid.
+    pub uid: Option<u32>,
+    pub name: String,
+    /// The S column: `R`, `S`, `D`, `Z`, ...
+    pub state: char,
+    pub rss_kb: u64,
+    /// Thread ids in the order `ps` listed them; the main thread comes first.
+    pub threads: Vec<u32>,
+}
+
+/// One thread as listed by `ps -T`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    pub tid: u32,
+    pub pid: u32,
+    pub name: String,
+    pub state: char,
+}
+
+/// Processes and threads from the PROCESSES AND THREADS section (`ps -A -T`) and, when the
+/// report has it, the PROCESSES section (`ps -A`), which carries full process names.
+#[derive(Debug, Default)]
+pub struct ProcessTable {
+    processes: BTreeMap<u32, ProcessInfo>,
+    threads: HashMap<u32, ThreadInfo>,
+}
+
+/// Where the columns we need sit in a `ps` header. The last column (`CMD` or `NAME`) may
+/// contain spaces, so it takes the rest of the line.
+struct Columns {
+    count: usize,
+    user: usize,
+    uid: Option<usize>,
+    pid: usize,
+    tid: Option<usize>,
+    ppid: Option<usize>,
+    rss: Option<usize>,
+    state: Option<usize>,
+    /// `NAME` is the full process name, `CMD` the kernel's 15 character thread name.
+    full_name: bool,
+}
+
+impl Columns {
+    fn from_header(line: &str) -> Option<Self> {
+        let names: Vec<&str> = line.split_whitespace().collect();
+        let find = |name: &str| names.iter().position(|column| *column == name);
+        let last = *names.last()?;
+        if last != "CMD" && last != "NAME" {
+            return None;
+        }
+        Some(Columns {
+            count: names.len(),
+            user: find("USER")?,
+            uid: find("UID"),
+            pid: find("PID")?,
+            tid: find("TID"),
+            ppid: find("PPID"),
+            rss: find("RSS"),
+            state: find("S"),
+            full_name: last == "NAME",
+        })
+    }
+}
+
+/// The uid behind a `ps` user name: `u<user>_a<app>` for apps, `u<user>_i<n>` for isolated
+/// processes, `u<user>_<name>` or `<name>` for the fixed uids, or the number itself.
+pub fn parse_uid(user: &str) -> Option<u32> {
+    let fixed = |name: &str| {
+        SYSTEM_UIDS
+            .iter()
+            .find(|(system, _)| *system == name)
+            .map(|(_, uid)| *uid)
+            .or_else(|| name.parse().ok())
+    };
+    let Some((android_user, name)) = user.strip_prefix('u').and_then(|rest| rest.split_once('_')) else {
+        return fixed(user);
+    };
+    let Ok(android_user) = android_user.parse::<u32>() else {
+        return fixed(user);
+    };
+    let app_id = if let Some(app) = name.strip_prefix('a').and_then(|app| app.parse::<u32>().ok()) {
+        FIRST_APPLICATION_UID + app
+    } else if let Some(isolated) = name.strip_prefix('i').and_then(|n| n.parse::<u32>().ok()) {
+        FIRST_ISOLATED_UID + isolated
+    } else {
+        fixed(name)?
+    };
+    Some(android_user * PER_USER_RANGE + app_id)
+}
+
+impl ProcessTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, lines: &[&str]) {
+        let mut columns = None;
+        for line in lines {
+            if let Some(header) = Columns::from_header(line) {
+                columns = Some(header);
+                continue;
+            }
+            if let Some(columns) = &columns {
+                self.parse_row(columns, line);
+            }
+        }
+    }
+
+    fn parse_row(&mut self, columns: &Columns, line: &str) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < columns.count {
+            return;
+        }
+        let number = |index: Option<usize>| index.and_then(|i| fields[i].parse::<u64>().ok());
+        let Some(pid) = number(Some(columns.pid)).map(|pid| pid as u32) else {
+            return;
+        };
+        let tid = number(columns.tid).map_or(pid, |tid| tid as u32);
+        let state = columns
+            .state
+            .and_then(|i| fields[i].chars().next())
+            .unwrap_or('?');
+        let name = fields[columns.count - 1..].join(" ");
+
+        let process = self.processes.entry(pid).or_insert_with(|| ProcessInfo {
+            pid,
+            ppid: 0,
+            user: String::new(),
+            uid: None,
+            name: String::new(),
+            state,
+            rss_kb: 0,
+            threads: Vec::new(),
+        });
+        if tid == pid {
+            process.ppid = number(columns.ppid).unwrap_or(0) as u32;
+            process.user = fields[columns.user].to_string();
+            process.uid = number(columns.uid)
+                .map(|uid| uid as u32)
+                .or_else(|| parse_uid(&process.user));
+            process.state = state;
+            process.rss_kb = number(columns.rss).unwrap_or(0);
+            // A full name from `ps -A` always wins over a truncated thread name
+            if columns.full_name || process.name.is_empty() {
+                process.name = name.clone();
+            }
+        }
+        if columns.tid.is_some() {
+            if !process.threads.contains(&tid) {
+                process.threads.push(tid);
+            }
+            self.threads.insert(tid, ThreadInfo { tid, pid, name, state });
+        }
+    }
+
+    /// Adds everything from `other`, keeping full process names over truncated ones.
+    pub fn merge(&mut self, other: &ProcessTable) {
+        for (pid, process) in &other.processes {
+            match self.processes.get_mut(pid) {
+                Some(existing) => {
+                    if existing.name.len() < process.name.len() {
+                        existing.name = process.name.clone();
+                    }
+                    existing.uid = existing.uid.or(process.uid);
+                    for tid in &process.threads {
+                        if !existing.threads.contains(tid) {
+                            existing.threads.push(*tid);
+                        }
+                    }
+                }
+                None => {
+                    self.processes.insert(*pid, process.clone());
+                }
+            }
+        }
+        for (tid, thread) in &other.threads {
+            self.threads.entry(*tid).or_insert_with(|| thread.clone());
+        }
+    }
+
+    pub fn get_process(&self, pid: u32) -> Option<&ProcessInfo> {
+        self.processes.get(&pid)
+    }
+
+    pub fn get_thread(&self, tid: u32) -> Option<&ThreadInfo> {
+        self.threads.get(&tid)
+    }
+
+    /// `5140` -> `com.android.systemui`.
+    pub fn process_name(&self, pid: u32) -> Option<&str> {
+        self.processes.get(&pid).map(|process| process.name.as_str())
+    }
+
+    /// `5300` -> `RenderThread`.
+    pub fn thread_name(&self, tid: u32) -> Option<&str> {
+        self.threads.get(&tid).map(|thread| thread.name.as_str())
+    }
+
+    /// Processes whose name is exactly `name`.
+    pub fn find_by_name<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a ProcessInfo> {
+        self.processes.values().filter(move |process| process.name == name)
+    }
+
+    pub fn processes(&self) -> impl Iterator<Item = &ProcessInfo> {
+        self.processes.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.processes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PS_THREADS: &[&str] = &[
+        "LABEL                          USER           PID   TID  PPID     VSZ    RSS WCHAN            ADDR S PRI  NI RTPRIO SCH  PCY     TIME CMD",
+        "u:r:init:s0                    root             1     1     0 10904816  9256 do_epoll_wait       0 S  19   0      -   0  fg 00:00:02 init",
+        "u:r:platform_app:s0:c512,c768  u0_a160       5140  5140  1026 17562428 312456 do_epoll_wait     0 S  10 -10      -   0  ta 00:01:07 ndroid.systemui",
+        "u:r:platform_app:s0:c512,c768  u0_a160       5140  5300  1026 17562428 312456 futex_wait_queue  0 S  16  -4      -   0  ta 00:00:21 RenderThread",
+        "u:r:platform_app:s0:c512,c768  u0_a160       5140  5301  1026 17562428 312456 binder_ioctl      0 S  20   0      -   0  ta 00:00:00 binder:5140_1",
+    ];
+
+    #[test]
+    fn test_parse_threads() {
+        let mut table = ProcessTable::new();
+        table.parse(PS_THREADS);
+        assert_eq!(table.len(), 2);
+        let systemui = table.get_process(5140).unwrap();
+        assert_eq!(systemui.ppid, 1026);
+        assert_eq!(systemui.user, "u0_a160");
+        assert_eq!(systemui.uid, Some(10160));
+        assert_eq!(table.get_process(1).unwrap().uid, Some(0));
+        assert_eq!(systemui.state, 'S');
+        assert_eq!(systemui.rss_kb, 312456);
+        assert_eq!(systemui.threads, vec![5140, 5300, 5301]);
+        assert_eq!(table.process_name(5140), Some("ndroid.systemui"));
+        assert_eq!(table.thread_name(5300), Some("RenderThread"));
+        assert_eq!(table.get_thread(5301).unwrap().pid, 5140);
+        assert_eq!(table.thread_name(9999), None);
+    }
+
+    #[test]
+    fn test_merge_full_names() {
+        let mut table = ProcessTable::new();
+        table.parse(PS_THREADS);
+        let mut processes = ProcessTable::new();
+        processes.parse(&[
+            "USER           PID  PPID     VSZ    RSS WCHAN            ADDR S NAME",
+            "u0_a160       5140  1026 17562428 312456 do_epoll_wait     0 S com.android.systemui",
+        ]);
+        table.merge(&processes);
+        assert_eq!(table.process_name(5140), Some("com.android.systemui"));
+        assert_eq!(table.thread_name(5140), Some("ndroid.systemui"));
+        assert_eq!(table.find_by_name("com.android.systemui").count(), 1);
+    }
+
+    #[test]
+    fn test_parse_uid() {
+        assert_eq!(parse_uid("u0_a160"), Some(10160));
+        assert_eq!(parse_uid("u10_a5"), Some(1010005));
+        assert_eq!(parse_uid("u0_i12"), Some(90012));
+        assert_eq!(parse_uid("u10_system"), Some(1001000));
+        assert_eq!(parse_uid("system"), Some(1000));
+        assert_eq!(parse_uid("shell"), Some(2000));
+        assert_eq!(parse_uid("1053"), Some(1053));
+        assert_eq!(parse_uid("unknown_user"), None);
+
+        // The UID column wins over the user name
+        let mut table = ProcessTable::new();
+        table.parse(&[
+            "USER           PID   UID  PPID     VSZ    RSS WCHAN            ADDR S NAME",
+            "webview_zygote 1200  1053     1 4200000 81234 do_sys_poll       0 S webview_zygote",
+        ]);
+        assert_eq!(table.get_process(1200).unwrap().uid, Some(1053));
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+/// The SYSTEM PROPERTIES section, i.e. the output of `getprop`, keyed by property name.
+#[derive(Debug, Default)]
+pub struct Properties(BTreeMap<String, String>);
+
+impl Properties {
+    pub fn new() -> Self {
+        Self(BTreeMap::new())
+    }
+
+    /// Reads `[key]: [value]` lines. A value containing newlines spans several lines and ends
+    /// at the line that closes its bracket.
+    pub fn parse(&mut self, lines: &[&str]) {
+        let mut pending: Option<(String, String)> = None;
+        for line in lines {
+            if let Some((key, mut value)) = pending.take() {
+                value.push('\n');
+                match line.strip_suffix(']') {
+                    Some(rest) => {
+                        value.push_str(rest);
+                        self.0.insert(key, value);
+                    }
+                    None => {
+                        value.push_str(line);
+                        pending = Some((key, value));
+                    }
+                }
+                continue;
+            }
+
+            let Some((key, value)) = line
+                .strip_prefix('[')
+                .and_then(|line| line.split_once("]: ["))
+            else {
+                continue;
+            };
+            match value.strip_suffix(']') {
+                Some(value) => {
+                    self.0.insert(key.to_string(), value.to_string());
+                }
+                None => pending = Some((key.to_string(), value.to_string())),
+            }
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// All properties, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Properties whose name starts with `prefix`, e.g. `ro.build.`.
+    pub fn with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.iter().filter(move |(key, _)| key.starts_with(prefix))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let lines = [
+            "[persist.sys.timezone]: [Asia/Shanghai]",
+            "[ro.build.version.sdk]: [35]",
+            "[ro.build.version.release]: [15]",
+            "[ro.empty]: []",
+            "[ro.multiline]: [first",
+            "second]",
+            "not a property",
+        ];
+        let mut properties = Properties::new();
+        properties.parse(&lines);
+        assert_eq!(properties.len(), 5);
+        assert_eq!(properties.get("ro.build.version.sdk"), Some("35"));
+        assert_eq!(properties.get("persist.sys.timezone"), Some("Asia/Shanghai"));
+        assert_eq!(properties.get("ro.empty"), Some(""));
+        assert_eq!(properties.get("ro.multiline"), Some("first\nsecond"));
+        assert_eq!(properties.get("ro.missing"), None);
+        let build: Vec<_> = properties.with_prefix("ro.build.").map(|(key, _)| key).collect();
+        assert_eq!(build, vec!["ro.build.version.release", "ro.build.version.sdk"]);
+    }
+}
@@ -0,0 +1,263 @@
+use std::ops::Not;
+
+use chrono::{DateTime, FixedOffset};
+use rayon::prelude::*;
+use regex::Regex;
+
+use super::logcat::{LogBuffer, LogcatLine, LogcatSection};
+use super::process::ProcessTable;
+
+/// A predicate over log lines, built from simple filters combined with AND/OR/NOT:
+///
+/// ```ignore
+/// // W and above from system_server between 10:01 and 10:02 mentioning Binder
+/// let query = Query::min_level('W')
+///     .and(Query::process("system_server"))
+///     .and(Query::between(start, end))
+///     .and(Query::contains("Binder"));
+/// let lines = bugreport.query(&query)?;
+/// ```
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// Every line.
+    All,
+    Tag(String),
+    /// This level or a more severe one, e.g. `W` keeps `W`, `E`, `F` and `A`.
+    MinLevel(char),
+    Pid(u32),
+    Tid(u32),
+    /// The process name as listed by `ps`, e.g. `system_server`.
+    Process(String),
+    Buffer(LogBuffer),
+    /// `start` inclusive, `end` exclusive; either may be open.
+    TimeRange {
+        start: Option<DateTime<FixedOffset>>,
+        end: Option<DateTime<FixedOffset>>,
+    },
+    /// The message contains the text.
+    Contains(String),
+    /// The message matches the regex.
+    Matches(Regex),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+}
+
+/// Android's priorities: `V` < `D` < `I` < `W` < `E` < `F` = `A`.
+fn severity(level: char) -> u8 {
+    match level {
+        'V' => 2,
+        'D' => 3,
+        'I' => 4,
+        'W' => 5,
+        'E' => 6,
+        'F' | 'A' => 7,
+        _ => 0,
+    }
+}
+
+impl Query {
+    pub fn tag(tag: &str) -> Self {
+        Query::Tag(tag.to_string())
+    }
+
+    pub fn min_level(level: char) -> Self {
+        Query::MinLevel(level)
+    }
+
+    pub fn pid(pid: u32) -> Self {
+        Query::Pid(pid)
+    }
+
+    pub fn tid(tid: u32) -> Self {
+        Query::Tid(tid)
+    }
+
+    pub fn process(name: &str) -> Self {
+        Query::Process(name.to_string())
+    }
+
+    pub fn buffer(buffer: LogBuffer) -> Self {
+        Query::Buffer(buffer)
+    }
+
+    pub fn between(start: DateTime<FixedOffset>, end: DateTime<FixedOffset>) -> Self {
+        Query::TimeRange {
+            start: Some(start),
+            end: Some(end),
+        }
+    }
+
+    pub fn since(start: DateTime<FixedOffset>) -> Self {
+        Query::TimeRange {
+            start: Some(start),
+            end: None,
+        }
+    }
+
+    pub fn until(end: DateTime<FixedOffset>) -> Self {
+        Query::TimeRange {
+            start: None,
+            end: Some(end),
+        }
+    }
+
+    pub fn contains(text: &str) -> Self {
+        Query::Contains(text.to_string())
+    }
+
+    /// Fails if `pattern` is not a valid regex.
+    pub fn matches(pattern: &str) -> std::result::Result<Self, regex::Error> {
+        Regex::new(pattern).map(Query::Matches)
+    }
+
+    pub fn and(self, other: Query) -> Self {
+        match self {
+            Query::And(mut queries) => {
+                queries.push(other);
+                Query::And(queries)
+            }
+            query => Query::And(vec![query, other]),
+        }
+    }
+
+    pub fn or(self, other: Query) -> Self {
+        match self {
+            Query::Or(mut queries) => {
+                queries.push(other);
+                Query::Or(queries)
+            }
+            query => Query::Or(vec![query, other]),
+        }
+    }
+
+    /// Whether evaluating the query needs the process table.
+    pub fn needs_processes(&self) -> bool {
+        match self {
+            Query::Process(_) => true,
+            Query::And(queries) | Query::Or(queries) => queries.iter().any(Query::needs_processes),
+            Query::Not(query) => query.needs_processes(),
+            _ => false,
+        }
+    }
+
+    /// Tests one line. `processes` is only consulted by [`Query::Process`], which never
+    /// matches without it.
+    pub fn test(&self, line: &LogcatLine, processes: Option<&ProcessTable>) -> bool {
+        match self {
+            Query::All => true,
+            Query::Tag(tag) => line.tag == *tag,
+            Query::MinLevel(level) => severity(line.level) >= severity(*level),
+            Query::Pid(pid) => line.pid == *pid,
+            Query::Tid(tid) => line.tid == *tid,
+            Query::Process(name) => {
+                processes.and_then(|table| line.process_name(table)) == Some(name.as_str())
+            }
+            Query::Buffer(buffer) => line.buffer == Some(*buffer),
+            Query::TimeRange { start, end } => {
+                start.is_none_or(|start| line.timestamp >= start)
+                    && end.is_none_or(|end| line.timestamp < end)
+            }
+            Query::Contains(text) => line.message.contains(text.as_str()),
+            Query::Matches(regex) => regex.is_match(&line.message),
+            Query::And(queries) => queries.iter().all(|query| query.test(line, processes)),
+            Query::Or(queries) => queries.iter().any(|query| query.test(line, processes)),
+            Query::Not(query) => !query.test(line, processes),
+        }
+    }
+
+    /// The matching lines of one section, in section order.
+    pub fn run<'a>(
+        &self,
+        section: &'a LogcatSection,
+        processes: Option<&ProcessTable>,
+    ) -> Vec<&'a LogcatLine> {
+        section
+            .lines()
+            .par_iter()
+            .filter(|line| self.test(line, processes))
+            .collect()
+    }
+}
+
+impl Not for Query {
+    type Output = Query;
+
+    fn not(self) -> Query {
+        Query::Not(Box::new(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::bugreport_txt::BugreportTxt;
+    use crate::bugreport::clock::{ReportClock, TimeDisplay};
+    use chrono::NaiveDate;
+
+    const REPORT: &[u8] = b"== dumpstate: 2024-08-16 10:02:11
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+--------- beginning of main
+08-16 10:00:59.000  1000  1500  1520 W BinderProxy: Binder transaction too early
+08-16 10:01:10.000  1000  1500  1520 W BinderProxy: Binder call took 2100ms
+08-16 10:01:20.000  1000  1500  1521 D BinderProxy: Binder call took 12ms
+08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: Binder died in systemui
+--------- beginning of crash
+08-16 10:01:40.000  1000  1500  1500 E ActivityManager: ANR in com.android.systemui, Binder stuck
+------ 0.052s was the duration of 'SYSTEM LOG' ------
+------ EVENT LOG (logcat -b events -v threadtime -v printable -v uid -d *:v) ------
+08-16 10:01:15.000  1000  1500  1530 I am_anr: [0,5140,com.android.systemui,952745541,Binder timeout]
+------ 0.010s was the duration of 'EVENT LOG' ------
+------ PROCESSES AND THREADS (ps -A -T) ------
+LABEL                          USER           PID   TID  PPID     VSZ    RSS WCHAN            ADDR S PRI  NI RTPRIO SCH  PCY     TIME CMD
+u:r:system_server:s0           system        1500  1500   900 17562428 312456 do_epoll_wait     0 S  18  -2      -   0  fg 00:05:07 system_server
+u:r:platform_app:s0:c512,c768  u0_a160       5140  5140   900 17562428 312456 do_epoll_wait     0 S  10 -10      -   0  ta 00:01:07 ndroid.systemui
+------ 0.030s was the duration of 'PROCESSES AND THREADS' ------
+";
+
+    fn at(hour: u32, min: u32) -> DateTime<FixedOffset> {
+        let naive = NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_opt(hour, min, 0)
+            .unwrap();
+        ReportClock::new(None, TimeDisplay::Device).localize(naive)
+    }
+
+    fn load() -> BugreportTxt {
+        let mut bugreport = BugreportTxt::from_bytes(REPORT.to_vec());
+        bugreport.load().unwrap();
+        bugreport
+    }
+
+    #[test]
+    fn test_combined_query() {
+        let bugreport = load();
+        let query = Query::min_level('W')
+            .and(Query::process("system_server"))
+            .and(Query::between(at(10, 1), at(10, 2)))
+            .and(Query::contains("Binder"));
+        let lines = bugreport.query(&query).unwrap();
+        let messages: Vec<&str> = lines.iter().map(|line| line.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec!["Binder call took 2100ms", "ANR in com.android.systemui, Binder stuck"]
+        );
+    }
+
+    #[test]
+    fn test_boolean_query() {
+        let bugreport = load();
+        // Results from both sections come back in timestamp order
+        let query = Query::tag("am_anr").or(Query::tag("AndroidRuntime"));
+        let tags: Vec<&str> = bugreport.query(&query).unwrap().iter().map(|l| l.tag.as_str()).collect();
+        assert_eq!(tags, vec!["am_anr", "AndroidRuntime"]);
+
+        let query = Query::pid(1500).and(!Query::buffer(LogBuffer::Crash)).and(!Query::tid(1521));
+        assert_eq!(bugreport.query(&query).unwrap().len(), 3);
+
+        let query = Query::matches(r"took \d{4}ms").unwrap().or(Query::since(at(10, 1)).and(Query::min_level('E')));
+        assert_eq!(bugreport.query(&query).unwrap().len(), 3);
+        assert!(Query::matches("(").is_err());
+        assert_eq!(bugreport.query(&Query::All).unwrap().len(), 6);
+    }
+}
@@ -1,9 +1,19 @@
 use super::{
+    binder::BinderTransactions,
+    decode::{self, DecodeMode},
     dumpsys::Dumpsys,
     logcat::{LogcatLine, LogcatSection},
+    process::ProcessTable,
+    properties::Properties,
+    traces::AnrTraces,
 };
+use crate::error::Result;
+use super::clock::ReportClock;
+use chrono::{Duration, NaiveDateTime};
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::ops::{Range, RangeInclusive};
+use std::sync::OnceLock;
 
 lazy_static! {
     pub static ref SECTION_BEGIN: Regex =
@@ -13,11 +23,39 @@ lazy_static! {
         Regex::new(r#"------ (\d+.\d+)s was the duration of '(.*?)(?: \(.*\))?' ------"#).unwrap();
 }
 
+/// The sections dumpstate fills with `dumpsys` output.
+pub(crate) const DUMPSYS_SECTIONS: [&str; 3] = ["DUMPSYS", "DUMPSYS CRITICAL", "DUMPSYS HIGH"];
+
+/// `0.052` -> 52ms, as dumpstate and dumpsys print durations in seconds.
+pub(crate) fn parse_seconds(seconds: &str) -> Option<Duration> {
+    let seconds: f64 = seconds.parse().ok()?;
+    Some(Duration::microseconds((seconds * 1_000_000.0).round() as i64))
+}
+
+/// Whether `line` is the marker dumpstate prints when it kills a section's command, e.g.
+/// `*** command 'logcat -L -d' timed out after 10.002s (killing pid 3030)`.
+pub(crate) fn is_command_timeout(line: &[u8]) -> bool {
+    line.starts_with(b"*** command ") && memchr::memmem::find(line, b"timed out").is_some()
+}
+
+/// Whether `line` is the marker dumpsys prints when a service does not finish its dump in
+/// time, e.g. `*** SERVICE 'activity' DUMP TIMEOUT (10000ms) EXPIRED ***`.
+pub(crate) fn is_service_timeout(line: &[u8]) -> bool {
+    line.starts_with(b"*** ") && memchr::memmem::find(line, b"TIMEOUT").is_some()
+}
+
 #[derive(Debug)]
 pub enum SectionContent {
     SystemLog(LogcatSection),
     EventLog(LogcatSection),
+    /// `dmesg`, read into log lines from the kernel buffer.
+    KernelLog(LogcatSection),
     Dumpsys(Dumpsys),
+    Properties(Properties),
+    Processes(ProcessTable),
+    Traces(AnrTraces),
+    /// The binder debugfs `transactions` or `state` file.
+    Binder(BinderTransactions),
     Other,
 }
 
@@ -26,62 +64,237 @@ impl PartialEq for SectionContent {
         match (self, other) {
             (Self::SystemLog(_), Self::SystemLog(_)) => true,
             (Self::EventLog(_), Self::EventLog(_)) => true,
+            (Self::KernelLog(_), Self::KernelLog(_)) => true,
             (Self::Dumpsys(_), Self::Dumpsys(_)) => true,
+            (Self::Properties(_), Self::Properties(_)) => true,
+            (Self::Processes(_), Self::Processes(_)) => true,
+            (Self::Traces(_), Self::Traces(_)) => true,
+            (Self::Binder(_), Self::Binder(_)) => true,
             (Self::Other, Self::Other) => true,
             _ => false,
         }
     }
 }
 
+impl SectionContent {
+    pub fn as_logcat(&self) -> Option<&LogcatSection> {
+        match self {
+            SectionContent::SystemLog(s)
+            | SectionContent::EventLog(s)
+            | SectionContent::KernelLog(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_dumpsys(&self) -> Option<&Dumpsys> {
+        match self {
+            SectionContent::Dumpsys(d) => Some(d),
+            _ => None,
+        }
+    }
+
+    pub fn as_properties(&self) -> Option<&Properties> {
+        match self {
+            SectionContent::Properties(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn as_processes(&self) -> Option<&ProcessTable> {
+        match self {
+            SectionContent::Processes(p) => Some(p),
+            _ => None,
+        }
+    }
+
+    pub fn as_traces(&self) -> Option<&AnrTraces> {
+        match self {
+            SectionContent::Traces(t) => Some(t),
+            _ => None,
+        }
+    }
+
+    pub fn as_binder(&self) -> Option<&BinderTransactions> {
+        match self {
+            SectionContent::Binder(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// The empty content a section called `name` is parsed into.
+    pub(crate) fn for_name(name: &str) -> Self {
+        match name {
+            "SYSTEM LOG" => SectionContent::SystemLog(LogcatSection::new(Vec::new())),
+            "EVENT LOG" => SectionContent::EventLog(LogcatSection::new(Vec::new())),
+            "KERNEL LOG" => SectionContent::KernelLog(LogcatSection::new(Vec::new())),
+            name if DUMPSYS_SECTIONS.contains(&name) => SectionContent::Dumpsys(Dumpsys::new()),
+            "SYSTEM PROPERTIES" => SectionContent::Properties(Properties::new()),
+            "PROCESSES AND THREADS" | "PROCESSES" => SectionContent::Processes(ProcessTable::new()),
+            "VM TRACES AT LAST ANR" | "VM TRACES JUST NOW" => SectionContent::Traces(AnrTraces::new()),
+            "BINDER TRANSACTIONS" | "BINDER STATE" => SectionContent::Binder(BinderTransactions::new()),
+            _ => SectionContent::Other,
+        }
+    }
+
+    fn parse(&mut self, lines: &[&str], clock: &ReportClock, reference: NaiveDateTime) {
+        match self {
+            SectionContent::SystemLog(s) | SectionContent::EventLog(s) => {
+                s.parse(lines, clock, reference);
+            }
+            SectionContent::KernelLog(s) => {
+                s.parse_kernel(lines, clock, reference);
+            }
+            SectionContent::Dumpsys(s) => {
+                s.parse(lines);
+            }
+            SectionContent::Properties(p) => {
+                p.parse(lines);
+            }
+            SectionContent::Processes(p) => {
+                p.parse(lines);
+            }
+            SectionContent::Traces(t) => {
+                t.parse(lines);
+            }
+            SectionContent::Binder(b) => {
+                b.parse(lines);
+            }
+            _ => {}
+        };
+    }
+}
+
+#[derive(Debug)]
+struct ParsedSection {
+    content: SectionContent,
+    repaired_lines: Vec<usize>,
+}
+
+/// One section of the report. Loading only records where the section is; its content is
+/// parsed the first time it is asked for and cached from then on.
 #[derive(Debug)]
 pub struct Section {
     pub name: String,
+    /// The command dumpstate ran for this section, e.g. `logcat -v threadtime -d *:v`.
+    pub command: Option<String>,
     start_line: usize,
     end_line: usize,
-    pub content: SectionContent,
+    byte_range: Range<usize>,
+    /// How long dumpstate spent on the section, from its closing marker.
+    duration: Option<Duration>,
+    parsed: OnceLock<ParsedSection>,
 }
 
 impl Section {
-    pub fn new(name: String, start_line: usize, end_line: usize, content: SectionContent) -> Self {
+    pub fn new(
+        name: String,
+        command: Option<String>,
+        start_line: usize,
+        end_line: usize,
+        byte_range: Range<usize>,
+        duration: Option<Duration>,
+    ) -> Self {
         Self {
             name,
+            command,
             start_line,
             end_line,
-            content,
+            byte_range,
+            duration,
+            parsed: OnceLock::new(),
         }
     }
 
+    pub fn get_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Whether the section's command was killed for running too long, which leaves its
+    /// output incomplete. `raw` is the whole report. A single dumpsys service timing out
+    /// does not count; see [`DumpsysEntry::timed_out`](super::dumpsys::DumpsysEntry).
+    pub fn is_timed_out(&self, raw: &[u8]) -> bool {
+        decode::RawLines::new(&raw[self.byte_range.clone()]).any(is_command_timeout)
+    }
+
     pub fn get_line_numbers(&self) -> usize {
-        self.end_line - self.start_line + 1
+        (self.end_line + 1).saturating_sub(self.start_line)
     }
 
-    pub fn parse(&mut self, lines: &[&str], year: i32) {
-        match self.content {
-            SectionContent::SystemLog(ref mut s) | SectionContent::EventLog(ref mut s) => {
-                s.parse(lines, year);
-            }
-            SectionContent::Dumpsys(ref mut s) => {
-                s.parse(lines, year);
-            }
-            _ => {}
-        };
+    /// 1-based, inclusive range of the lines between the section markers.
+    pub fn get_line_range(&self) -> RangeInclusive<usize> {
+        self.start_line..=self.end_line
     }
 
-    pub fn search_by_tag(&self, tag: &str) -> Option<Vec<LogcatLine>> {
-        match self.content {
-            SectionContent::SystemLog(ref s) | SectionContent::EventLog(ref s) => {
-                Some(s.search_by_tag(tag))
-            }
-            _ => None,
+    /// Byte range of the lines between the section markers within the report.
+    pub fn get_byte_range(&self) -> Range<usize> {
+        self.byte_range.clone()
+    }
+
+    /// The parsed content, or `None` if nobody has asked for it yet.
+    pub fn content(&self) -> Option<&SectionContent> {
+        self.parsed.get().map(|parsed| &parsed.content)
+    }
+
+    pub fn is_parsed(&self) -> bool {
+        self.parsed.get().is_some()
+    }
+
+    /// Lines of this section that were not valid UTF-8, once it has been parsed.
+    pub fn get_repaired_lines(&self) -> &[usize] {
+        self.parsed
+            .get()
+            .map_or(&[], |parsed| parsed.repaired_lines.as_slice())
+    }
+
+    /// Parses the section out of `raw`, the bytes of the whole report, unless that already
+    /// happened. `reference` is the dumpstate time as the device printed it.
+    pub fn parse(
+        &self,
+        raw: &[u8],
+        mode: DecodeMode,
+        clock: &ReportClock,
+        reference: NaiveDateTime,
+    ) -> Result<&SectionContent> {
+        if let Some(parsed) = self.parsed.get() {
+            return Ok(&parsed.content);
         }
+
+        let (lines, repaired_lines) =
+            decode::decode_lines(&raw[self.byte_range.clone()], mode, self.start_line)
+                .map_err(|e| e.in_section(&self.name))?;
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_ref()).collect();
+        let mut content = SectionContent::for_name(&self.name);
+        content.parse(&lines, clock, reference);
+
+        // Another thread may have won the race; either result is the same.
+        let _ = self.parsed.set(ParsedSection {
+            content,
+            repaired_lines,
+        });
+        Ok(&self.parsed.get().unwrap().content)
     }
 
-    pub fn search_by_time(&self, time: &str) -> Option<Vec<LogcatLine>> {
-        match self.content {
-            SectionContent::SystemLog(ref s) | SectionContent::EventLog(ref s) => {
-                Some(s.search_by_time(time))
-            }
-            _ => None,
+    /// Installs content parsed elsewhere, e.g. restored from the on-disk cache.
+    pub(crate) fn set_content(&self, content: SectionContent, repaired_lines: Vec<usize>) {
+        let _ = self.parsed.set(ParsedSection {
+            content,
+            repaired_lines,
+        });
+    }
+
+    /// Searches the parsed content; `None` for sections that are not logs or not parsed yet.
+    pub fn search_by_tag(&self, tag: &str) -> Option<Vec<LogcatLine>> {
+        self.content()?.as_logcat().map(|s| s.search_by_tag(tag))
+    }
+
+    pub fn search_by_time(
+        &self,
+        time: &str,
+        clock: &ReportClock,
+    ) -> Result<Option<Vec<LogcatLine>>> {
+        match self.content().and_then(SectionContent::as_logcat) {
+            Some(s) => s.search_by_time(time, clock).map(Some),
+            None => Ok(None),
         }
     }
 }
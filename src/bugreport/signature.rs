@@ -0,0 +1,253 @@
+use std::fmt::{self, Display, Formatter};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use xxhash_rust::xxh3::xxh3_64;
+
+use super::traces::StackFrame;
+
+lazy_static! {
+    /// `at com.foo.Bar.baz(Bar.java:12)`, with or without the `at ` and the location.
+    static ref JAVA_FRAME: Regex = Regex::new(r"^(?:at )?(?P<method>[\w$.<>/-]+)(?:\(.*\))?$").unwrap();
+    /// `native: #00 pc 000000000009c9e4  /apex/.../libc.so (__ioctl+4) (BuildId: 1a2b3c)`.
+    static ref NATIVE_FRAME: Regex =
+        Regex::new(r"^(?:native: )?#\d+ pc [0-9a-fA-F]+\s+(?P<library>\S+)(?P<rest>.*)$").unwrap();
+    /// D8 and R8 lambdas (`$$ExternalSyntheticLambda3`), older desugared ones
+    /// (`$$Lambda$Foo$2`, `-$$Lambda$Foo$AbCd`) and runtime ones (`$$Lambda/0x0000000801`).
+    static ref LAMBDA_CLASS: Regex =
+        Regex::new(r"-?\$\$(?:ExternalSynthetic)?Lambda(?:\d+|[$/][\w$]*)?").unwrap();
+    /// `lambda$onCreate$3`, the method a lambda body is compiled into.
+    static ref LAMBDA_METHOD: Regex = Regex::new(r"lambda\$(?P<name>\w+?)\$\d+").unwrap();
+    /// `Foo$1`: anonymous classes are numbered in source order.
+    static ref ANONYMOUS_CLASS: Regex = Regex::new(r"\$\d+\b").unwrap();
+}
+
+/// How many app frames a signature keeps by default.
+pub const DEFAULT_DEPTH: usize = 5;
+
+/// Packages whose frames say where a thread is, but not which app code got it there.
+const FRAMEWORK_PACKAGES: [&str; 11] = [
+    "android.",
+    "androidx.",
+    "com.android.internal.",
+    "dalvik.",
+    "java.",
+    "javax.",
+    "jdk.",
+    "kotlin.",
+    "kotlinx.",
+    "libcore.",
+    "sun.",
+];
+
+/// A frame as it reads in every build: no line numbers, addresses or build ids, and lambdas and
+/// anonymous classes without the numbers the compiler gave them. `None` for lines that are not
+/// frames, like `- locked <0x...>` or `... 5 more`.
+pub fn normalize_frame(frame: &str) -> Option<String> {
+    normalize(frame).map(|(frame, _)| frame)
+}
+
+/// The normalized frame and whether it is a Java one.
+fn normalize(frame: &str) -> Option<(String, bool)> {
+    let frame = frame.trim();
+    if let Some(caps) = NATIVE_FRAME.captures(frame) {
+        let library = caps["library"].rsplit('/').next().unwrap_or_default();
+        let frame = match native_symbol(&caps["rest"]) {
+            Some(symbol) => format!("{} {}", library, symbol),
+            None => library.to_string(),
+        };
+        return Some((frame, false));
+    }
+    let method = &JAVA_FRAME.captures(frame)?["method"];
+    if !method.contains('.') {
+        return None;
+    }
+    let method = LAMBDA_CLASS.replace_all(method, "$$$$Lambda");
+    let method = LAMBDA_METHOD.replace_all(&method, "lambda$$$name");
+    Some((ANONYMOUS_CLASS.replace_all(&method, "$$").into_owned(), true))
+}
+
+/// The function of a native frame without its offset: `(android::Foo::bar(int)+64)` ->
+/// `android::Foo::bar(int)`. The `(offset 0x1000)` and `(BuildId: ...)` parts are skipped.
+fn native_symbol(rest: &str) -> Option<&str> {
+    let mut rest = rest.trim();
+    while let Some(start) = rest.rfind(" (").or_else(|| rest.starts_with('(').then_some(0)) {
+        let part = rest[start..].trim_start().strip_prefix('(')?.strip_suffix(')')?;
+        rest = rest[..start].trim_end();
+        if part.starts_with("offset ") || part.starts_with("BuildId: ") {
+            continue;
+        }
+        let symbol = match part.rfind('+') {
+            Some(plus) if part[plus + 1..].chars().all(|c| c.is_ascii_digit()) => &part[..plus],
+            _ => part,
+        };
+        return Some(symbol);
+    }
+    None
+}
+
+/// Whether a normalized Java frame is app code: not in the framework and not an obfuscated
+/// class like `a.b.c`, which R8 names anew in every release.
+fn is_app_frame(method: &str) -> bool {
+    if FRAMEWORK_PACKAGES.iter().any(|package| method.starts_with(package)) {
+        return false;
+    }
+    let class = method.rsplit('.').nth(1).unwrap_or_default();
+    class.split('$').next().unwrap_or_default().len() > 2
+}
+
+/// What identifies a stack across builds and releases: the frame the thread is in plus its top
+/// app frames, normalized by [`normalize_frame`]. Stacks without app frames use their top
+/// frames instead.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StackSignature {
+    /// The exception class of a crash, e.g. `java.lang.NullPointerException`.
+    exception: Option<String>,
+    frames: Vec<String>,
+    hash: u64,
+}
+
+impl StackSignature {
+    /// `frames` are lines of a stack, top first, as printed by a thread dump or a crash.
+    pub fn new<'f>(exception: Option<&str>, frames: impl IntoIterator<Item = &'f str>, depth: usize) -> Self {
+        let normalized: Vec<(String, bool)> = frames.into_iter().filter_map(normalize).collect();
+        let mut frames: Vec<String> = normalized.first().map(|(frame, _)| frame.clone()).into_iter().collect();
+        let rest = normalized.iter().skip(1);
+        let app_frames: Vec<&String> = rest
+            .clone()
+            .filter(|(frame, java)| *java && is_app_frame(frame))
+            .map(|(frame, _)| frame)
+            .collect();
+        if app_frames.is_empty() {
+            frames.extend(rest.take(depth).map(|(frame, _)| frame.clone()));
+        } else {
+            frames.extend(app_frames.into_iter().take(depth).cloned());
+        }
+
+        let exception = exception.map(|exception| {
+            // The message changes from one crash to the next, the class does not
+            exception.split(':').next().unwrap_or_default().trim().to_string()
+        });
+        let mut text = exception.clone().unwrap_or_default();
+        for frame in &frames {
+            text.push('\n');
+            text.push_str(frame);
+        }
+        Self {
+            exception,
+            frames,
+            hash: xxh3_64(text.as_bytes()),
+        }
+    }
+
+    pub fn from_frames(frames: &[StackFrame], depth: usize) -> Self {
+        let lines: Vec<String> = frames.iter().map(StackFrame::to_string).collect();
+        Self::new(None, lines.iter().map(String::as_str), depth)
+    }
+
+    pub fn get_hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// The hash as 12 hex digits, short enough for a bug title.
+    pub fn get_id(&self) -> String {
+        format!("{:012x}", self.hash >> 16)
+    }
+
+    /// The exception and frames the hash was taken over, e.g.
+    /// `java.lang.NullPointerException at Foo.bar < Foo.lambda$baz`.
+    pub fn get_key(&self) -> String {
+        let frames = self.frames.join(" < ");
+        match &self.exception {
+            Some(exception) if frames.is_empty() => exception.clone(),
+            Some(exception) => format!("{} at {}", exception, frames),
+            None => frames,
+        }
+    }
+
+    pub fn get_frames(&self) -> &[String] {
+        &self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exception.is_none() && self.frames.is_empty()
+    }
+}
+
+impl Display for StackSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.get_id(), self.get_key())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_frame() {
+        let cases = [
+            ("at com.foo.Bar.baz(Bar.java:12)", Some("com.foo.Bar.baz")),
+            ("\tat com.foo.Bar.baz(Unknown Source:4)", Some("com.foo.Bar.baz")),
+            ("at android.os.BinderProxy.transactNative(Native method)", Some("android.os.BinderProxy.transactNative")),
+            ("at com.foo.Bar$$ExternalSyntheticLambda3.run(D8$$SyntheticClass:0)", Some("com.foo.Bar$$Lambda.run")),
+            ("at com.foo.-$$Lambda$Bar$AbCd12.run(lambda)", Some("com.foo.$$Lambda.run")),
+            ("at com.foo.Bar$$Lambda/0x0000000801234.run(Unknown Source:6)", Some("com.foo.Bar$$Lambda.run")),
+            ("at com.foo.Bar.lambda$onCreate$3(Bar.java:40)", Some("com.foo.Bar.lambda$onCreate")),
+            ("at com.foo.Bar$1.run(Bar.java:50)", Some("com.foo.Bar$.run")),
+            ("at com.foo.Bar$Inner.run(Bar.java:50)", Some("com.foo.Bar$Inner.run")),
+            (
+                "native: #00 pc 000000000009c9e4  /apex/com.android.runtime/lib64/bionic/libc.so (__ioctl+4) (BuildId: 1a2b3c)",
+                Some("libc.so __ioctl"),
+            ),
+            (
+                "#01 pc 0000000000012345  /vendor/lib64/libpowerhal.so (android::PowerHal::setBoost(int)+64)",
+                Some("libpowerhal.so android::PowerHal::setBoost(int)"),
+            ),
+            ("native: #01 pc 0000000000056a4c  /system/lib64/libbinder.so (offset 0x1000) (BuildId: 4d5e6f)", Some("libbinder.so")),
+            ("- locked <0x0c2a1b3f> (a com.android.server.am.ActivityManagerService)", None),
+            ("... 5 more", None),
+        ];
+        for (frame, expected) in cases {
+            assert_eq!(normalize_frame(frame).as_deref(), expected, "{}", frame);
+        }
+    }
+
+    #[test]
+    fn test_signature() {
+        let release_1 = [
+            "at android.os.MessageQueue.nativePollOnce(Native method)",
+            "at com.example.app.a.b(SourceFile:3)",
+            "at com.example.app.Loader.lambda$load$2(Loader.java:88)",
+            "at com.example.app.Loader$$ExternalSyntheticLambda1.run(D8$$SyntheticClass:0)",
+            "at android.os.Handler.dispatchMessage(Handler.java:106)",
+            "at com.example.app.MainActivity.onCreate(MainActivity.java:20)",
+        ];
+        // The next release moved every line and renamed the obfuscated class
+        let release_2 = [
+            "at android.os.MessageQueue.nativePollOnce(Native method)",
+            "at com.example.app.c.d(SourceFile:9)",
+            "at com.example.app.Loader.lambda$load$5(Loader.java:93)",
+            "at com.example.app.Loader$$ExternalSyntheticLambda4.run(D8$$SyntheticClass:0)",
+            "at android.os.Handler.dispatchMessage(Handler.java:110)",
+            "at com.example.app.MainActivity.onCreate(MainActivity.java:22)",
+        ];
+        let first = StackSignature::new(None, release_1, DEFAULT_DEPTH);
+        let second = StackSignature::new(None, release_2, DEFAULT_DEPTH);
+        assert_eq!(first, second);
+        assert_eq!(first.get_id().len(), 12);
+        assert_eq!(
+            first.get_key(),
+            "android.os.MessageQueue.nativePollOnce < com.example.app.Loader.lambda$load < com.example.app.Loader$$Lambda.run < com.example.app.MainActivity.onCreate"
+        );
+        assert_ne!(first, StackSignature::new(None, release_1, 1));
+
+        // Without app frames the top frames are used
+        let idle = StackSignature::new(None, [release_1[0], release_1[4]], DEFAULT_DEPTH);
+        assert_eq!(idle.get_frames().len(), 2);
+
+        let crash = StackSignature::new(Some("java.lang.IllegalStateException: id 42 not found"), release_1, 2);
+        assert!(crash.to_string().contains(" java.lang.IllegalStateException at android.os.MessageQueue"));
+        assert_eq!(crash, StackSignature::new(Some("java.lang.IllegalStateException: id 7 not found"), release_2, 2));
+    }
+}
@@ -0,0 +1,215 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::{self, Display, Formatter};
+
+use chrono::{DateTime, FixedOffset};
+
+use super::logcat::window::TimeWindow;
+use super::logcat::{LogcatLine, LogcatSection};
+
+/// What happened at one point of the timeline.
+#[derive(Debug, Clone, Copy)]
+pub enum TimelineEvent<'a> {
+    Log(&'a LogcatLine),
+}
+
+impl TimelineEvent<'_> {
+    pub fn timestamp(&self) -> DateTime<FixedOffset> {
+        match self {
+            TimelineEvent::Log(line) => line.timestamp,
+        }
+    }
+}
+
+impl Display for TimelineEvent<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineEvent::Log(line) => line.fmt(f),
+        }
+    }
+}
+
+/// An event together with the source it came from, e.g. `SYSTEM LOG`.
+#[derive(Debug, Clone, Copy)]
+pub struct TimelineEntry<'a> {
+    pub source: &'a str,
+    pub event: TimelineEvent<'a>,
+}
+
+impl TimelineEntry<'_> {
+    pub fn timestamp(&self) -> DateTime<FixedOffset> {
+        self.event.timestamp()
+    }
+}
+
+impl Display for TimelineEntry<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.source, self.event)
+    }
+}
+
+/// Several time-ordered sources read as one stream. Each source is sorted once when it is
+/// added; iterating merges them lazily, so a window near the start costs little.
+#[derive(Debug, Default)]
+pub struct Timeline<'a> {
+    sources: Vec<Vec<TimelineEntry<'a>>>,
+}
+
+impl<'a> Timeline<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the events of one source. Events with the same timestamp keep their order, and
+    /// sources added earlier come first on ties.
+    pub fn add(&mut self, source: &'a str, events: impl IntoIterator<Item = TimelineEvent<'a>>) {
+        let mut entries: Vec<TimelineEntry<'a>> = events
+            .into_iter()
+            .map(|event| TimelineEntry { source, event })
+            .collect();
+        // logcat -d already merges its buffers by time; only reordered sources pay for a sort
+        if !entries.is_sorted_by_key(TimelineEntry::timestamp) {
+            entries.sort_by_key(TimelineEntry::timestamp);
+        }
+        if !entries.is_empty() {
+            self.sources.push(entries);
+        }
+    }
+
+    pub fn add_logcat(&mut self, source: &'a str, section: &'a LogcatSection) {
+        self.add(source, section.lines().iter().map(TimelineEvent::Log));
+    }
+
+    pub fn len(&self) -> usize {
+        self.sources.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Every entry in timestamp order.
+    pub fn iter(&self) -> TimelineIter<'_, 'a> {
+        let heads = self
+            .sources
+            .iter()
+            .enumerate()
+            .map(|(source, entries)| Reverse((entries[0].timestamp(), source, 0)))
+            .collect();
+        TimelineIter {
+            sources: &self.sources,
+            heads,
+        }
+    }
+
+    /// The entries inside `window`, in timestamp order.
+    pub fn window(&self, window: &TimeWindow) -> Vec<TimelineEntry<'a>> {
+        self.iter()
+            .skip_while(|entry| entry.timestamp() < window.start)
+            .take_while(|entry| entry.timestamp() <= window.end)
+            .collect()
+    }
+}
+
+/// K-way merge over the sources of a [`Timeline`]; see [`Timeline::iter`].
+pub struct TimelineIter<'t, 'a> {
+    sources: &'t [Vec<TimelineEntry<'a>>],
+    /// The next entry of every source that has one left: `(timestamp, source, position)`.
+    heads: BinaryHeap<Reverse<(DateTime<FixedOffset>, usize, usize)>>,
+}
+
+impl<'a> Iterator for TimelineIter<'_, 'a> {
+    type Item = TimelineEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse((_, source, position)) = self.heads.pop()?;
+        let entries = &self.sources[source];
+        if let Some(next) = entries.get(position + 1) {
+            self.heads.push(Reverse((next.timestamp(), source, position + 1)));
+        }
+        Some(entries[position])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::bugreport_txt::BugreportTxt;
+    use crate::bugreport::clock::{ReportClock, TimeDisplay};
+    use chrono::NaiveDate;
+
+    const REPORT: &[u8] = b"== dumpstate: 2024-08-16 10:02:11
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+08-16 10:00:59.000  1000  1500  1520 W BinderProxy: Binder transaction too early
+08-16 10:01:10.000  1000  1500  1520 W BinderProxy: Binder call took 2100ms
+08-16 10:01:20.000  1000  1500  1521 D BinderProxy: Binder call took 12ms
+08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: Binder died in systemui
+08-16 10:01:40.000  1000  1500  1500 E ActivityManager: ANR in com.android.systemui, Binder stuck
+------ 0.052s was the duration of 'SYSTEM LOG' ------
+------ EVENT LOG (logcat -b events -v threadtime -v printable -v uid -d *:v) ------
+08-16 10:01:15.000  1000  1500  1530 I am_anr: [0,5140,com.android.systemui,952745541,Binder timeout]
+------ 0.010s was the duration of 'EVENT LOG' ------
+";
+
+    fn section(lines: &[&str]) -> LogcatSection {
+        let reference = NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_opt(10, 2, 11)
+            .unwrap();
+        let mut section = LogcatSection::new(Vec::new());
+        section.parse(lines, &ReportClock::new(None, TimeDisplay::Device), reference);
+        section
+    }
+
+    #[test]
+    fn test_merge() {
+        let system = section(&[
+            "08-16 10:01:10.000  1000  1500  1520 W BinderProxy: Binder call took 2100ms",
+            "08-16 10:01:15.000  1000  1500  1500 I ActivityManager: ANR in com.android.systemui",
+            "08-16 10:01:30.000 10160  5140  5140 E AndroidRuntime: FATAL EXCEPTION: main",
+        ]);
+        let events = section(&[
+            "08-16 10:01:15.000  1000  1500  1530 I am_anr: [0,5140,com.android.systemui,952745541,Input dispatching timed out]",
+            "08-16 10:01:05.000  1000  1500  1530 I am_proc_start: [0,5140,10160,com.android.systemui]",
+        ]);
+        let radio = section(&[]);
+        let mut timeline = Timeline::new();
+        timeline.add_logcat("SYSTEM LOG", &system);
+        timeline.add_logcat("EVENT LOG", &events);
+        timeline.add_logcat("RADIO LOG", &radio);
+        assert_eq!(timeline.len(), 5);
+
+        let merged: Vec<(&str, &str)> = timeline
+            .iter()
+            .map(|entry| match entry.event {
+                TimelineEvent::Log(line) => (entry.source, line.tag.as_str()),
+            })
+            .collect();
+        assert_eq!(
+            merged,
+            vec![
+                ("EVENT LOG", "am_proc_start"),
+                ("SYSTEM LOG", "BinderProxy"),
+                ("SYSTEM LOG", "ActivityManager"),
+                ("EVENT LOG", "am_anr"),
+                ("SYSTEM LOG", "AndroidRuntime"),
+            ]
+        );
+
+        let anr = system.get_line(1).unwrap().timestamp;
+        assert_eq!(timeline.window(&TimeWindow::new(anr, anr)).len(), 2);
+        assert!(format!("{}", timeline.iter().next().unwrap()).starts_with("[EVENT LOG] 2024-08-16 10:01:05.000"));
+    }
+
+    #[test]
+    fn test_report_timeline() {
+        let mut bugreport = BugreportTxt::from_bytes(REPORT.to_vec());
+        bugreport.load().unwrap();
+        let timeline = bugreport.timeline().unwrap();
+        let sources: Vec<&str> = timeline.iter().map(|entry| entry.source).collect();
+        assert_eq!(
+            sources,
+            vec!["SYSTEM LOG", "SYSTEM LOG", "EVENT LOG", "SYSTEM LOG", "SYSTEM LOG", "SYSTEM LOG"]
+        );
+    }
+}
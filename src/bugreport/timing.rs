@@ -0,0 +1,266 @@
+use std::cmp::Reverse;
+use std::fmt::{self, Display, Formatter};
+
+use chrono::Duration;
+
+use super::bugreport_txt::BugreportTxt;
+use super::section::DUMPSYS_SECTIONS;
+use crate::error::Result;
+
+/// Whether an entry is a whole dumpstate section or one service inside a DUMPSYS section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingKind {
+    Section,
+    Service,
+}
+
+/// How long dumpstate spent on one section or dumpsys service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimingEntry {
+    pub kind: TimingKind,
+    pub name: String,
+    /// For a service, the DUMPSYS section it was dumped in; for a section, its own name.
+    pub section: String,
+    /// `None` when the closing marker did not say.
+    pub duration: Option<Duration>,
+    /// The command or service hit its timeout, so its output is cut short.
+    pub timed_out: bool,
+    /// Size of the output that made it into the report.
+    pub bytes: usize,
+}
+
+/// Every section and dumpsys service of a report with its duration, to spot the slow and the
+/// timed-out ones. A dumpsys that is slow usually means the service itself is blocked.
+#[derive(Debug)]
+pub struct TimingReport {
+    entries: Vec<TimingEntry>,
+    slow_threshold: Duration,
+    /// Size of the whole report.
+    total_bytes: usize,
+}
+
+impl TimingReport {
+    /// Sections and services that take at least this long are reported as slow.
+    pub fn default_slow_threshold() -> Duration {
+        Duration::seconds(1)
+    }
+
+    /// Collects the timings of `bugreport`, parsing its DUMPSYS sections if that has not
+    /// happened yet.
+    pub fn build(bugreport: &BugreportTxt, slow_threshold: Duration) -> Result<Self> {
+        let raw: &[u8] = &bugreport.raw_file;
+        let mut entries = Vec::new();
+        for section in bugreport.get_sections() {
+            entries.push(TimingEntry {
+                kind: TimingKind::Section,
+                name: section.name.clone(),
+                section: section.name.clone(),
+                duration: section.get_duration(),
+                timed_out: section.is_timed_out(raw),
+                bytes: section.get_byte_range().len(),
+            });
+            // Other sections are only measured, not parsed
+            if !DUMPSYS_SECTIONS.contains(&section.name.as_str()) {
+                continue;
+            }
+            if let Some(dumpsys) = bugreport.get_content(section)?.as_dumpsys() {
+                for service in dumpsys.get_entries() {
+                    entries.push(TimingEntry {
+                        kind: TimingKind::Service,
+                        name: service.name.clone(),
+                        section: section.name.clone(),
+                        duration: service.duration,
+                        timed_out: service.timed_out,
+                        bytes: service.data.len(),
+                    });
+                }
+            }
+        }
+        Ok(Self {
+            entries,
+            slow_threshold,
+            total_bytes: raw.len(),
+        })
+    }
+
+    /// Sections and services in report order, each service right after its section.
+    pub fn get_entries(&self) -> &[TimingEntry] {
+        &self.entries
+    }
+
+    pub fn get_slow_threshold(&self) -> Duration {
+        self.slow_threshold
+    }
+
+    /// Entries that took at least the slow threshold, slowest first.
+    pub fn slow(&self) -> Vec<&TimingEntry> {
+        let mut slow: Vec<&TimingEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.duration.is_some_and(|d| d >= self.slow_threshold))
+            .collect();
+        slow.sort_by_key(|entry| Reverse(entry.duration));
+        slow
+    }
+
+    pub fn timed_out(&self) -> impl Iterator<Item = &TimingEntry> {
+        self.entries.iter().filter(|entry| entry.timed_out)
+    }
+
+    /// The time dumpstate spent on all sections. Services are part of their section and are
+    /// not counted again.
+    pub fn total_duration(&self) -> Duration {
+        self.sections().filter_map(|entry| entry.duration).sum()
+    }
+
+    /// Time spent on sections that timed out, i.e. waited for output that never came.
+    pub fn timed_out_duration(&self) -> Duration {
+        self.sections()
+            .filter(|entry| entry.timed_out)
+            .filter_map(|entry| entry.duration)
+            .sum()
+    }
+
+    /// Bytes of the report that belong to cut-short output. A timed-out service inside a
+    /// section that did not time out only counts its own bytes.
+    pub fn incomplete_bytes(&self) -> usize {
+        let sections: usize = self
+            .sections()
+            .filter(|entry| entry.timed_out)
+            .map(|entry| entry.bytes)
+            .sum();
+        let services: usize = self
+            .entries
+            .iter()
+            .filter(|entry| entry.kind == TimingKind::Service && entry.timed_out)
+            .filter(|service| !self.section_timed_out(&service.section))
+            .map(|entry| entry.bytes)
+            .sum();
+        sections + services
+    }
+
+    /// Share of the report, by size, whose output is incomplete because of a timeout.
+    pub fn incomplete_ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.incomplete_bytes() as f64 / self.total_bytes as f64
+    }
+
+    fn sections(&self) -> impl Iterator<Item = &TimingEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.kind == TimingKind::Section)
+    }
+
+    fn section_timed_out(&self, name: &str) -> bool {
+        self.sections()
+            .any(|entry| entry.name == name && entry.timed_out)
+    }
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(d) => format!("{:.3}s", d.num_milliseconds() as f64 / 1000.0),
+        None => "-".to_string(),
+    }
+}
+
+impl Display for TimingEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self.kind {
+            TimingKind::Section => self.name.clone(),
+            TimingKind::Service => format!("  dumpsys {}", self.name),
+        };
+        write!(f, "{:>9}  {}", format_duration(self.duration), name)?;
+        if self.timed_out {
+            write!(f, "  [TIMEOUT]")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for TimingReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} sections, {} dumpsys services, {} in total",
+            self.sections().count(),
+            self.entries.len() - self.sections().count(),
+            format_duration(Some(self.total_duration())),
+        )?;
+        writeln!(f, "Slow (>= {}):", format_duration(Some(self.slow_threshold)))?;
+        for entry in self.slow() {
+            writeln!(f, "{}", entry)?;
+        }
+        writeln!(f, "Timed out:")?;
+        for entry in self.timed_out() {
+            writeln!(f, "{}", entry)?;
+        }
+        writeln!(
+            f,
+            "Incomplete: {} of {} bytes ({:.1}%), {} spent waiting",
+            self.incomplete_bytes(),
+            self.total_bytes,
+            self.incomplete_ratio() * 100.0,
+            format_duration(Some(self.timed_out_duration())),
+        )?;
+        writeln!(f, "All:")?;
+        for entry in &self.entries {
+            writeln!(f, "{}", entry)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REPORT: &[u8] = b"========================================================
+== dumpstate: 2024-08-16 10:02:11
+========================================================
+------ KERNEL LOG (dmesg) ------
+<6>[    0.000000] Booting Linux on physical CPU 0x0
+------ 0.120s was the duration of 'KERNEL LOG' ------
+------ LAST LOGCAT (logcat -L -b all -v threadtime -d) ------
+*** command 'logcat -L -b all -v threadtime -d' timed out after 10.002s (killing pid 3030)
+------ 10.002s was the duration of 'LAST LOGCAT' ------
+------ DUMPSYS (/system/bin/dumpsys) ------
+-------------------------------------------------------------------------------
+DUMP OF SERVICE activity:
+*** SERVICE 'activity' DUMP TIMEOUT (10000ms) EXPIRED ***
+--------- 10.004s was the duration of dumpsys activity, ending at: 2024-08-16 10:02:40
+-------------------------------------------------------------------------------
+DUMP OF SERVICE alarm:
+  Current Alarm Manager state:
+--------- 0.013s was the duration of dumpsys alarm, ending at: 2024-08-16 10:02:40
+------ 10.030s was the duration of 'DUMPSYS' ------
+";
+
+    #[test]
+    fn test_timing_report() {
+        let mut bugreport = BugreportTxt::from_bytes(REPORT.to_vec());
+        bugreport.load().unwrap();
+        let report = TimingReport::build(&bugreport, TimingReport::default_slow_threshold()).unwrap();
+
+        let entries = report.get_entries();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0].duration, Some(Duration::milliseconds(120)));
+        assert!(!entries[0].timed_out);
+        assert_eq!(entries[3].kind, TimingKind::Service);
+        assert_eq!(entries[3].section, "DUMPSYS");
+
+        let slow: Vec<&str> = report.slow().iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(slow, vec!["DUMPSYS", "activity", "LAST LOGCAT"]);
+        let timed_out: Vec<&str> = report.timed_out().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(timed_out, vec!["LAST LOGCAT", "activity"]);
+
+        assert_eq!(report.total_duration(), Duration::milliseconds(20152));
+        assert_eq!(report.timed_out_duration(), Duration::milliseconds(10002));
+        let expected = entries[1].bytes + entries[3].bytes;
+        assert_eq!(report.incomplete_bytes(), expected);
+        assert!(report.incomplete_ratio() > 0.0 && report.incomplete_ratio() < 1.0);
+        assert!(report.to_string().contains("dumpsys activity  [TIMEOUT]"));
+    }
+}
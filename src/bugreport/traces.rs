@@ -0,0 +1,609 @@
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDateTime};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::decode::{self, DecodeMode};
+use crate::error::Result;
+
+lazy_static! {
+    /// `"main" prio=5 tid=1 Blocked`, `"Signal Catcher" daemon prio=10 tid=6 Runnable`,
+    /// `"binder:1500_2" prio=10 (not attached)` or, for native processes, `"vold" sysTid=612`.
+    static ref THREAD_HEADER: Regex = Regex::new(
+        r#"^"(?P<name>.*)"(?P<daemon> daemon)?(?: prio=(?P<prio>\d+))?(?: tid=(?P<tid>\d+) (?P<state>.+)| sysTid=(?P<sys_tid>\d+)| \(not attached\))$"#
+    )
+    .unwrap();
+    /// `native: #00 pc 000000000004e2dc  /apex/.../libc.so (__ioctl+12) (BuildId: ...)`.
+    static ref NATIVE_FRAME: Regex =
+        Regex::new(r"^(?:native: )?#(?P<index>\d+) pc (?P<pc>[0-9a-f]+)\s+(?P<library>\S+)(?P<rest>.*)$").unwrap();
+}
+
+/// What a thread was doing, from the word after `tid=` in its header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadState {
+    Runnable,
+    /// Running native code, e.g. waiting for a binder reply.
+    Native,
+    /// Waiting to enter a monitor another thread holds.
+    Blocked,
+    /// `Object.wait()` or parked without a timeout.
+    Waiting,
+    TimedWaiting,
+    Sleeping,
+    Suspended,
+    /// A native thread, or one not attached to the runtime; no state is printed.
+    Unknown,
+    Other(String),
+}
+
+impl ThreadState {
+    fn from_name(name: &str) -> Self {
+        match name {
+            "Runnable" => ThreadState::Runnable,
+            "Native" => ThreadState::Native,
+            "Blocked" | "Monitor" => ThreadState::Blocked,
+            "Waiting" | "Wait" => ThreadState::Waiting,
+            "TimedWaiting" | "TimedWait" => ThreadState::TimedWaiting,
+            "Sleeping" => ThreadState::Sleeping,
+            "Suspended" => ThreadState::Suspended,
+            other => ThreadState::Other(other.to_string()),
+        }
+    }
+}
+
+/// How a frame refers to a lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    /// `- locked <0x...>`: the thread holds the monitor.
+    Locked,
+    /// `- waiting to lock <0x...>`: blocked entering a monitor.
+    WaitingToLock,
+    /// `- waiting on <0x...>`: in `Object.wait()`, the monitor is released.
+    WaitingOn,
+    /// `- parking to wait for <0x...>`: a `java.util.concurrent` lock or condition.
+    ParkingToWaitFor,
+    /// `- sleeping on <0x...>`: in `Thread.sleep()`.
+    SleepingOn,
+}
+
+/// A lock mentioned by a frame, e.g. `- waiting to lock <0x0c2a1b3f> (a java.lang.Object)
+/// held by thread 42`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockRef {
+    pub kind: LockKind,
+    /// The object address without the brackets, e.g. `0x0c2a1b3f`.
+    pub address: String,
+    /// The class of the lock object, e.g. `java.lang.Object`.
+    pub class_name: Option<String>,
+    /// The `tid=` (not `sysTid`) of the owner, when the runtime printed it.
+    pub held_by: Option<u32>,
+}
+
+impl LockRef {
+    fn parse(text: &str) -> Option<Self> {
+        const KINDS: [(&str, LockKind); 5] = [
+            ("locked ", LockKind::Locked),
+            ("waiting to lock ", LockKind::WaitingToLock),
+            ("waiting on ", LockKind::WaitingOn),
+            ("parking to wait for ", LockKind::ParkingToWaitFor),
+            ("sleeping on ", LockKind::SleepingOn),
+        ];
+        let (kind, rest) = KINDS
+            .iter()
+            .find_map(|(prefix, kind)| text.strip_prefix(prefix).map(|rest| (*kind, rest.trim_start())))?;
+        let rest = rest.strip_prefix('<')?;
+        let (address, rest) = rest.split_once('>')?;
+        let class_name = rest
+            .trim_start()
+            .strip_prefix("(a ")
+            .and_then(|rest| rest.split_once(')'))
+            .map(|(class_name, _)| class_name.to_string());
+        let held_by = ["held by thread ", "held by tid="].iter().find_map(|marker| {
+            let (_, owner) = rest.split_once(marker)?;
+            let digits = owner.find(|c: char| !c.is_ascii_digit()).unwrap_or(owner.len());
+            owner[..digits].parse().ok()
+        });
+        Some(LockRef {
+            kind,
+            address: address.to_string(),
+            class_name,
+            held_by,
+        })
+    }
+}
+
+/// One line of a thread's stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StackFrame {
+    /// `at com.android.server.Foo.bar(Foo.java:12)`.
+    Java { method: String, location: String },
+    /// `native: #00 pc 000000000004e2dc  /apex/.../libc.so (__ioctl+12)`.
+    Native {
+        index: u32,
+        pc: String,
+        library: String,
+        symbol: Option<String>,
+    },
+    /// A `- locked <...>` style line, which belongs to the Java frame above it.
+    Lock(LockRef),
+}
+
+impl StackFrame {
+    fn parse(line: &str) -> Option<Self> {
+        if let Some(frame) = line.strip_prefix("at ") {
+            let (method, location) = match frame.split_once('(') {
+                Some((method, location)) => (method, location.strip_suffix(')').unwrap_or(location)),
+                None => (frame, ""),
+            };
+            return Some(StackFrame::Java {
+                method: method.to_string(),
+                location: location.to_string(),
+            });
+        }
+        if let Some(lock) = line.strip_prefix("- ") {
+            return LockRef::parse(lock).map(StackFrame::Lock);
+        }
+        let caps = NATIVE_FRAME.captures(line)?;
+        // `(symbol+12)`, optionally followed by `(BuildId: ...)`; `(offset 0x1000)` is no symbol
+        let rest = caps["rest"].trim();
+        let rest = rest.find(" (BuildId: ").map_or(rest, |end| &rest[..end]);
+        let symbol = rest
+            .strip_prefix('(')
+            .and_then(|rest| rest.strip_suffix(')'))
+            .filter(|symbol| !symbol.starts_with("BuildId: ") && !symbol.starts_with("offset "))
+            .map(str::to_string);
+        Some(StackFrame::Native {
+            index: caps["index"].parse().ok()?,
+            pc: caps["pc"].to_string(),
+            library: caps["library"].to_string(),
+            symbol,
+        })
+    }
+
+    /// The lock this line refers to, if it is a lock line.
+    pub fn as_lock(&self) -> Option<&LockRef> {
+        match self {
+            StackFrame::Lock(lock) => Some(lock),
+            _ => None,
+        }
+    }
+}
+
+impl Display for StackFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StackFrame::Java { method, location } => write!(f, "at {}({})", method, location),
+            StackFrame::Native {
+                index,
+                pc,
+                library,
+                symbol,
+            } => {
+                write!(f, "#{:02} pc {}  {}", index, pc, library)?;
+                match symbol {
+                    Some(symbol) => write!(f, " ({})", symbol),
+                    None => Ok(()),
+                }
+            }
+            StackFrame::Lock(lock) => {
+                let action = match lock.kind {
+                    LockKind::Locked => "locked",
+                    LockKind::WaitingToLock => "waiting to lock",
+                    LockKind::WaitingOn => "waiting on",
+                    LockKind::ParkingToWaitFor => "parking to wait for",
+                    LockKind::SleepingOn => "sleeping on",
+                };
+                write!(f, "- {} <{}>", action, lock.address)?;
+                if let Some(class_name) = &lock.class_name {
+                    write!(f, " (a {})", class_name)?;
+                }
+                match lock.held_by {
+                    Some(tid) => write!(f, " held by thread {}", tid),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// One thread of a process dump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadDump {
+    pub name: String,
+    pub daemon: bool,
+    pub priority: Option<u32>,
+    /// The runtime's thread id, `tid=` in the header; `None` for native threads.
+    pub tid: Option<u32>,
+    /// The kernel thread id, as `ps -T` and logcat print it.
+    pub sys_tid: Option<u32>,
+    pub state: ThreadState,
+    pub nice: Option<i32>,
+    /// The `flags=` of the `| group=` line; 1 means a suspend request is pending.
+    pub flags: Option<u32>,
+    /// The `cgrp=` of the `| sysTid=` line, e.g. `foreground`.
+    pub cgroup: Option<String>,
+    /// What follows `| held mutexes=`, e.g. `"mutator lock"(shared held)`.
+    pub held_mutexes: Option<String>,
+    pub frames: Vec<StackFrame>,
+    /// The thread's lines as the dump printed them.
+    lines: Vec<String>,
+}
+
+impl ThreadDump {
+    fn from_header(line: &str) -> Option<Self> {
+        let caps = THREAD_HEADER.captures(line)?;
+        let number = |name: &str| caps.name(name).and_then(|m| m.as_str().parse().ok());
+        let state = match caps.name("state") {
+            Some(state) => ThreadState::from_name(state.as_str().trim()),
+            None => ThreadState::Unknown,
+        };
+        Some(ThreadDump {
+            name: caps["name"].to_string(),
+            daemon: caps.name("daemon").is_some(),
+            priority: number("prio"),
+            tid: number("tid"),
+            sys_tid: number("sys_tid"),
+            state,
+            nice: None,
+            flags: None,
+            cgroup: None,
+            held_mutexes: None,
+            frames: Vec::new(),
+            lines: vec![line.to_string()],
+        })
+    }
+
+    /// Reads one line of the thread's body: a `| key=value` line or a frame.
+    fn add_line(&mut self, line: &str) {
+        self.lines.push(line.to_string());
+        let line = line.trim();
+        if let Some(properties) = line.strip_prefix("| ") {
+            if let Some(held) = properties.strip_prefix("held mutexes=") {
+                self.held_mutexes = Some(held.trim().to_string()).filter(|held| !held.is_empty());
+                return;
+            }
+            for (key, value) in properties.split_whitespace().filter_map(|pair| pair.split_once('=')) {
+                match key {
+                    "sysTid" => self.sys_tid = value.parse().ok().or(self.sys_tid),
+                    "nice" => self.nice = value.parse().ok(),
+                    "flags" => self.flags = value.parse().ok(),
+                    "cgrp" => self.cgroup = Some(value.to_string()),
+                    _ => {}
+                }
+            }
+        } else if let Some(frame) = StackFrame::parse(line) {
+            self.frames.push(frame);
+        }
+    }
+
+    /// The thread's lines as the dump printed them, header first.
+    pub fn lines(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn is_main(&self) -> bool {
+        self.name == "main" || self.tid == Some(1)
+    }
+
+    /// Every lock line of the stack, top frame first.
+    pub fn locks(&self) -> impl Iterator<Item = &LockRef> {
+        self.frames.iter().filter_map(StackFrame::as_lock)
+    }
+
+    /// The monitor or `java.util.concurrent` lock the thread is blocked on.
+    pub fn waiting_lock(&self) -> Option<&LockRef> {
+        self.locks()
+            .find(|lock| matches!(lock.kind, LockKind::WaitingToLock | LockKind::ParkingToWaitFor))
+    }
+
+    /// Whether the thread holds the monitor at `address`.
+    pub fn holds(&self, address: &str) -> bool {
+        self.locks()
+            .any(|lock| lock.kind == LockKind::Locked && lock.address == address)
+    }
+
+    /// The Java methods of the stack, e.g. `android.os.BinderProxy.transactNative`.
+    pub fn java_methods(&self) -> impl Iterator<Item = &str> {
+        self.frames.iter().filter_map(|frame| match frame {
+            StackFrame::Java { method, .. } => Some(method.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// The threads of one process, between `----- pid 1500 at ... -----` and `----- end 1500 -----`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessDump {
+    pub pid: u32,
+    /// When the dump was taken, in the device's wall-clock time.
+    pub time: Option<NaiveDateTime>,
+    /// The `Cmd line:`, i.e. the process name.
+    pub cmd_line: Option<String>,
+    pub threads: Vec<ThreadDump>,
+    /// The `----- pid ... -----` line.
+    header: String,
+}
+
+impl ProcessDump {
+    /// `----- pid 1500 at 2024-08-16 10:01:30.123456789+0800 -----`.
+    fn from_header(line: &str) -> Option<Self> {
+        let rest = line.strip_prefix("----- pid ")?.strip_suffix(" -----")?;
+        let (pid, time) = match rest.split_once(" at ") {
+            Some((pid, time)) => (pid, Some(time)),
+            None => (rest, None),
+        };
+        let time = time.and_then(|time| {
+            DateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f%z")
+                .map(|time| time.naive_local())
+                .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S%.f"))
+                .ok()
+        });
+        Some(ProcessDump {
+            pid: pid.trim().parse().ok()?,
+            time,
+            cmd_line: None,
+            threads: Vec::new(),
+            header: line.to_string(),
+        })
+    }
+
+    /// The `----- pid ... -----` line.
+    pub fn get_header(&self) -> &str {
+        &self.header
+    }
+
+    pub fn main_thread(&self) -> Option<&ThreadDump> {
+        self.threads
+            .iter()
+            .find(|thread| thread.is_main())
+            .or_else(|| self.threads.iter().find(|thread| thread.sys_tid == Some(self.pid)))
+    }
+
+    /// The thread with runtime id `tid`, as named by `held by thread`.
+    pub fn get_thread(&self, tid: u32) -> Option<&ThreadDump> {
+        self.threads.iter().find(|thread| thread.tid == Some(tid))
+    }
+
+    pub fn get_thread_by_sys_tid(&self, sys_tid: u32) -> Option<&ThreadDump> {
+        self.threads.iter().find(|thread| thread.sys_tid == Some(sys_tid))
+    }
+
+    /// The thread that holds `lock`: the one its `held by` names, or else the one with a
+    /// matching `- locked` line.
+    pub fn lock_holder(&self, lock: &LockRef) -> Option<&ThreadDump> {
+        lock.held_by
+            .and_then(|tid| self.get_thread(tid))
+            .or_else(|| self.threads.iter().find(|thread| thread.holds(&lock.address)))
+    }
+}
+
+/// An ANR traces file, or the VM TRACES sections of a bugreport: one dump per process.
+#[derive(Debug, Default)]
+pub struct AnrTraces {
+    processes: Vec<ProcessDump>,
+}
+
+impl AnrTraces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(&mut self, lines: &[&str]) {
+        let mut process: Option<ProcessDump> = None;
+        let mut thread: Option<ThreadDump> = None;
+        for line in lines {
+            if let Some(next) = ProcessDump::from_header(line) {
+                self.finish(&mut process, &mut thread);
+                process = Some(next);
+                continue;
+            }
+            let Some(current) = process.as_mut() else {
+                continue;
+            };
+            if line.starts_with("----- end ") {
+                self.finish(&mut process, &mut thread);
+            } else if let Some(next) = ThreadDump::from_header(line) {
+                current.threads.extend(thread.replace(next));
+            } else if let Some(cmd_line) = line.strip_prefix("Cmd line: ") {
+                current.cmd_line = Some(cmd_line.trim().to_string());
+            } else if line.trim().is_empty() {
+                current.threads.extend(thread.take());
+            } else if let Some(thread) = thread.as_mut() {
+                thread.add_line(line);
+            }
+        }
+        self.finish(&mut process, &mut thread);
+    }
+
+    fn finish(&mut self, process: &mut Option<ProcessDump>, thread: &mut Option<ThreadDump>) {
+        if let Some(mut process) = process.take() {
+            process.threads.extend(thread.take());
+            self.processes.push(process);
+        }
+    }
+
+    /// Reads a traces file such as `/data/anr/anr_2024-08-16-10-01-30-123`. Invalid UTF-8 is
+    /// replaced.
+    pub fn load(path: &Path) -> Result<Self> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let (lines, _) = decode::decode_lines(bytes, DecodeMode::Replace, 1)?;
+        let lines: Vec<&str> = lines.iter().map(|line| line.as_ref()).collect();
+        let mut traces = Self::new();
+        traces.parse(&lines);
+        Ok(traces)
+    }
+
+    pub fn processes(&self) -> &[ProcessDump] {
+        &self.processes
+    }
+
+    pub fn len(&self) -> usize {
+        self.processes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.processes.is_empty()
+    }
+
+    /// Every dump of process `pid`; a file may hold several taken at different times.
+    pub fn get_process(&self, pid: u32) -> impl Iterator<Item = &ProcessDump> {
+        self.processes.iter().filter(move |process| process.pid == pid)
+    }
+
+    pub fn find_by_cmd_line<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a ProcessDump> {
+        self.processes
+            .iter()
+            .filter(move |process| process.cmd_line.as_deref() == Some(name))
+    }
+
+    /// Every thread of every process, with the process it belongs to.
+    pub fn threads(&self) -> impl Iterator<Item = (&ProcessDump, &ThreadDump)> {
+        self.processes
+            .iter()
+            .flat_map(|process| process.threads.iter().map(move |thread| (process, thread)))
+    }
+}
+
+#[cfg(test)]
+pub(crate) const TEST_TRACES: &str = r#"
+----- pid 1500 at 2024-08-16 10:01:30.123456789+0800 -----
+Cmd line: system_server
+Build fingerprint: 'Xiaomi/haotian/haotian:15/AQ3A.240812.002/OS2.0.107.0.VOBCNXM:userdebug/test-keys'
+ABI: 'arm64'
+
+DALVIK THREADS (3):
+"main" prio=5 tid=1 Blocked
+  | group="main" sCount=1 ucsCount=0 flags=1 obj=0x72a3e4f8 self=0xb400007a2c8c2c00
+  | sysTid=1500 nice=-2 cgrp=foreground sched=0/0 handle=0x7b4a8944f8
+  | state=S schedstat=( 12345 6789 1234 ) utm=123 stm=45 core=3 HZ=100
+  | held mutexes=
+  at com.android.server.am.ActivityManagerService.broadcastIntent(ActivityManagerService.java:14010)
+  - waiting to lock <0x0c2a1b3f> (a com.android.server.am.ActivityManagerService) held by thread 42
+  at android.app.ContextImpl.sendBroadcast(ContextImpl.java:1180)
+  at com.android.server.SystemServer.run(SystemServer.java:1000)
+
+"Binder:1500_3" prio=5 tid=42 Native
+  | group="main" sCount=1 ucsCount=0 flags=1 obj=0x13c80158 self=0xb400007a2c8d4800
+  | sysTid=1532 nice=0 cgrp=foreground sched=0/0 handle=0x7a1e4ffcb0
+  | held mutexes=
+  native: #00 pc 000000000009c9e4  /apex/com.android.runtime/lib64/bionic/libc.so (__ioctl+4) (BuildId: 1a2b3c)
+  native: #01 pc 0000000000056a4c  /system/lib64/libbinder.so (offset 0x1000) (BuildId: 4d5e6f)
+  at android.os.BinderProxy.transactNative(Native method)
+  at android.os.BinderProxy.transact(BinderProxy.java:584)
+  at android.hardware.power.IPower$Stub$Proxy.setBoost(IPower.java:510)
+  at com.android.server.am.ActivityManagerService.updateOomAdj(ActivityManagerService.java:15510)
+  - locked <0x0c2a1b3f> (a com.android.server.am.ActivityManagerService)
+  at android.os.Binder.execTransact(Binder.java:1339)
+
+"binder:1500_2" prio=10 (not attached)
+  | sysTid=1531 nice=-20 cgrp=foreground
+  native: #00 pc 000000000009c9e4  /apex/com.android.runtime/lib64/bionic/libc.so (__ioctl+4)
+
+----- end 1500 -----
+
+----- pid 800 at 2024-08-16 10:01:31.000000000+0800 -----
+Cmd line: /vendor/bin/hw/android.hardware.power-service
+ABI: 'arm64'
+
+"android.hardwar" sysTid=800
+    #00 pc 00000000000a6f88  /apex/com.android.runtime/lib64/bionic/libc.so (__epoll_pwait+8)
+    #01 pc 0000000000012345  /vendor/lib64/libpowerhal.so (android::PowerHal::setBoost(int)+64)
+
+----- end 800 -----
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn load() -> AnrTraces {
+        AnrTraces::from_bytes(TEST_TRACES.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_processes() {
+        let traces = load();
+        assert_eq!(traces.len(), 2);
+        let system = traces.get_process(1500).next().unwrap();
+        assert_eq!(system.cmd_line.as_deref(), Some("system_server"));
+        let time = NaiveDate::from_ymd_opt(2024, 8, 16)
+            .unwrap()
+            .and_hms_nano_opt(10, 1, 30, 123456789)
+            .unwrap();
+        assert_eq!(system.time, Some(time));
+        assert_eq!(system.threads.len(), 3);
+        let hal = traces.find_by_cmd_line("/vendor/bin/hw/android.hardware.power-service").next().unwrap();
+        assert_eq!(hal.main_thread().unwrap().name, "android.hardwar");
+        assert_eq!(traces.threads().count(), 4);
+    }
+
+    #[test]
+    fn test_threads() {
+        let traces = load();
+        let system = traces.get_process(1500).next().unwrap();
+        let main = system.main_thread().unwrap();
+        assert_eq!((main.tid, main.sys_tid, main.priority), (Some(1), Some(1500), Some(5)));
+        assert_eq!((main.state.clone(), main.nice, main.flags), (ThreadState::Blocked, Some(-2), Some(1)));
+        assert_eq!(main.cgroup.as_deref(), Some("foreground"));
+        assert_eq!(main.held_mutexes, None);
+        assert_eq!(main.frames.len(), 4);
+        assert_eq!(main.lines().len(), 9);
+
+        let lock = main.waiting_lock().unwrap();
+        assert_eq!(lock.kind, LockKind::WaitingToLock);
+        assert_eq!(lock.address, "0x0c2a1b3f");
+        assert_eq!(lock.class_name.as_deref(), Some("com.android.server.am.ActivityManagerService"));
+        assert_eq!(lock.held_by, Some(42));
+        let holder = system.lock_holder(lock).unwrap();
+        assert_eq!(holder.name, "Binder:1500_3");
+        assert!(holder.holds("0x0c2a1b3f"));
+        assert_eq!(holder.state, ThreadState::Native);
+        assert_eq!(system.get_thread_by_sys_tid(1532), Some(holder));
+
+        let unattached = &system.threads[2];
+        assert_eq!((unattached.tid, unattached.sys_tid), (None, Some(1531)));
+        assert_eq!(unattached.state, ThreadState::Unknown);
+    }
+
+    #[test]
+    fn test_frames() {
+        let traces = load();
+        let system = traces.get_process(1500).next().unwrap();
+        let binder = system.get_thread(42).unwrap();
+        assert_eq!(
+            binder.frames[0],
+            StackFrame::Native {
+                index: 0,
+                pc: "000000000009c9e4".to_string(),
+                library: "/apex/com.android.runtime/lib64/bionic/libc.so".to_string(),
+                symbol: Some("__ioctl+4".to_string()),
+            }
+        );
+        assert!(matches!(&binder.frames[1], StackFrame::Native { symbol: None, .. }));
+        assert_eq!(
+            binder.frames[2],
+            StackFrame::Java {
+                method: "android.os.BinderProxy.transactNative".to_string(),
+                location: "Native method".to_string(),
+            }
+        );
+        assert_eq!(binder.frames[2].to_string(), "at android.os.BinderProxy.transactNative(Native method)");
+        assert_eq!(binder.java_methods().count(), 5);
+
+        let hal = traces.get_process(800).next().unwrap();
+        let frame = &hal.threads[0].frames[1];
+        assert!(matches!(frame, StackFrame::Native { symbol: Some(symbol), .. } if symbol == "android::PowerHal::setBoost(int)+64"));
+
+        let parked = LockRef::parse("parking to wait for  <0x0b3f4d5e> (a java.util.concurrent.locks.ReentrantLock$NonfairSync)").unwrap();
+        assert_eq!((parked.kind, parked.address.as_str(), parked.held_by), (LockKind::ParkingToWaitFor, "0x0b3f4d5e", None));
+        let old = LockRef::parse("waiting to lock <0x1> (a java.lang.Object) held by tid=17 (Binder:1500_1)").unwrap();
+        assert_eq!(old.held_by, Some(17));
+        assert!(LockRef::parse("waiting to lock an unknown object").is_none());
+    }
+}
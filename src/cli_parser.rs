@@ -18,6 +18,18 @@ pub struct Cli {
     /// 是否启用 REPL 模式
     #[arg(short, long, action, default_value = "false")]
     pub repl: bool,
+
+    /// 不读取也不写入解析缓存
+    #[arg(long, action, default_value = "false")]
+    pub no_cache: bool,
+
+    /// 解析缓存所在目录（默认与 bugreport 同目录）
+    #[arg(long, value_parser)]
+    pub cache_dir: Option<String>,
+
+    /// 以 UTC 显示时间（默认使用设备时区）
+    #[arg(long, action, default_value = "false")]
+    pub utc: bool,
 }
 
 /// 支持的操作模式
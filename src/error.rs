@@ -0,0 +1,141 @@
+use std::fmt::{self, Display, Formatter};
+use std::io;
+
+use zip::result::ZipError;
+
+/// Errors raised anywhere in the parsing pipeline, from opening the archive down to a single
+/// logcat line. Variants that can be pinned to a place in the report carry the line number and
+/// the name of the section they were found in, when known.
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The dumpstate zip could not be read or lacks an expected entry.
+    Archive(ZipError),
+    /// The header ended before a required field was found.
+    TruncatedHeader { line: usize, missing: &'static str },
+    /// A section the caller relies on is absent from the report.
+    MissingSection(String),
+    BadTimestamp {
+        value: String,
+        line: Option<usize>,
+        section: Option<String>,
+    },
+    /// The bytes at the given line are not valid UTF-8.
+    BadEncoding {
+        line: Option<usize>,
+        section: Option<String>,
+    },
+    /// A line does not have the expected layout, or one of its fields is out of range.
+    Malformed {
+        what: &'static str,
+        line: Option<usize>,
+        section: Option<String>,
+    },
+    /// A plugin failed while analyzing the report.
+    Plugin { name: String, source: Box<Error> },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn bad_timestamp(value: &str) -> Self {
+        Error::BadTimestamp {
+            value: value.to_string(),
+            line: None,
+            section: None,
+        }
+    }
+
+    pub fn malformed(what: &'static str) -> Self {
+        Error::Malformed {
+            what,
+            line: None,
+            section: None,
+        }
+    }
+
+    /// Records the 1-based line number the error was found at, unless one is already known.
+    pub fn at_line(mut self, line_number: usize) -> Self {
+        match &mut self {
+            Error::BadTimestamp { line, .. }
+            | Error::BadEncoding { line, .. }
+            | Error::Malformed { line, .. } => {
+                line.get_or_insert(line_number);
+            }
+            _ => {}
+        }
+        self
+    }
+
+    /// Records the section the error was found in, unless one is already known.
+    pub fn in_section(mut self, name: &str) -> Self {
+        match &mut self {
+            Error::BadTimestamp { section, .. }
+            | Error::BadEncoding { section, .. }
+            | Error::Malformed { section, .. } => {
+                section.get_or_insert_with(|| name.to_string());
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
+fn write_location(f: &mut Formatter<'_>, line: &Option<usize>, section: &Option<String>) -> fmt::Result {
+    if let Some(line) = line {
+        write!(f, " at line {}", line)?;
+    }
+    if let Some(section) = section {
+        write!(f, " in section '{}'", section)?;
+    }
+    Ok(())
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Archive(e) => write!(f, "Archive error: {}", e),
+            Error::TruncatedHeader { line, missing } => {
+                write!(f, "Header truncated at line {}: missing {}", line, missing)
+            }
+            Error::MissingSection(name) => write!(f, "Section '{}' not found", name),
+            Error::BadTimestamp { value, line, section } => {
+                write!(f, "Invalid timestamp '{}'", value)?;
+                write_location(f, line, section)
+            }
+            Error::BadEncoding { line, section } => {
+                write!(f, "Invalid UTF-8")?;
+                write_location(f, line, section)
+            }
+            Error::Malformed { what, line, section } => {
+                write!(f, "Malformed {}", what)?;
+                write_location(f, line, section)
+            }
+            Error::Plugin { name, source } => write!(f, "Plugin {} failed: {}", name, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Archive(e) => Some(e),
+            Error::Plugin { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(e: ZipError) -> Self {
+        Error::Archive(e)
+    }
+}
@@ -1,4 +1,3 @@
-use bugreport::bugreport_txt::BugreportTxt;
 use clap::Parser;
 use plugin::{timestamp_plugin, PluginRepo};
 use std::{path::Path, sync::{Arc, Mutex}};
@@ -7,6 +6,7 @@ use cli_parser::{Cli, Mode};
 
 pub mod bugreport;
 pub mod cli_parser;
+pub mod error;
 pub mod models;
 pub mod plugin;
 pub mod repl;
@@ -30,9 +30,24 @@ fn main() {
         return;
     }
 
+    let cache_path = if args.no_cache {
+        None
+    } else {
+        Some(bugreport::cache::cache_path_for(
+            file_path,
+            args.cache_dir.as_deref().map(Path::new),
+        ))
+    };
+
+    let time_display = if args.utc {
+        bugreport::clock::TimeDisplay::Utc
+    } else {
+        bugreport::clock::TimeDisplay::Device
+    };
+
     if args.repl {
         println!("Welcome to the Rust REPL!");
-        repl::repl(file_path);
+        repl::repl(file_path, cache_path.as_deref(), time_display);
         return;
     }
 
@@ -54,13 +69,31 @@ fn main() {
             }
         }
         Mode::Bugreport => {
-            let mut bugreport = BugreportTxt::new(file_path).unwrap();
-            let _ = bugreport.load();
+            let bugreport = match bugreport::bugreport::open(file_path, cache_path.as_deref(), time_display) {
+                Ok(bugreport) => bugreport,
+                Err(e) => {
+                    eprintln!("Error: Failed to load '{}': {}", file_path.display(), e);
+                    return;
+                }
+            };
+            if bugreport.get_metadata().clock.is_guessed() {
+                eprintln!("Warning: The report does not give the device timezone, times are read as UTC.");
+            }
             let plugin = plugin::input_focus_plugin::InputFocusPlugin::new();
             let timestamp_plugin = timestamp_plugin::TimestampPlugin::new();
             PluginRepo::register(Arc::new(Mutex::new(plugin)));
             PluginRepo::register(Arc::new(Mutex::new(timestamp_plugin)));
-            PluginRepo::analyze_all(&bugreport);
+            let timing_plugin = plugin::section_timing_plugin::SectionTimingPlugin::new();
+            PluginRepo::register(Arc::new(Mutex::new(timing_plugin)));
+            let lock_plugin = plugin::lock_plugin::LockPlugin::new();
+            PluginRepo::register(Arc::new(Mutex::new(lock_plugin)));
+            let binder_plugin = plugin::binder_chain_plugin::BinderChainPlugin::new();
+            PluginRepo::register(Arc::new(Mutex::new(binder_plugin)));
+            let crash_plugin = plugin::crash_plugin::CrashPlugin::new();
+            PluginRepo::register(Arc::new(Mutex::new(crash_plugin)));
+            if let Err(e) = PluginRepo::analyze_all(&bugreport) {
+                eprintln!("Error: {}", e);
+            }
             println!("Plugin report:");
             println!("{}", PluginRepo::report_all());
         }
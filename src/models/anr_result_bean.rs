@@ -1,24 +1,28 @@
-use regex::Regex;
 use std::fs::File;
 use std::io::{self, BufWriter, Write};
 
-// 定义 TraceAndFile 结构体
+use crate::bugreport::signature::{StackSignature, DEFAULT_DEPTH};
+
+// 定义 TraceAndFile 结构体：同一签名的 trace 只保留第一次出现的原文
 #[derive(Debug)]
 struct TraceAndFile {
     traces: Vec<String>,
+    signature: StackSignature,
     log_file_paths: Vec<String>,
 }
 
 impl TraceAndFile {
-    fn new() -> Self {
+    fn new(traces: &[String]) -> Self {
         TraceAndFile {
-            traces: Vec::new(),
+            traces: traces.to_vec(),
+            signature: signature_of(traces),
             log_file_paths: Vec::new(),
         }
     }
 
-    fn set_traces(&mut self, traces: Vec<String>) {
-        self.traces = traces;
+    fn set_traces(&mut self, traces: &[String]) {
+        self.traces = traces.to_vec();
+        self.signature = signature_of(traces);
     }
 
     fn add_log_file_path(&mut self, path: String) {
@@ -32,6 +36,7 @@ impl TraceAndFile {
             "<<<<<<<<<<<<<<<<<<<<<<<<<<<{} times>>>>>>>>>>>>>>>>>>>>>>>>>>>",
             self.log_file_paths.len()
         )?;
+        writeln!(writer, "signature: {}", self.signature)?;
 
         if self.traces.is_empty() {
             writeln!(writer, "  no trace")?;
@@ -49,6 +54,11 @@ impl TraceAndFile {
     }
 }
 
+// trace 列表的签名
+fn signature_of(traces: &[String]) -> StackSignature {
+    StackSignature::new(None, traces.iter().map(String::as_str), DEFAULT_DEPTH)
+}
+
 // 定义 ANRResultBean 结构体
 #[derive(Debug)]
 pub struct ANRResultBean {
@@ -73,68 +83,27 @@ impl ANRResultBean {
     }
 
     pub fn add_traces(&mut self, traces: &Vec<String>) -> usize {
-        let mut trace_and_file = TraceAndFile::new();
-        let re = Regex::new(r"\d").unwrap();
-
-        for trace in traces {
-            let masked_trace = re.replace_all(&trace, "X").to_string();
-            trace_and_file.traces.push(masked_trace);
-        }
-
-        self.trace_file_list.push(trace_and_file);
+        self.trace_file_list.push(TraceAndFile::new(traces));
         self.trace_file_list.len() - 1
     }
 
     fn set_traces(&mut self, traces: &Vec<String>, index: usize) {
-        if index >= self.trace_file_list.len() {
-            return;
-        }
-
-        let re = Regex::new(r"\d").unwrap();
-        self.trace_file_list[index].traces.clear();
-
-        for trace in traces {
-            let masked_trace = re.replace_all(&trace, "X").to_string();
-            self.trace_file_list[index].traces.push(masked_trace);
+        if let Some(trace_and_file) = self.trace_file_list.get_mut(index) {
+            trace_and_file.set_traces(traces);
         }
     }
 
+    // 按签名比较：行号、lambda 编号和混淆名不同的同一堆栈归为一组
     pub fn compare_trace(&self, traces: &mut Vec<String>) -> Option<usize> {
-        if self.trace_file_list.is_empty() {
-            return None;
-        }
-
-        let re = Regex::new(r"\d").unwrap();
-        let masked_traces: Vec<String> = traces
+        let signature = signature_of(traces);
+        self.trace_file_list
             .iter()
-            .map(|trace| re.replace_all(trace, "X").to_string())
-            .collect();
-
-        for (i, trace_and_file) in self.trace_file_list.iter().enumerate() {
-            if Self::is_same_list(&masked_traces, &trace_and_file.traces) {
-                return Some(i);
-            }
-        }
-
-        None
+            .position(|trace_and_file| trace_and_file.signature == signature)
     }
 
-    fn is_same_list(list1: &Vec<String>, list2: &Vec<String>) -> bool {
-        if list1.is_empty() && list2.is_empty() {
-            return true;
-        }
-
-        if list1.len() != list2.len() {
-            return false;
-        }
-
-        for (a, b) in list1.iter().zip(list2.iter()) {
-            if a != b {
-                return false;
-            }
-        }
-
-        true
+    // 按签名分出的组数
+    pub fn get_group_count(&self) -> usize {
+        self.trace_file_list.len()
     }
 
     pub fn add_log_file_path(&mut self, path: String, index: usize) {
@@ -191,4 +160,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_compare_trace() {
+        let mut anr_result = ANRResultBean::new();
+        let first = vec![
+            "at android.os.BinderProxy.transactNative(Native method)".to_string(),
+            "at com.example.app.Loader.lambda$load$2(Loader.java:88)".to_string(),
+        ];
+        let index = anr_result.add_traces(&first);
+
+        // 另一个版本中行号和 lambda 编号变了，仍归为同一组
+        let mut moved = vec![
+            "at android.os.BinderProxy.transactNative(Native method)".to_string(),
+            "at com.example.app.Loader.lambda$load$5(Loader.java:93)".to_string(),
+        ];
+        assert_eq!(anr_result.compare_trace(&mut moved), Some(index));
+
+        let mut other = vec!["at android.os.MessageQueue.nativePollOnce(Native method)".to_string()];
+        assert_eq!(anr_result.compare_trace(&mut other), None);
+    }
 }
@@ -1,6 +1,8 @@
 use chrono::{NaiveDateTime, Utc};
 use std::fmt;
 
+use crate::bugreport::process::ProcessTable;
+
 // 定义 LogItemBean 结构体
 #[derive(Debug, Clone, Default)]
 pub struct LogItemBean {
@@ -103,6 +105,18 @@ impl LogItemBean {
         self.content = Some(content);
     }
 
+    // 通过进程表把 pid 解析为进程名
+    pub fn resolve_process_name<'t>(&self, processes: &'t ProcessTable) -> Option<&'t str> {
+        let pid = self.pid.as_deref()?.trim().parse().ok()?;
+        processes.process_name(pid)
+    }
+
+    // 通过进程表把 tid 解析为线程名
+    pub fn resolve_thread_name<'t>(&self, processes: &'t ProcessTable) -> Option<&'t str> {
+        let tid = self.tid.as_deref()?.trim().parse().ok()?;
+        processes.thread_name(tid)
+    }
+
     // 比较两个 LogItemBean 是否相等
     pub fn equals(&self, other: &LogItemBean, max_time_diff: i64) -> bool {
         if other.time.is_none() {
@@ -165,13 +179,10 @@ impl fmt::Display for LogItemBean {
 
 #[cfg(test)]
 mod tests {
+    use crate::bugreport::traces::AnrTraces;
     use crate::models::lock_bean::LockBean;
     use crate::trace_analysis::TraceAnalysis;
-    use std::{
-        fs::File,
-        io::{BufReader, BufWriter},
-        path::Path,
-    };
+    use std::{fs::File, io::BufWriter, path::Path};
 
     #[test]
     fn main() {
@@ -185,22 +196,20 @@ mod tests {
         if let Ok(file) = File::create("path/to/output_file") {
             let mut writer = BufWriter::new(file);
 
-            // 创建 BufReader 实例
-            if let Ok(file) = File::open(src_file) {
-                let mut reader = BufReader::new(file);
-
+            // 解析 trace 文件
+            if let Ok(traces) = AnrTraces::load(src_file) {
                 // 创建 TracesAnalyse 实例
                 let mut analyser = TraceAnalysis::new();
 
-                // 调用 analyse_trace_by_lock 方法
-                if let Err(e) = analyser.analyse_trace_by_lock(
-                    &mut lock_object,
-                    &mut Vec::new(),
-                    &mut writer,
-                    src_file,
-                    &mut reader,
-                ) {
-                    eprintln!("Error: {}", e);
+                // 对每个进程的主线程调用 analyse_trace_by_lock 方法
+                for process in traces.processes() {
+                    if let Some(main) = process.main_thread() {
+                        if let Err(e) =
+                            analyser.analyse_trace_by_lock(&mut lock_object, process, main, &mut writer)
+                        {
+                            eprintln!("Error: {}", e);
+                        }
+                    }
                 }
             }
         }
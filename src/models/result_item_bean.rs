@@ -1,6 +1,9 @@
+use crate::bugreport::process::ProcessTable;
+
 #[derive(Debug, Default)]
 pub struct ResultItemBean {
     process_name: String,
+    pid: Option<u32>,
     trace_list: Vec<String>,
     out_path: String,
 }
@@ -10,6 +13,7 @@ impl ResultItemBean {
     pub fn new() -> Self {
         ResultItemBean {
             process_name: String::new(),
+            pid: None,
             trace_list: Vec::new(),
             out_path: String::new(),
         }
@@ -18,6 +22,7 @@ impl ResultItemBean {
     pub fn with_details(process_name: String, trace_list: Vec<String>, out_path: String) -> Self {
         ResultItemBean {
             process_name,
+            pid: None,
             trace_list,
             out_path,
         }
@@ -28,6 +33,21 @@ impl ResultItemBean {
         self.process_name = name;
     }
 
+    // 设置 pid
+    pub fn set_pid(&mut self, pid: u32) {
+        self.pid = Some(pid);
+    }
+
+    // 获取 pid
+    pub fn get_pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    // 通过进程表把 pid 解析为进程名
+    pub fn resolve_process_name<'t>(&self, processes: &'t ProcessTable) -> Option<&'t str> {
+        processes.process_name(self.pid?)
+    }
+
     // 添加 trace 到 trace_list
     pub fn set_trace_list(&mut self, line: String) {
         self.trace_list.push(line);
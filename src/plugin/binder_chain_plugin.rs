@@ -0,0 +1,53 @@
+use crate::bugreport::binder::{BinderEndpoint, CallChain, ChainHop};
+use crate::bugreport::bugreport_txt::BugreportTxt;
+use crate::error::Result;
+
+use super::{titled_report, Plugin};
+
+/// Reports the cross-process binder call chain of every main thread in the report's dump.
+#[derive(Default)]
+pub struct BinderChainPlugin {
+    report: Option<String>,
+}
+
+impl Plugin for BinderChainPlugin {
+    fn name(&self) -> &str {
+        "BinderChainPlugin"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()> {
+        let (Some(binder), Some(traces)) = (bugreport.get_binder_transactions()?, bugreport.get_anr_traces()?) else {
+            self.report = None;
+            return Ok(());
+        };
+        let mut report = String::new();
+        for process in traces.processes() {
+            let start = BinderEndpoint {
+                pid: process.pid,
+                tid: process.pid,
+            };
+            // 只关心经过 binder 调用的链，纯锁等待由 LockPlugin 报告
+            let Some(chain) = CallChain::trace(binder, traces, start) else { continue };
+            if chain.hops.iter().any(|hop| matches!(hop, ChainHop::Binder { .. })) {
+                let name = process.cmd_line.as_deref().unwrap_or("?");
+                report.push_str(&format!("{} (pid {}) main thread:\n{}", name, process.pid, chain));
+            }
+        }
+        self.report = Some(report);
+        Ok(())
+    }
+
+    fn report(&self) -> String {
+        titled_report("Binder call chains", &self.report)
+    }
+}
+
+impl BinderChainPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
@@ -0,0 +1,40 @@
+use crate::bugreport::bugreport_txt::BugreportTxt;
+use crate::bugreport::crash::{CrashGroup, JavaCrash};
+use crate::bugreport::logcat::LogBuffer;
+use crate::bugreport::signature::DEFAULT_DEPTH;
+use crate::error::Result;
+
+use super::{titled_report, Plugin};
+
+/// Groups the Java crashes of the crash buffer by stack signature.
+#[derive(Default)]
+pub struct CrashPlugin {
+    report: Option<String>,
+}
+
+impl Plugin for CrashPlugin {
+    fn name(&self) -> &str {
+        "CrashPlugin"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()> {
+        let crashes = JavaCrash::parse(&bugreport.search_by_buffer(LogBuffer::Crash)?);
+        let groups = CrashGroup::group(&crashes, DEFAULT_DEPTH);
+        self.report = Some(groups.iter().map(CrashGroup::to_string).collect());
+        Ok(())
+    }
+
+    fn report(&self) -> String {
+        titled_report("Crashes", &self.report)
+    }
+}
+
+impl CrashPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
@@ -1,25 +1,40 @@
 use std::sync::{Arc, Mutex};
 
-use lazy_static::lazy_static;
-use regex::Regex;
-
-use crate::bugreport::{bugreport_txt::BugreportTxt, logcat::LogcatLine, section::Section};
+use crate::bugreport::bugreport_txt::BugreportTxt;
+use crate::bugreport::logcat::{event::EventRecord, LogcatLine};
+use crate::error::{Error, Result};
 
 use super::{Plugin, PluginRepo};
 
-lazy_static! {
-    pub static ref INPUT_FOCUS_REQUEST: Regex =
-        Regex::new(r#"\[Focus request ([\w /\.]+),reason=(\w+)\]"#).unwrap();
-    pub static ref INPUT_FOCUS_RECEIVE: Regex =
-        Regex::new(r#"\[Focus receive :([\w /\.]+),.*\]"#).unwrap();
-    pub static ref INPUT_FOCUS_ENTERING: Regex =
-        Regex::new(r#"\[Focus entering ([\w /\.]+) (\(server\))?,.*\]"#).unwrap();
-    pub static ref INPUT_FOCUS_LEAVING: Regex =
-        Regex::new(r#"\[Focus leaving ([\w /\.]+) (\(server\))?,.*\]"#).unwrap();
+/// What an `input_focus` event says happened to the focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusAction {
+    Request,
+    Receive,
+    Entering,
+    Leaving,
+}
+
+/// Splits the `window` field of an `input_focus` event, e.g.
+/// `Focus request 3e5f1ae com.android.settings/.Settings`, into the action and the window.
+pub fn focus_action(event: &EventRecord) -> Option<(FocusAction, &str)> {
+    let window = event.get_str("window")?;
+    let (action, window) = [
+        ("Focus request ", FocusAction::Request),
+        ("Focus receive :", FocusAction::Receive),
+        ("Focus entering ", FocusAction::Entering),
+        ("Focus leaving ", FocusAction::Leaving),
+    ]
+    .into_iter()
+    .find_map(|(prefix, action)| window.strip_prefix(prefix).map(|window| (action, window)))?;
+    let window = window.strip_suffix(" (server)").unwrap_or(window);
+    Some((action, window.trim()))
 }
 
 #[derive(Debug)]
 pub struct InputFocusTuple {
+    /// The window focus was requested for.
+    pub window: String,
     pub request: Option<LogcatLine>,
     pub receive: Option<LogcatLine>,
     pub entering: Option<LogcatLine>,
@@ -37,16 +52,16 @@ impl Plugin for InputFocusPlugin {
     }
 
     fn version(&self) -> &str {
-        todo!()
+        "1.0.0"
     }
 
-    fn analyze(&mut self, bugreport: &BugreportTxt) {
-        let event_log_section = match bugreport.sections.iter().find(|s| s.name == "EVENT LOG") {
-            Some(section) => section,
-            None => panic!("EVENT LOG section not found"),
-        };
-
-        self.pair_input_focus(event_log_section);
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()> {
+        if !bugreport.sections.iter().any(|s| s.name == "EVENT LOG") {
+            return Err(Error::MissingSection("EVENT LOG".to_string()));
+        }
+        let events = bugreport.search_events("input_focus")?;
+        self.pair_input_focus(&events);
+        Ok(())
     }
 
     fn report(&self) -> String {
@@ -67,72 +82,40 @@ impl InputFocusPlugin {
     /// 1. 第一步通过 dump of service greezer 找到用户开关屏幕的时间点，也可以考虑通过 screen_toggled 0
     /// 2. 第二步根据上述开关屏时间点找当时的 input_focus 记录，看看每一个时间点的 focus 到底在哪里
     /// 3. 第三步看 wm 生命周期，看能不能跟 focus 记录对上
-    pub fn pair_input_focus(&mut self, section: &Section) {
-        let result = match section.search_by_tag("input_focus") {
-            Some(logs) => logs,
-            None => return,
-        };
+    pub fn pair_input_focus(&mut self, events: &[EventRecord]) {
+        let actions: Vec<Option<(FocusAction, &str)>> = events.iter().map(focus_action).collect();
 
-        // find all the entries with its message containing "Focus request"
-        let mut request_focus = Vec::new();
-        for (i, line) in result.iter().enumerate() {
-            if line.message.contains("Focus request") {
-                request_focus.push((i, line));
-            }
-        }
-
-        // find entries with its message containing "Focus receive", "Focus entering", "Focus leaving" that appear after the request_focus
-        // and the timestamp is the closest to the request_focus
-        // compact the result into a vec of InputFocusPair
-        for (i, request) in request_focus {
-            let mut window = String::new();
-            if let Some(captures) = INPUT_FOCUS_REQUEST.captures(&request.message) {
-                window = captures.get(1).map_or("", |m| m.as_str()).to_string();
-            }
-            // Bug fix: The `push_str` method of the `String` type takes only one argument, which is the string to be appended.
-            // The original code tried to use a format string, which is incorrect. We need to use the `format!` macro to format the string first,
-            // and then append it to `self.result`.
+        // For every request, find the receive, entering and leaving events for the same window
+        // that follow it
+        for (i, action) in actions.iter().enumerate() {
+            let Some((FocusAction::Request, window)) = *action else {
+                continue;
+            };
             self.result.push_str(&format!("window: {}\n", window));
 
             let mut receive = None;
             let mut entering = None;
             let mut leaving = None;
-            for line in result.iter().skip(i + 1) {
-                if let Some(captures) = INPUT_FOCUS_RECEIVE.captures(&line.message) {
-                    if receive.is_none() && captures.get(1).map_or("", |m| m.as_str()) == window {
-                        receive = Some(line);
-                    }
-                } else if let Some(captures) = INPUT_FOCUS_ENTERING.captures(&line.message) {
-                    if entering.is_none() && captures.get(1).map_or("", |m| m.as_str()) == window {
-                        entering = Some(line);
-                    }
-                } else if let Some(captures) = INPUT_FOCUS_LEAVING.captures(&line.message) {
-                    if leaving.is_none() && captures.get(1).map_or("", |m| m.as_str()) == window {
-                        leaving = Some(line);
-                    }
-                }
+            for (event, action) in events.iter().zip(&actions).skip(i + 1) {
+                let slot = match *action {
+                    Some((FocusAction::Receive, w)) if w == window => &mut receive,
+                    Some((FocusAction::Entering, w)) if w == window => &mut entering,
+                    Some((FocusAction::Leaving, w)) if w == window => &mut leaving,
+                    _ => continue,
+                };
+                slot.get_or_insert_with(|| event.line.clone());
                 if receive.is_some() && entering.is_some() && leaving.is_some() {
                     break;
                 }
             }
             self.records.push(InputFocusTuple {
-                request: Some(request.clone()),
-                receive: match receive {
-                    Some(line) => Some(line.clone()),
-                    None => None,
-                },
-                entering: match entering {
-                    Some(line) => Some(line.clone()),
-                    None => None,
-                },
-                leaving: match leaving {
-                    Some(line) => Some(line.clone()),
-                    None => None,
-                },
+                window: window.to_string(),
+                request: Some(events[i].line.clone()),
+                receive,
+                entering,
+                leaving,
             });
         }
-
-        println!("rsrasreraerasera: {:?}", self.records.len());
     }
 
     fn get_records(&self) -> &Vec<InputFocusTuple> {
@@ -153,16 +136,11 @@ mod tests {
             Err(e) => panic!("Error: {}", e),
         };
         let mut plugin = InputFocusPlugin::new();
-        plugin.analyze(&bugreport);
+        plugin.analyze(&bugreport).unwrap();
         let result = plugin.get_records();
         for pair in result {
             println!("{:?}", pair);
-            let request_activity = INPUT_FOCUS_REQUEST
-                .captures(&pair.request.as_ref().unwrap().message)
-                .unwrap()
-                .get(1)
-                .unwrap()
-                .as_str();
+            let request_activity = pair.window.as_str();
             // check if the four fields have increasing timestamp and the same activity
             // avoid formatting the following lines
 
@@ -191,4 +169,44 @@ mod tests {
             assert!(pair.leaving.as_ref().unwrap().message.contains(request_activity));
         }
     }
+
+    #[test]
+    fn test_pair_events() {
+        let mut bugreport = BugreportTxt::from_bytes(
+            b"== dumpstate: 2024-08-16 10:02:11
+------ EVENT LOG (logcat -b events -v threadtime -v printable -v uid -d *:v) ------
+--------- beginning of events
+08-16 10:01:30.003  1000  1500  1600 I input_focus: [Focus request 3e5f1ae com.android.settings/com.android.settings.Settings,reason=UpdateInputWindows]
+08-16 10:01:30.013  1000  1500  1600 I input_focus: [Focus receive :3e5f1ae com.android.settings/com.android.settings.Settings,reason=setFocusedWindow]
+08-16 10:01:30.023  1000  1500  1600 I input_focus: [Focus leaving 9a1b2c3 com.android.launcher3/.Launcher (server),reason=NO_WINDOW]
+08-16 10:01:30.033  1000  1500  1600 I input_focus: [Focus entering 3e5f1ae com.android.settings/com.android.settings.Settings (server),reason=setFocusedWindow]
+------ 0.010s was the duration of 'EVENT LOG' ------
+"
+            .to_vec(),
+        );
+        bugreport.load().unwrap();
+        let mut plugin = InputFocusPlugin::new();
+        plugin.analyze(&bugreport).unwrap();
+        let records = plugin.get_records();
+        assert_eq!(records.len(), 1);
+        let pair = &records[0];
+        assert_eq!(pair.window, "3e5f1ae com.android.settings/com.android.settings.Settings");
+        assert!(pair.receive.as_ref().unwrap().message.contains("Focus receive"));
+        assert!(pair.entering.as_ref().unwrap().message.contains("Focus entering"));
+        assert!(pair.leaving.is_none());
+    }
+
+    #[test]
+    fn test_missing_event_log() {
+        let mut bugreport = BugreportTxt::from_bytes(
+            b"== dumpstate: 2024-08-16 10:02:11\nUptime: up 0 weeks, 0 days, 0 hours, 32 minutes\n"
+                .to_vec(),
+        );
+        bugreport.load().unwrap();
+        let mut plugin = InputFocusPlugin::new();
+        assert!(matches!(
+            plugin.analyze(&bugreport),
+            Err(Error::MissingSection(name)) if name == "EVENT LOG"
+        ));
+    }
 }
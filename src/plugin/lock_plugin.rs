@@ -0,0 +1,38 @@
+use crate::bugreport::bugreport_txt::BugreportTxt;
+use crate::bugreport::locks::{LockReport, LONG_CHAIN};
+use crate::error::Result;
+
+use super::{titled_report, Plugin};
+
+/// Reports the deadlocks and long lock chains of the report's thread dump.
+#[derive(Default)]
+pub struct LockPlugin {
+    report: Option<String>,
+}
+
+impl Plugin for LockPlugin {
+    fn name(&self) -> &str {
+        "LockPlugin"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()> {
+        self.report = bugreport
+            .get_anr_traces()?
+            .map(|traces| LockReport::build(traces, LONG_CHAIN).to_string());
+        Ok(())
+    }
+
+    fn report(&self) -> String {
+        titled_report("Locks", &self.report)
+    }
+}
+
+impl LockPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
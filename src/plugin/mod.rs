@@ -6,18 +6,32 @@ use std::{
 };
 
 use crate::bugreport::{bugreport_txt::BugreportTxt, logcat::LogcatLine};
+use crate::error::{Error, Result};
 
+pub mod binder_chain_plugin;
+pub mod crash_plugin;
 pub mod input_focus_plugin;
+pub mod lock_plugin;
+pub mod section_timing_plugin;
 pub mod timestamp_plugin;
 
 // Define the plugin trait
 pub trait Plugin: Send + Sync {
     fn name(&self) -> &str;
     fn version(&self) -> &str;
-    fn analyze(&mut self, bugreport: &BugreportTxt);
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()>;
     fn report(&self) -> String;
 }
 
+// 插件的分析结果往往借用了 bugreport 里解析出的数据，不能跨 analyze 保存，
+// 所以只保存渲染好的文本，report 时再加上标题
+pub(crate) fn titled_report(title: &str, report: &Option<String>) -> String {
+    match report {
+        Some(report) if !report.is_empty() => format!("{}:\n{}", title, report),
+        _ => String::new(),
+    }
+}
+
 // Global plugin repository
 lazy_static! {
     static ref PLUGIN_REPO: Mutex<Vec<Arc<Mutex<dyn Plugin>>>> = Mutex::new(Vec::new());
@@ -47,11 +61,20 @@ impl PluginRepo {
             .map(Arc::clone)
     }
 
-    pub fn analyze_all(bugreport: &BugreportTxt) {
+    /// Run every registered plugin. A failing plugin does not stop the others; the first
+    /// failure is returned once all of them have run.
+    pub fn analyze_all(bugreport: &BugreportTxt) -> Result<()> {
+        let mut first_error = None;
         for plugin in PluginRepo::get_all() {
             let mut plugin = plugin.lock().unwrap();
-            plugin.analyze(bugreport);
+            if let Err(e) = plugin.analyze(bugreport) {
+                first_error.get_or_insert(Error::Plugin {
+                    name: plugin.name().to_string(),
+                    source: Box::new(e),
+                });
+            }
         }
+        first_error.map_or(Ok(()), Err)
     }
 
     pub fn report_all() -> String {
@@ -78,7 +101,7 @@ mod test {
             todo!()
         }
 
-        fn analyze(&mut self, _: &BugreportTxt) {
+        fn analyze(&mut self, _: &BugreportTxt) -> Result<()> {
             todo!()
         }
 
@@ -98,7 +121,7 @@ mod test {
             todo!()
         }
 
-        fn analyze(&mut self, _: &BugreportTxt) {
+        fn analyze(&mut self, _: &BugreportTxt) -> Result<()> {
             todo!()
         }
 
@@ -0,0 +1,42 @@
+use crate::bugreport::bugreport_txt::BugreportTxt;
+use crate::bugreport::timing::TimingReport;
+use crate::error::Result;
+
+use super::Plugin;
+
+/// Reports how long every section and dumpsys service took, and which of them timed out.
+#[derive(Default)]
+pub struct SectionTimingPlugin {
+    report: Option<TimingReport>,
+}
+
+impl Plugin for SectionTimingPlugin {
+    fn name(&self) -> &str {
+        "SectionTimingPlugin"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()> {
+        self.report = Some(TimingReport::build(
+            bugreport,
+            TimingReport::default_slow_threshold(),
+        )?);
+        Ok(())
+    }
+
+    fn report(&self) -> String {
+        match &self.report {
+            Some(report) => format!("Section timing:\n{}", report),
+            None => String::new(),
+        }
+    }
+}
+
+impl SectionTimingPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
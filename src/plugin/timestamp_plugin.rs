@@ -1,11 +1,12 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, FixedOffset, Utc};
 
 use crate::bugreport::bugreport_txt::BugreportTxt;
+use crate::error::Result;
 
 use super::Plugin;
 
 pub struct TimestampPlugin {
-    timestamp: DateTime<Local>,
+    timestamp: DateTime<FixedOffset>,
 }
 
 impl Plugin for TimestampPlugin {
@@ -17,10 +18,11 @@ impl Plugin for TimestampPlugin {
         "1.0.0"
     }
 
-    fn analyze(&mut self, bugreport: &BugreportTxt) {
+    fn analyze(&mut self, bugreport: &BugreportTxt) -> Result<()> {
         // Analyze the bug report and extract timestamps
         self.timestamp = bugreport.get_metadata().timestamp.clone();
         println!("Analyzed timestamps: {:?}", self.timestamp);
+        Ok(())
     }
 
     fn report(&self) -> String {
@@ -31,7 +33,7 @@ impl Plugin for TimestampPlugin {
 impl TimestampPlugin {
     pub fn new() -> Self {
         TimestampPlugin {
-            timestamp: Local::now(),
+            timestamp: Utc::now().fixed_offset(),
         }
     }
 }
@@ -46,7 +48,7 @@ mod test {
         let mut bugreport = test_setup_bugreport().unwrap();
         bugreport.load().unwrap();
         let mut plugin = TimestampPlugin::new();
-        plugin.analyze(&bugreport);
+        plugin.analyze(&bugreport).unwrap();
         assert_eq!(plugin.report(), "Bugreport timestamp: 2024-08-16T10:02:11+08:00");
     }
 }
@@ -2,8 +2,10 @@ use std::path::Path;
 use std::rc::Rc;
 use rustyline::{DefaultEditor, error::ReadlineError};
 
-use crate::bugreport::bugreport_txt::BugreportTxt;
-use crate::bugreport::logcat::LogcatLine;
+use crate::bugreport::bugreport::{self, Bugreport};
+use crate::bugreport::clock::TimeDisplay;
+use crate::bugreport::logcat::window::{self, TimeWindow};
+use crate::bugreport::logcat::{LogBuffer, LogcatLine};
 
 
 enum ReplStatus {
@@ -12,23 +14,27 @@ enum ReplStatus {
 }
 
 struct ReplState {
-    bugreport: BugreportTxt,
+    bugreport: Bugreport,
     status: ReplStatus,
     last_command: String,
     last_result: Rc<Vec<LogcatLine>>,
 }
 
-pub fn repl(path: &Path) {
+pub fn repl(path: &Path, cache_path: Option<&Path>, display: TimeDisplay) {
     let mut rl = DefaultEditor::new().unwrap();
-    let mut bugreport = match BugreportTxt::new(path) {
+    let bugreport = match bugreport::open(path, cache_path, display) {
         Ok(bugreport) => bugreport,
         Err(_) => return,
     };
+    if bugreport.get_metadata().clock.is_guessed() {
+        println!("The report does not give the device timezone, times are read as UTC");
+    }
+    // 交互式查询需要即时响应，先为日志建立索引
+    match bugreport.build_indexes() {
+        Ok(memory) => println!("Log index built, {} KiB", memory / 1024),
+        Err(e) => println!("Failed to build log index: {}", e),
+    }
 
-    match bugreport.load() {
-        Ok(matches) => matches,
-        Err(_) => return,
-    };
     let mut state = ReplState {
         bugreport,
         status: ReplStatus::Ready,
@@ -93,18 +99,61 @@ fn evaluate_input(input: &str, state: &mut ReplState) -> String {
         }
         let tag_name = parts[1];
 
-        // 执行 tag 命令
-        // TODO: 需要优化，如果上一次查询的结果不为空，则直接使用上一次查询的结果
+        // 执行 tag 命令，重复同一条命令时直接使用上一次查询的结果，否则走索引查询
         // TODO: 需要优化，加入自动补全 tag，避免想不起来
-        let results = if state.last_result.len() > 0 {
-            state.bugreport.search_by_tag(tag_name)
-        } else {
-            state.bugreport.search_by_tag(tag_name)
-        };
-        state.last_result = Rc::new(results.unwrap());
+        if state.last_command != input || state.last_result.is_empty() {
+            match state.bugreport.search_by_tag(tag_name) {
+                Ok(results) => state.last_result = Rc::new(results),
+                Err(e) => return format!("Error: {}", e),
+            }
+            state.last_command = input.to_string();
+        }
 
         return format!("Tagged results: {:?}", state.last_result);
     }
 
+    if input.starts_with("buffer") {
+        // 解析 buffer 命令，例如 buffer crash
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        if parts.len() != 2 {
+            return "Invalid buffer command. Usage: buffer <main|system|crash|radio|events>".to_string();
+        }
+        let buffer = match parts[1].parse::<LogBuffer>() {
+            Ok(buffer) => buffer,
+            Err(_) => return format!("Unknown buffer: {}", parts[1]),
+        };
+        match state.bugreport.search_by_buffer(buffer) {
+            Ok(results) => state.last_result = Rc::new(results),
+            Err(e) => return format!("Error: {}", e),
+        }
+        state.last_command = input.to_string();
+
+        return format!("Buffer results: {:?}", state.last_result);
+    }
+
+    if input.starts_with("around") {
+        // 解析 around 命令，例如 around 2024-08-16 10:01:30.250 30s，时间跨度默认前后各 30s
+        let parts: Vec<&str> = input.split_whitespace().collect();
+        if parts.len() != 3 && parts.len() != 4 {
+            return "Invalid around command. Usage: around <YYYY-MM-DD HH:MM:SS[.mmm]> [span, e.g. 30s]".to_string();
+        }
+        let clock = &state.bugreport.get_metadata().clock;
+        let time = match window::parse_time(&parts[1..3].join(" "), clock) {
+            Ok(time) => time,
+            Err(e) => return format!("Error: {}", e),
+        };
+        let span = match window::parse_offset(parts.get(3).unwrap_or(&"30s")) {
+            Ok(span) => span.abs(),
+            Err(e) => return format!("Error: {}", e),
+        };
+        match state.bugreport.search_by_window(&TimeWindow::around(time, span, span)) {
+            Ok(results) => state.last_result = Rc::new(results),
+            Err(e) => return format!("Error: {}", e),
+        }
+        state.last_command = input.to_string();
+
+        return format!("Window results: {:?}", state.last_result);
+    }
+
     "Ok".to_string()
 }
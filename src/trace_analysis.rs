@@ -1,15 +1,17 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{self, File},
-    io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write},
+    io::{self, BufWriter, Write},
     path::Path,
 };
 
 use crate::{
+    bugreport::binder::{BinderEndpoint, BinderTransactions, CallChain},
+    bugreport::locks::{HolderState, LockGraph},
+    bugreport::traces::{AnrTraces, LockKind, ProcessDump, StackFrame, ThreadDump},
+    error::{Error, Result},
     models::{
-        anr_result_bean::ANRResultBean,
-        lock_bean::{HeldThread, LockBean},
-        log_item_bean::LogItemBean,
+        anr_result_bean::ANRResultBean, lock_bean::LockBean, log_item_bean::LogItemBean,
         result_item_bean::ResultItemBean,
     },
     utils::file_utils,
@@ -19,22 +21,15 @@ const BINDER_TRANSACT: &str = "$Stub$Proxy.";
 const BINDER_PROXY: &str = "$Proxy.";
 const TRANSACT: &str = ".onTransact";
 const BIND_EXEC_TRANSACT: &str = "android.os.Binder.execTransact";
-const HELD_BY_TID: &str = "held by tid=";
-const HELD_BY_THREAD: &str = "held by thread ";
-const PRE_TID: &str = "tid=";
-const WAITING_TO_LOCK: &str = "waiting to lock <";
-const LOCKED: &str = "- locked <";
-const PREFIX_PID: &str = "----- pid ";
-const PREFIX_CMD: &str = "Cmd line: ";
+// LogItemBean::time_in_frame 使用的时间格式
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
 #[derive(Debug, Default)]
 pub struct TraceAnalysis {
-    current_pid_line: String,
-    current_cmd_line: String,
-    last_pid_line: String,
-    last_cmd_line: String,
-    current_log_bean: Option<LogItemBean>,
-    lock_map: HashMap<String, bool>,
+    // 最近一次写出头部的进程 pid，同一进程的 pid/Cmd line 只写一次
+    last_written_pid: Option<u32>,
+    // 报告里的 binder 事务记录，有它时 binder 调用按 pid:tid 追踪而不是按接口名猜测
+    binder: Option<BinderTransactions>,
 }
 
 impl TraceAnalysis {
@@ -45,64 +40,10 @@ impl TraceAnalysis {
         }
     }
 
-    // 设置 current_pid_line
-    fn set_current_pid_line(&mut self, pid_line: String) {
-        self.current_pid_line = pid_line;
-    }
-
-    // 获取 current_pid_line
-    fn get_current_pid_line(&self) -> &str {
-        &self.current_pid_line
-    }
-
-    // 设置 current_cmd_line
-    fn set_current_cmd_line(&mut self, cmd_line: String) {
-        self.current_cmd_line = cmd_line;
-    }
-
-    // 获取 current_cmd_line
-    fn get_current_cmd_line(&self) -> &str {
-        &self.current_cmd_line
-    }
-
-    // 设置 last_pid_line
-    fn set_last_pid_line(&mut self, pid_line: String) {
-        self.last_pid_line = pid_line;
-    }
-
-    // 获取 last_pid_line
-    fn get_last_pid_line(&self) -> &str {
-        &self.last_pid_line
-    }
-
-    // 设置 last_cmd_line
-    fn set_last_cmd_line(&mut self, cmd_line: String) {
-        self.last_cmd_line = cmd_line;
-    }
-
-    // 获取 last_cmd_line
-    fn get_last_cmd_line(&self) -> &str {
-        &self.last_cmd_line
-    }
-
-    // 设置 current_log_bean
-    fn set_current_log_bean(&mut self, log_bean: LogItemBean) {
-        self.current_log_bean = Some(log_bean);
-    }
-
-    // 获取 current_log_bean
-    fn get_current_log_bean(&self) -> Option<&LogItemBean> {
-        self.current_log_bean.as_ref()
-    }
-
-    // 设置 lock_map 的值
-    fn set_lock_map_value(&mut self, key: String, value: bool) {
-        self.lock_map.insert(key, value);
-    }
-
-    // 获取 lock_map 的值
-    fn get_lock_map_value(&self, key: &str) -> Option<bool> {
-        self.lock_map.get(key).copied()
+    // 使用 bugreport 中 BINDER TRANSACTIONS 的事务记录
+    pub fn with_binder_transactions(mut self, binder: BinderTransactions) -> Self {
+        self.binder = Some(binder);
+        self
     }
 }
 
@@ -114,30 +55,29 @@ impl TraceAnalysis {
         bean_list: &mut [LogItemBean],
         result_list: &mut Vec<ResultItemBean>,
         out_folder: Option<&Path>,
-    ) -> Vec<i32> {
+    ) -> Result<Vec<i32>> {
         let mut reasons = Vec::new();
 
         if bean_list.is_empty() {
             reasons.push(-2); // 如果 bean_list 为空，返回 -2
-            return reasons;
+            return Ok(reasons);
         }
 
+        let src_dir = trace_dir(src_file)?;
         for log_bean in bean_list {
             let mut item = ResultItemBean::new();
-            item.set_process_name(log_bean.get_process_name().unwrap().to_string());
-            item.set_out_path(src_file.parent().unwrap().to_string_lossy().to_string());
-
-            let reason = self.analyse_trace(
-                src_file,
-                log_bean,
-                &mut item,
-                out_folder.unwrap_or(src_file.parent().unwrap()),
-            );
+            item.set_process_name(process_name(log_bean)?.to_string());
+            if let Some(pid) = log_bean.get_pid().and_then(|pid| pid.trim().parse().ok()) {
+                item.set_pid(pid);
+            }
+            item.set_out_path(src_dir.to_string_lossy().to_string());
+
+            let reason = self.analyse_trace(src_file, log_bean, &mut item, out_folder.unwrap_or(src_dir))?;
             reasons.push(reason);
             result_list.push(item);
         }
 
-        reasons
+        Ok(reasons)
     }
 
     // 分析单个 LogItemBean
@@ -147,541 +87,316 @@ impl TraceAnalysis {
         log_bean: &mut LogItemBean,
         item: &mut ResultItemBean,
         out_folder: &Path,
-    ) -> i32 {
+    ) -> Result<i32> {
         if !src_file.exists() || !src_file.is_file() {
-            return -1;
+            return Ok(-1);
         }
 
-        self.current_log_bean = Some(log_bean.clone());
+        // trace 文件只解析一次，后续分析都基于解析出的模型
+        let traces = match AnrTraces::load(src_file) {
+            Ok(traces) => traces,
+            Err(_) => return Ok(-1),
+        };
 
         let out_dir = if out_folder.to_string_lossy().is_empty() {
-            src_file.parent().unwrap().to_path_buf()
+            trace_dir(src_file)?.to_path_buf()
         } else {
             out_folder.to_path_buf()
         };
 
+        let process_name = process_name(log_bean)?;
         let out_filename = if let Some(time) = log_bean.get_time() {
-            format!(
-                "result_trace_{}_{}",
-                log_bean.get_process_name().unwrap(),
-                time
-            )
+            format!("result_trace_{}_{}", process_name, time)
         } else {
-            format!("result_trace_{}", log_bean.get_process_name().unwrap())
+            format!("result_trace_{}", process_name)
         };
 
         let out_file = out_dir.join(out_filename);
 
         if let Ok(file) = File::create(&out_file) {
             let mut writer = BufWriter::new(file);
-            let main_reason = self.analyse_trace_internal(src_file, log_bean, item, &mut writer);
+            let main_reason = self.analyse_traces(&traces, log_bean, item, &mut writer);
             println!("Output file: {:?}", out_file);
-            main_reason
+            Ok(main_reason)
         } else {
-            -1
+            Ok(-1)
         }
     }
 
-    // 内部分析逻辑
-    fn analyse_trace_internal(
+    // 在解析好的 trace 上分析单个 LogItemBean，返回值同 get_main
+    pub fn analyse_traces<W: Write>(
         &mut self,
-        src_file: &Path,
+        traces: &AnrTraces,
         log_bean: &mut LogItemBean,
         result_bean: &mut ResultItemBean,
-        writer: &mut BufWriter<File>,
+        writer: &mut W,
     ) -> i32 {
-        // this.mCurrentLogBean = logBean; Consider how to convert this to Rust
-        if let Ok(file) = File::open(src_file) {
-            let mut reader = BufReader::new(file);
-            let start_line = if let Some(pid) = log_bean.get_pid() {
-                format!("----- pid {} at ", pid)
-            } else {
-                format!("Cmd line: {}", log_bean.get_process_name().unwrap())
-            };
-
-            let mut previous_line = String::new();
-            for line in reader.by_ref().lines().flatten() {
-                if line.starts_with(&start_line) {
-                    if line.starts_with("----- pid ") {
-                        self.current_pid_line = line.clone();
-                    } else if line.starts_with("Cmd line: ") {
-                        self.current_pid_line = previous_line.clone();
-                        self.current_cmd_line = line.clone();
-                    }
+        self.last_written_pid = None;
+        let process = match Self::find_process(traces, log_bean) {
+            Some(process) => process,
+            None => return -1,
+        };
 
-                    if log_bean.get_time().is_none() {
-                        if let Some(start) = self.current_pid_line.find("at") {
-                            if let Some(end) = self.current_pid_line.rfind(" -----") {
-                                let time = &self.current_pid_line[start + 3..end];
-                                log_bean.set_time(time.to_string());
-                            }
-                        }
-                    }
+        if log_bean.get_time().is_none() {
+            if let Some(time) = process.time {
+                log_bean.set_time(time.format(TIME_FORMAT).to_string());
+            }
+        }
 
-                    if log_bean.get_time().is_some() && log_bean.get_pid().is_some() {
-                        let end = line.rfind(" -----").unwrap();
-                        let start = start_line.len();
-                        let time = &line[start..end];
-                        if !log_bean.time_in_frame(time, 30000) {
-                            continue;
-                        }
+        self.get_main(traces, process, result_bean, writer)
+    }
+
+    // 找到 ANR 对应的进程 dump：有 pid 时按 pid 匹配，并要求与 ANR 时间相差 30s 以内；否则按进程名匹配
+    fn find_process<'t>(traces: &'t AnrTraces, log_bean: &LogItemBean) -> Option<&'t ProcessDump> {
+        match log_bean.get_pid().and_then(|pid| pid.trim().parse().ok()) {
+            Some(pid) => traces.get_process(pid).find(|process| {
+                match (log_bean.get_time(), process.time) {
+                    (Some(_), Some(time)) => {
+                        log_bean.time_in_frame(&time.format(TIME_FORMAT).to_string(), 30000)
                     }
+                    _ => true,
                 }
-                previous_line = line;
+            }),
+            None => {
+                let name = log_bean.get_process_name()?;
+                traces.processes().iter().find(|process| process.cmd_line.as_deref() == Some(name))
             }
-            self.get_main(&mut reader, writer, src_file, result_bean) // 成功
-        } else {
-            -1 // 失败
         }
     }
 
-    fn get_main(
+    // 分析主线程：写出主线程堆栈，并根据其阻塞原因继续分析
+    // 返回 -1 表示没有主线程，1 表示阻塞在 binder 调用，2 表示等锁，0 表示其他
+    fn get_main<W: Write>(
         &mut self,
-        reader: &mut BufReader<File>,
-        writer: &mut BufWriter<File>,
-        src_file: &Path,
+        traces: &AnrTraces,
+        process: &ProcessDump,
         result_bean: &mut ResultItemBean,
+        writer: &mut W,
     ) -> i32 {
-        let mut is_main_mode = false;
-        let mut type_code = 0;
-
-        if self.lock_map.is_empty() {
-            self.lock_map.clear();
-        }
-
-        let mut line = String::new();
-        while reader.read_line(&mut line).unwrap() > 0 {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-
-            if line.starts_with("Cmd line: ") {
-                self.current_cmd_line = line.to_string();
-            }
-
-            if line[1..].starts_with("main") {
-                is_main_mode = true;
-                break;
-            }
-        }
-
-        if !is_main_mode {
-            return -1;
-        }
+        let main = match process.main_thread() {
+            Some(main) => main,
+            None => return -1,
+        };
 
-        self.write_process_info(writer).unwrap();
-        file_utils::write_line_to_file(&line, writer).unwrap();
+        let _ = self.write_thread(process, main, writer);
 
-        let mut api = String::new();
-        let mut package_name = String::new();
-        let mut lock_bean = LockBean::new();
+        // 记录主线程的 Java 堆栈，直到第一个属于本进程包名的帧为止
         result_bean.get_trace_list_mut().clear();
         let process_name = result_bean.get_process_name().to_string();
-        let mut is_add_trace_line_continue = true;
-
-        while reader.read_line(&mut line).unwrap() > 0 && !line.starts_with("\"") {
-            file_utils::write_line_to_file(&line, writer).unwrap();
-
-            if !process_name.is_empty() {
-                if line.trim().starts_with("at") && is_add_trace_line_continue {
-                    result_bean.get_trace_list_mut().push(line.to_string());
-                }
-
-                if line.contains(&process_name) {
-                    is_add_trace_line_continue = false;
+        if !process_name.is_empty() {
+            for frame in main.frames.iter().filter(|frame| matches!(frame, StackFrame::Java { .. })) {
+                let frame = frame.to_string();
+                let is_app_frame = frame.contains(&process_name);
+                result_bean.get_trace_list_mut().push(frame);
+                if is_app_frame {
+                    break;
                 }
             }
-
-            if line.contains("$Stub$Proxy.") {
-                type_code = 1;
-                let start = line.find("$Stub$Proxy.").unwrap() + "$Stub$Proxy.".len();
-                api = line[start..line.rfind("(").unwrap()].to_string();
-                package_name = line[..line.find("$Proxy.").unwrap()].to_string();
-                continue;
-            }
-
-            if line.contains("waiting to lock <") {
-                type_code = 2;
-                self.get_lock_from_line(&line, &mut lock_bean);
-                continue;
-            }
-
-            if line.contains("- locked <") {
-                self.get_lock_from_line(&line, &mut lock_bean);
-                let lock_key =
-                    line[line.find("<").unwrap() + 1..line.find(">").unwrap()].to_string();
-                self.lock_map.insert(lock_key, true);
-            }
         }
 
-        match type_code {
-            1 => {
-                self.binder_call_timeout(&api, &package_name, writer, src_file);
-            }
-            2 => {
-                let mut model_lines = Vec::new();
-                if let Ok(file) = File::open(src_file) {
-                    let mut reader = BufReader::new(file);
-                    self.analyse_trace_by_lock(
-                        &mut lock_bean,
-                        &mut model_lines,
-                        writer,
-                        src_file,
-                        &mut reader,
-                    );
-                }
-            }
-            _ => {}
+        if main.waiting_lock().is_some() {
+            let mut lock_bean = LockBean::new();
+            let _ = self.analyse_trace_by_lock(&mut lock_bean, process, main, writer);
+            return 2;
         }
 
-        type_code
-    }
-
-    // Binder 调用超时逻辑
-    fn binder_call_timeout(
-        &mut self,
-        api: &str,
-        remote_package: &str,
-        writer: &mut BufWriter<File>,
-        src_file: &Path,
-    ) -> io::Result<()> {
-        if api.is_empty() || remote_package.is_empty() {
-            return Ok(());
-        }
-
-        let remote_package = format!("{}.onTransact", remote_package);
-
-        let file = File::open(src_file)?;
-        let mut reader = BufReader::new(file);
-        let mut mode_lines = Vec::new();
-        let mut has_api = false;
-        let mut has_package = false;
-        let mut has_binder_transact = false;
-
-        let mut line = String::new();
-        while reader.read_line(&mut line)? > 0 {
-            if line.starts_with("----- pid ") {
-                self.current_pid_line = line.trim().to_string();
-            } else if line.starts_with("Cmd line: ") {
-                self.current_cmd_line = line.trim().to_string();
-            }
-
-            if line.starts_with("\"") {
-                mode_lines.clear();
-                mode_lines.push(self.current_pid_line.clone());
-                mode_lines.push(self.current_cmd_line.clone());
-                has_api = false;
-                has_package = false;
-                has_binder_transact = false;
-            }
-
-            mode_lines.push(line.trim().to_string());
-
-            if line.contains(api) {
-                has_api = true;
-            }
-            if line.contains(&remote_package) {
-                has_package = true;
-            }
-            if line.contains("android.os.Binder.execTransact") {
-                has_binder_transact = true;
-            }
-
-            if has_api && has_package && has_binder_transact {
-                break;
+        if let Some(binder) = self.binder.take() {
+            let written = self.binder_call_chain(&binder, traces, process, main, writer);
+            self.binder = Some(binder);
+            if written {
+                return 1;
             }
         }
 
-        let mut lock_bean = LockBean::new();
-        self.get_lock_from_model(&mut lock_bean, &mode_lines, writer);
-        self.analyse_trace_by_lock(
-            &mut lock_bean,
-            &mut mode_lines,
-            writer,
-            src_file,
-            &mut reader,
-        );
+        if let Some((interface, api)) = binder_proxy_call(main) {
+            let _ = self.binder_call_timeout(traces, interface, api, writer);
+            return 1;
+        }
 
-        Ok(())
+        0
     }
 
-    // 从行中提取锁信息
-    fn get_lock_from_line(&self, line: &str, lock_bean: &mut LockBean) {
-        if line.contains("waiting to lock <") {
-            let object = line
-                .split("waiting to lock <")
-                .nth(1)
-                .and_then(|s| s.split('>').next())
-                .map(|s| s.trim().to_string());
-            if let Some(obj) = object {
-                lock_bean.add_waiting(obj);
-            }
-        } else if line.contains("- locked <") {
-            let object = line
-                .split("- locked <")
-                .nth(1)
-                .and_then(|s| s.split('>').next())
-                .map(|s| s.trim().to_string());
-            if let Some(obj) = object {
-                lock_bean.add_lock(obj);
-            }
-        }
-
-        let thread_tag = if line.contains("held by tid=") {
-            Some("held by tid=")
-        } else if line.contains("held by thread ") {
-            Some("held by thread ")
-        } else {
-            None
+    // 按 binder 事务记录还原主线程的跨进程调用链，写出链上每个线程和整条链；主线程不在 binder 调用中时返回 false
+    fn binder_call_chain<W: Write>(
+        &mut self,
+        binder: &BinderTransactions,
+        traces: &AnrTraces,
+        process: &ProcessDump,
+        main: &ThreadDump,
+        writer: &mut W,
+    ) -> bool {
+        let start = BinderEndpoint {
+            pid: process.pid,
+            tid: main.sys_tid.unwrap_or(process.pid),
         };
-
-        if let Some(tag) = thread_tag {
-            let substring = line.split(tag).nth(1).unwrap_or("");
-            let tid = if let Some(end_index) = substring.find(' ') {
-                format!("tid={}", &substring[..end_index])
-            } else {
-                format!("tid={}", substring)
-            };
-
-            let thread_name = if substring.contains('(') && substring.contains(')') {
-                Some(
-                    substring
-                        .split('(')
-                        .nth(1)
-                        .and_then(|s| s.split(')').next())
-                        .unwrap_or("")
-                        .to_string(),
-                )
-            } else {
-                None
-            };
-
-            lock_bean.add_waiting_thread(tid, thread_name);
+        let chain = match CallChain::trace(binder, traces, start) {
+            Some(chain) => chain,
+            None => return false,
+        };
+        for (process, thread) in chain.threads() {
+            let _ = self.write_thread(process, thread, writer);
         }
+        let _ = file_utils::write_line_to_file("Binder call chain:", writer);
+        let _ = file_utils::write_line_to_file(chain.to_string().trim_end(), writer);
+        true
     }
 
-    // 从模型行中提取锁信息
-    fn get_lock_from_model(
-        &self,
-        lock_bean: &mut LockBean,
-        model_lines: &[String],
-        writer: &mut BufWriter<File>,
+    // 没有 binder 事务记录时的 Binder 调用超时逻辑：找到正在执行该接口方法的 binder 线程，写出它并继续追踪它等待的锁
+    fn binder_call_timeout<W: Write>(
+        &mut self,
+        traces: &AnrTraces,
+        interface: &str,
+        api: &str,
+        writer: &mut W,
     ) -> io::Result<()> {
-        if model_lines.is_empty() {
-            println!("getLockFromModel modelLines is empty");
+        if api.is_empty() || interface.is_empty() {
             return Ok(());
         }
 
-        if writer.get_ref().metadata().is_err() || lock_bean.get_locked_objects().is_empty() {
-            println!("getLockFromModel lockBean or writer is invalid");
-            return Ok(());
-        }
-
-        lock_bean.clear();
-
-        for line in model_lines {
-            file_utils::write_line_to_file(line, writer)?;
-            self.get_lock_from_line(line, lock_bean);
-        }
+        let on_transact = format!("{}{}", interface, TRANSACT);
+        let api_suffix = format!(".{}", api);
+        let remote = traces.threads().find(|(_, thread)| {
+            thread.java_methods().any(|method| method.ends_with(&api_suffix))
+                && thread.java_methods().any(|method| method == on_transact)
+                && thread.java_methods().any(|method| method == BIND_EXEC_TRANSACT)
+        });
 
-        Ok(())
-    }
-
-    // 检查是否包含特殊锁
-    fn contain_special_lock(&self, line: &str, search_lock: &[String]) -> bool {
-        for lock in search_lock {
-            if line.contains(lock) {
-                return true;
-            }
+        if let Some((process, thread)) = remote {
+            self.write_thread(process, thread, writer)?;
+            let mut lock_bean = LockBean::new();
+            self.analyse_trace_by_lock(&mut lock_bean, process, thread, writer)?;
         }
-        false
-    }
 
-    // 写入进程信息
-    fn write_process_info(&self, writer: &mut BufWriter<File>) -> io::Result<()> {
-        if !file_utils::is_empty(&self.current_pid_line)
-            && self.current_pid_line != self.last_pid_line
-        {
-            file_utils::write_line_to_file(&self.current_pid_line, writer)?;
-        }
-        if !file_utils::is_empty(&self.current_cmd_line)
-            && self.current_cmd_line != self.last_cmd_line
-        {
-            file_utils::write_line_to_file(&self.current_cmd_line, writer)?;
-        }
         Ok(())
     }
 
-    // 检查线程模型
-    fn check_thread_model(&self, line: &str, held_threads: &[HeldThread]) -> bool {
-        if file_utils::is_empty(line) {
-            return false;
-        }
-        if held_threads.is_empty() {
-            return true;
-        }
-
-        let mut is_in_model = false;
-        for held_thread in held_threads {
-            if !line.contains(&held_thread.tid) {
-                continue;
-            }
-            match &held_thread.thread_name {
-                Some(name) => {
-                    if line.contains(name) {
-                        is_in_model = true;
-                        break;
+    // 把线程引用的锁记录到 LockBean 中
+    fn get_lock_from_thread(&self, thread: &ThreadDump, lock_bean: &mut LockBean) {
+        for lock in thread.locks() {
+            match lock.kind {
+                LockKind::Locked => lock_bean.add_lock(lock.address.clone()),
+                LockKind::WaitingToLock | LockKind::ParkingToWaitFor => {
+                    lock_bean.add_waiting(lock.address.clone());
+                    if let Some(tid) = lock.held_by {
+                        lock_bean.add_waiting_thread(format!("tid={}", tid), None);
                     }
                 }
-                None => {
-                    is_in_model = true;
-                    break;
-                }
+                _ => {}
             }
         }
+    }
 
-        if is_in_model {
-            if let Some(start) = self.current_pid_line.find("at") {
-                if let Some(end) = self.current_pid_line.find(" -----") {
-                    let start = start + 3; // "at" 的长度是 2，加上空格
-                    if start < end {
-                        let time = &self.current_pid_line[start..end];
-                        if let Some(log_bean) = &self.current_log_bean {
-                            is_in_model = log_bean.time_in_frame(time, 21000);
-                            return is_in_model;
-                        }
-                    }
-                }
+    // 写出线程所在进程的头部（每个进程只写一次）和线程的全部行
+    fn write_thread<W: Write>(
+        &mut self,
+        process: &ProcessDump,
+        thread: &ThreadDump,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        if self.last_written_pid != Some(process.pid) {
+            file_utils::write_line_to_file(process.get_header(), writer)?;
+            if let Some(cmd_line) = &process.cmd_line {
+                file_utils::write_line_to_file(&format!("Cmd line: {}", cmd_line), writer)?;
             }
+            self.last_written_pid = Some(process.pid);
         }
-        false
+        for line in thread.lines() {
+            file_utils::write_line_to_file(line, writer)?;
+        }
+        Ok(())
     }
 
-    // 分析基于锁的跟踪
-    pub(crate) fn analyse_trace_by_lock(
+    // 分析基于锁的跟踪：沿着等锁图从线程走到最终持锁者并写出途经的每个持锁线程；
+    // 走回已访问的线程说明成环，写出死锁，否则写出最终持锁者在做什么
+    pub(crate) fn analyse_trace_by_lock<W: Write>(
         &mut self,
         lock_object: &mut LockBean,
-        model_lines: &mut Vec<String>,
-        writer: &mut BufWriter<File>,
-        src_file: &Path,
-        reader: &mut BufReader<File>,
+        process: &ProcessDump,
+        thread: &ThreadDump,
+        writer: &mut W,
     ) -> io::Result<()> {
-        if lock_object.get_waiting_objects().is_empty() || !src_file.exists() {
-            return Ok(());
-        }
-
-        model_lines.clear();
-
-        // 将锁定的对象添加到锁映射中
-        for object in lock_object.get_locked_objects() {
-            self.lock_map.insert(object.clone(), true);
-        }
-
-        // 构建搜索锁列表
-        let search_lock: Vec<String> = lock_object
-            .get_waiting_objects()
-            .iter()
-            .map(|obj| format!("- locked <{}>", obj))
-            .collect();
-
-        let mut line = String::new();
-        let mut is_find = false;
-        let mut is_in_the_model = false;
-        let temp_pid_line = self.current_pid_line.clone();
-        let temp_cmd_line = self.current_cmd_line.clone();
-        let mut pos_in_model_list = 0;
-
-        // 读取文件内容
-        while reader.read_line(&mut line)? > 0 {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-
-            if line.starts_with("----- pid ") {
-                self.current_pid_line = line.to_string();
-            } else if line.starts_with("Cmd line: ") {
-                self.current_cmd_line = line.to_string();
-            }
-
-            if line.starts_with("\"") {
-                if is_find {
-                    break;
-                }
-
-                is_in_the_model = self.check_thread_model(line, lock_object.get_waiting_threads());
-                if is_in_the_model {
-                    pos_in_model_list = model_lines.len();
-                    model_lines.push(self.current_pid_line.clone());
-                    model_lines.push(self.current_cmd_line.clone());
-                }
-            }
-
-            if is_in_the_model {
-                model_lines.push(line.to_string());
-                if self.contain_special_lock(line, &search_lock) {
-                    is_find = true;
+        self.get_lock_from_thread(thread, lock_object);
+        let graph = LockGraph::build(process);
+        let start = match graph.position(thread) {
+            Some(position) => position,
+            None => return Ok(()),
+        };
+        let mut current = start;
+        let mut visited = HashSet::new();
+        visited.insert(current);
+
+        while let Some(edge) = graph.waits_for(current) {
+            if !visited.insert(edge.holder) {
+                let holder = &process.threads[edge.holder];
+                let deadlock = graph
+                    .deadlocks()
+                    .into_iter()
+                    .find(|deadlock| deadlock.threads.iter().any(|t| std::ptr::eq(*t, holder)));
+                if let Some(deadlock) = deadlock {
+                    file_utils::write_line_to_file(deadlock.to_string().trim_end(), writer)?;
                 }
+                return Ok(());
             }
+            let holder = &process.threads[edge.holder];
+            self.write_thread(process, holder, writer)?;
+            self.get_lock_from_thread(holder, lock_object);
+            current = edge.holder;
         }
 
-        // 重置读取器
-        reader.seek(io::SeekFrom::Start(0))?;
-
-        // 恢复原始状态
-        self.current_pid_line = temp_pid_line;
-        self.current_cmd_line = temp_cmd_line;
-
-        // 处理模型行
-        if !model_lines.is_empty() {
-            if is_find {
-                model_lines.truncate(pos_in_model_list);
-            }
-
-            // 调用 get_lock_from_model 方法
-            self.get_lock_from_model(lock_object, model_lines, writer)?;
-
-            // 递归调用 analyse_trace_by_lock
-            self.analyse_trace_by_lock(lock_object, model_lines, writer, src_file, reader)?;
+        if current != start {
+            let root = &process.threads[current];
+            let state = HolderState::of(root);
+            file_utils::write_line_to_file(&format!("Lock holder \"{}\" is {}", root.name, state), writer)?;
         }
-
         Ok(())
     }
 }
 
-// 解析日志文件
-pub fn parse_log(path: &Path, args: &[String]) {
-    let mut anr_result_bean_list = Vec::new();
+// 主线程正在发起的 binder 调用：(接口名, 方法名)，例如 ("android.app.IActivityManager$Stub", "startActivity")
+fn binder_proxy_call(thread: &ThreadDump) -> Option<(&str, &str)> {
+    thread.java_methods().find_map(|method| {
+        let start = method.find(BINDER_TRANSACT)?;
+        let interface = &method[..method.find(BINDER_PROXY)?];
+        Some((interface, &method[start + BINDER_TRANSACT.len()..]))
+    })
+}
 
-    if !path.is_dir() {
-        if let Some(result) = parse_single_log(path, args) {
-            anr_result_bean_list.push(result);
-        }
-        // return anr_result_bean_list;
-    }
+// 日志条目里的进程名，结果文件以它命名
+fn process_name(log_bean: &LogItemBean) -> Result<&str> {
+    log_bean.get_process_name().ok_or_else(|| Error::malformed("process name of the ANR log"))
+}
+
+// trace 文件所在的目录，默认的输出目录
+fn trace_dir(src_file: &Path) -> Result<&Path> {
+    src_file.parent().ok_or_else(|| Error::malformed("trace file path"))
+}
 
+// 解析日志文件
+pub fn parse_log(path: &Path, args: &[String]) {
     let mut item_list = Vec::new();
-    if let Ok(entries) = fs::read_dir(path) {
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let file_path = entry.path();
-                if !file_path.to_string_lossy().contains("summary.txt") {
-                    if let Some(mut temp) = parse_single_log(&file_path, args) {
-                        item_list.append(&mut temp);
-                    }
+    if !path.is_dir() {
+        if let Some(mut result) = parse_single_log(path, args) {
+            item_list.append(&mut result);
+        }
+    } else if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            let file_path = entry.path();
+            if !file_path.to_string_lossy().contains("summary.txt") {
+                if let Some(mut temp) = parse_single_log(&file_path, args) {
+                    item_list.append(&mut temp);
                 }
             }
         }
     }
 
-    if !item_list.is_empty() {
-        let list = turn_result_item_to_anr_list(item_list);
-        // anr_result_bean_list.extend(list);
-    }
-
-    // write_summary(&anr_result_bean_list, path);
-    // anr_result_bean_list
+    // 按堆栈签名分组后写出总结文件，单个文件时写在它所在的目录
+    let anr_result_bean_list = turn_result_item_to_anr_list(item_list);
+    let summary_dir = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(Path::new("."))
+    };
+    write_summary(&anr_result_bean_list, summary_dir);
 }
 
 // 解析单个日志文件
@@ -699,11 +414,8 @@ fn parse_single_log(path: &Path, args: &[String]) -> Option<Vec<ResultItemBean>>
 
     let scr_file = Path::new(&new_path);
     // 调用日志分析逻辑（需要实现）
-    Some(vec![ResultItemBean::with_details(
-        "example_process".to_string(),
-        vec!["trace1".to_string(), "trace2".to_string()],
-        new_path,
-    )])
+    // 实现之前不返回占位结果，否则 parse_log 会把它们写进用户目录的 summary.txt
+    None
 }
 
 // 写入总结文件
@@ -736,11 +448,10 @@ fn turn_result_item_to_anr_list(item_list: Vec<ResultItemBean>) -> Vec<ANRResult
 
         if let Some(&index) = process_to_index.get(&process_name) {
             let anr_bean: &mut ANRResultBean = &mut anr_list[index];
-            let trace_index = anr_bean.compare_trace(&mut trace_list);
-            if trace_index.is_none() {
-                anr_bean.add_traces(&trace_list);
-            }
-            anr_bean.add_log_file_path(out_path, trace_index.unwrap());
+            let index = anr_bean
+                .compare_trace(&mut trace_list)
+                .unwrap_or_else(|| anr_bean.add_traces(&trace_list));
+            anr_bean.add_log_file_path(out_path, index);
         } else {
             let mut anr_bean = ANRResultBean::new();
             anr_bean.set_process_name(process_name.clone());
@@ -753,3 +464,172 @@ fn turn_result_item_to_anr_list(item_list: Vec<ResultItemBean>) -> Vec<ANRResult
 
     anr_list
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bugreport::traces::TEST_TRACES;
+
+    const BINDER_TRACES: &str = r#"
+----- pid 5140 at 2024-08-16 10:01:30.000000000+0800 -----
+Cmd line: com.android.systemui
+
+"main" prio=5 tid=1 Native
+  | sysTid=5140 nice=-10 cgrp=top-app
+  at android.os.BinderProxy.transactNative(Native method)
+  at android.app.IActivityManager$Stub$Proxy.getRunningAppProcesses(IActivityManager.java:5500)
+  at com.android.systemui.Dependency.get(Dependency.java:100)
+  at android.app.ActivityThread.main(ActivityThread.java:8177)
+
+----- end 5140 -----
+
+----- pid 1500 at 2024-08-16 10:01:30.500000000+0800 -----
+Cmd line: system_server
+
+"Binder:1500_5" prio=5 tid=60 Blocked
+  | sysTid=1600 nice=0 cgrp=foreground
+  at com.android.server.am.ActivityManagerService.getRunningAppProcesses(ActivityManagerService.java:9000)
+  - waiting to lock <0x0c2a1b3f> (a com.android.server.am.ActivityManagerService) held by thread 42
+  at android.app.IActivityManager$Stub.onTransact(IActivityManager.java:2800)
+  at android.os.Binder.execTransact(Binder.java:1339)
+
+"Binder:1500_3" prio=5 tid=42 Native
+  | sysTid=1532 nice=0 cgrp=foreground
+  at android.os.BinderProxy.transactNative(Native method)
+  - locked <0x0c2a1b3f> (a com.android.server.am.ActivityManagerService)
+
+----- end 1500 -----
+"#;
+
+    const BINDER_TRANSACTIONS: &str = r#"
+proc 5140
+context binder
+  thread 5140: l 10 need_return 0 tr 0
+    outgoing transaction 2500001: 0000000000000000 from 5140:5140 to 1500:1600 code 3 flags 10 pri 0:110 r1 elapsed 9012ms node 1024 size 152:8 data 0000000000000000
+proc 1500
+context binder
+  thread 1532: l 11 need_return 0 tr 0
+    outgoing transaction 2500007: 0000000000000000 from 1500:1532 to 800:0 code 1 flags 10 pri 0:110 r1 elapsed 8990ms node 3 size 92:0 data 0000000000000000
+"#;
+
+    fn analyse(traces: &str, pid: Option<&str>, process_name: &str) -> (i32, ResultItemBean, String) {
+        analyse_with(TraceAnalysis::new(), traces, pid, process_name)
+    }
+
+    fn analyse_with(
+        mut analysis: TraceAnalysis,
+        traces: &str,
+        pid: Option<&str>,
+        process_name: &str,
+    ) -> (i32, ResultItemBean, String) {
+        let traces = AnrTraces::from_bytes(traces.as_bytes()).unwrap();
+        let mut log_bean = LogItemBean::default();
+        log_bean.set_process_name(process_name.to_string());
+        if let Some(pid) = pid {
+            log_bean.set_pid(pid.to_string());
+        }
+        let mut item = ResultItemBean::new();
+        item.set_process_name(process_name.to_string());
+        let mut out = Vec::new();
+        let reason = analysis.analyse_traces(&traces, &mut log_bean, &mut item, &mut out);
+        // The ANR time comes from the matched dump when the log did not give one
+        assert_eq!(log_bean.get_time().is_some(), reason != -1);
+        (reason, item, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_lock_analysis() {
+        let (reason, item, out) = analyse(TEST_TRACES, Some("1500"), "system_server");
+        assert_eq!(reason, 2);
+        assert_eq!(item.get_trace_list().len(), 3);
+        // The process header is written once, then main and the thread holding its lock
+        assert_eq!(out.matches("----- pid 1500").count(), 1);
+        assert!(out.contains("\"main\" prio=5 tid=1 Blocked"));
+        assert!(out.contains("\"Binder:1500_3\" prio=5 tid=42 Native"));
+        assert!(!out.contains("binder:1500_2"));
+        assert!(out.ends_with("Lock holder \"Binder:1500_3\" is in a binder call\n"));
+    }
+
+    #[test]
+    fn test_binder_analysis() {
+        let (reason, item, out) = analyse(BINDER_TRACES, None, "com.android.systemui");
+        assert_eq!(reason, 1);
+        assert_eq!(
+            item.get_trace_list().last().map(String::as_str),
+            Some("at com.android.systemui.Dependency.get(Dependency.java:100)")
+        );
+        // systemui main -> the binder thread serving the call -> the thread holding its lock
+        let order: Vec<usize> = ["\"main\"", "\"Binder:1500_5\"", "\"Binder:1500_3\""]
+            .iter()
+            .map(|name| out.find(name).unwrap())
+            .collect();
+        assert!(order.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(out.contains("----- pid 1500"));
+        assert_eq!(analyse(BINDER_TRACES, Some("999"), "missing").0, -1);
+    }
+
+    #[test]
+    fn test_binder_chain_analysis() {
+        let mut binder = BinderTransactions::new();
+        binder.parse(&BINDER_TRANSACTIONS.lines().collect::<Vec<_>>());
+        let analysis = TraceAnalysis::new().with_binder_transactions(binder);
+        let (reason, _, out) = analyse_with(analysis, BINDER_TRACES, None, "com.android.systemui");
+        assert_eq!(reason, 1);
+        // The callee is found by its tid, then followed through its lock into the HAL call
+        assert!(out.contains("\"Binder:1500_5\" prio=5 tid=60 Blocked"));
+        assert!(out.contains("  5140:5140 \"main\" -> 1500:1600 \"Binder:1500_5\" binder call 2500001 code 0x3 for 9012ms\n"));
+        assert!(out.contains("  1500:1600 \"Binder:1500_5\" -> 1500:1532 \"Binder:1500_3\" waits for lock <0x0c2a1b3f>\n"));
+        assert!(out.ends_with("no thread has picked it up\n  root 800:0 was not dumped\n"));
+    }
+
+    #[test]
+    fn test_turn_result_item_to_anr_list() {
+        let item = |traces: &[&str], out_path: &str| {
+            ResultItemBean::with_details(
+                "com.android.systemui".to_string(),
+                traces.iter().map(|trace| trace.to_string()).collect(),
+                out_path.to_string(),
+            )
+        };
+        let binder = ["at android.os.BinderProxy.transactNative(Native method)"];
+        let idle = ["at android.os.MessageQueue.nativePollOnce(Native method)"];
+        let anr_list = turn_result_item_to_anr_list(vec![
+            item(&binder, "anr_1"),
+            item(&idle, "anr_2"),
+            item(&binder, "anr_3"),
+        ]);
+        // 同一进程的两种堆栈分成两组
+        assert_eq!(anr_list.len(), 1);
+        assert_eq!(anr_list[0].get_group_count(), 2);
+
+        // 总结文件里每组都带签名
+        let dir = std::env::temp_dir().join(format!("summary_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        write_summary(&anr_list, &dir);
+        let summary = fs::read_to_string(dir.join("summary.txt")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(summary.matches("signature: ").count(), 2);
+        assert!(summary.contains("<2 times>"));
+    }
+
+    #[test]
+    fn test_analyse_trace_list_without_process_name() {
+        // 日志没有进程名时返回错误而不是 panic
+        let mut beans = vec![LogItemBean::default()];
+        let mut results = Vec::new();
+        let result = TraceAnalysis::new().analyse_trace_list(Path::new("traces.txt"), &mut beans, &mut results, None);
+        assert!(matches!(result, Err(Error::Malformed { .. })));
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_parse_log_writes_no_placeholder_summary() {
+        let dir = std::env::temp_dir().join(format!("parse_log_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("anr_2024-08-16-10-01-30"), "").unwrap();
+        parse_log(&dir, &[]);
+        let written = dir.join("summary.txt").exists();
+        let _ = fs::remove_dir_all(&dir);
+        assert!(!written);
+    }
+}
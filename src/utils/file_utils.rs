@@ -1,8 +1,7 @@
 use std::ffi::OsStr;
-use std::fs::{self, File};
-use std::io::{self, BufWriter, Write};
+use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use zip::ZipArchive;
 
 // 获取日志文件
 pub fn get_log_file(file_path: &str) -> Option<PathBuf> {
@@ -12,13 +11,7 @@ pub fn get_log_file(file_path: &str) -> Option<PathBuf> {
 
     match get_file_type(file_path) {
         0 => get_logfile_from_directory(Path::new(file_path)),
-        1 => {
-            if let Some(unzip_dir) = up_zip_file(file_path) {
-                get_logfile_from_directory(&unzip_dir)
-            } else {
-                None
-            }
-        }
+        1 => Some(PathBuf::from(file_path)), // 压缩包由 Bugreport 直接在内存中读取，不再解压到磁盘
         2 => None, // RAR 文件暂不支持
         _ => Some(PathBuf::from(file_path)),
     }
@@ -76,37 +69,8 @@ fn get_logfile_from_directory(folder: &Path) -> Option<PathBuf> {
     None
 }
 
-// 解压 ZIP 文件
-fn up_zip_file(zip_file_path: &str) -> Option<PathBuf> {
-    let zip_file = Path::new(zip_file_path);
-    let dest_dir = zip_file.parent().unwrap().join("upZipLogFolder");
-
-    if dest_dir.exists() {
-        fs::remove_dir_all(&dest_dir).ok()?;
-    }
-    fs::create_dir_all(&dest_dir).ok()?;
-
-    if let Ok(file) = File::open(zip_file) {
-        if let Ok(mut archive) = ZipArchive::new(file) {
-            for i in 0..archive.len() {
-                if let Ok(mut file) = archive.by_index(i) {
-                    let out_path = dest_dir.join(file.name());
-                    if let Some(parent) = out_path.parent() {
-                        fs::create_dir_all(parent).ok()?;
-                    }
-                    if let Ok(mut out_file) = File::create(&out_path) {
-                        io::copy(&mut file, &mut out_file).ok()?;
-                    }
-                }
-            }
-            return Some(dest_dir);
-        }
-    }
-    None
-}
-
 // 写入一行到文件
-pub fn write_line_to_file(line: &str, writer: &mut BufWriter<File>) -> io::Result<()> {
+pub fn write_line_to_file(line: &str, writer: &mut impl Write) -> io::Result<()> {
     if !is_empty(line) {
         writeln!(writer, "{}", line)?;
         writer.flush()?;